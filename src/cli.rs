@@ -1,4 +1,7 @@
-use clap::{Parser, Subcommand};
+use clap::{ArgGroup, CommandFactory, Parser, Subcommand, ValueEnum};
+use std::ffi::OsString;
+use std::net::IpAddr;
+use std::time::Duration;
 
 /// runbeam: Rust-based CLI
 ///
@@ -6,7 +9,13 @@ use clap::{Parser, Subcommand};
 /// -v / -vv / -vvv to increase verbosity
 /// -q to reduce output
 #[derive(Debug, Parser)]
-#[command(name = "runbeam", version, about = "Runbeam command-line interface", long_about = None)]
+#[command(
+    name = "runbeam",
+    version,
+    about = "Runbeam command-line interface",
+    long_about = None,
+    after_help = "Namespaced commands may also be written with a space, e.g. `runbeam harmony list` for `runbeam harmony:list`."
+)]
 pub struct Cli {
     /// Increase output verbosity (-v, -vv, -vvv)
     #[arg(short = 'v', long = "verbose", action = clap::ArgAction::Count)]
@@ -16,26 +25,478 @@ pub struct Cli {
     #[arg(short = 'q', long = "quiet", global = true)]
     pub quiet: bool,
 
+    /// Read the legacy plaintext auth file without migrating or deleting it
+    #[arg(long = "no-migrate", global = true)]
+    pub no_migrate: bool,
+
+    /// Output format for command results [default: table on a terminal, json when piped]
+    #[arg(short = 'o', long = "output", global = true, value_enum)]
+    pub output: Option<OutputFormat>,
+
+    /// Print JSON output on a single line instead of pretty-printed
+    #[arg(long = "compact", global = true)]
+    pub compact: bool,
+
+    /// Print how long each network call and the whole command took
+    #[arg(long = "timings", global = true)]
+    pub timings: bool,
+
+    /// Send all HTTP requests through this proxy (hosts in NO_PROXY are still bypassed)
+    #[arg(
+        long = "proxy",
+        value_name = "URL",
+        global = true,
+        conflicts_with = "no_proxy"
+    )]
+    pub proxy: Option<String>,
+
+    /// Never use a proxy, ignoring HTTP_PROXY/HTTPS_PROXY
+    #[arg(long = "no-proxy", global = true)]
+    pub no_proxy: bool,
+
+    /// Fail fast on anything that needs the network instead of timing out
+    #[arg(long = "offline", visible_alias = "no-network", global = true)]
+    pub offline: bool,
+
+    /// Resolve HOST to IP instead of using DNS, like curl's --resolve (repeatable, "HOST:IP")
+    #[arg(long = "resolve", value_name = "HOST:IP", global = true, value_parser = parse_resolve)]
+    pub resolve: Vec<(String, IpAddr)>,
+
+    /// Retries after a connection error, timeout or HTTP 5xx [default: retries config key, else 2]
+    #[arg(long = "retries", value_name = "N", global = true)]
+    pub retries: Option<u32>,
+
+    /// Pause before the first retry, doubled for each further one (e.g. 500ms, 2s)
+    #[arg(long = "retry-delay", value_name = "DURATION", global = true, value_parser = parse_duration)]
+    pub retry_delay: Option<Duration>,
+
+    /// Request ID sent as X-Request-Id on every request [default: a new UUID per run]
+    #[arg(long = "trace-id", value_name = "ID", global = true, value_parser = parse_trace_id)]
+    pub trace_id: Option<String>,
+
+    /// Reach Harmony instances through this SSH jump host ("[user@]host"; needs the ssh-tunnel feature)
+    #[arg(long = "connect-via", value_name = "USER@HOST", global = true, value_parser = parse_connect_via)]
+    pub connect_via: Option<String>,
+
+    /// Answer "yes" to every confirmation prompt (for scripts)
+    #[arg(short = 'y', long = "yes", global = true)]
+    pub yes: bool,
+
+    /// Abort the command if it runs longer than this (e.g. 500ms, 30s, 2m; bare numbers are seconds)
+    #[arg(long = "max-time", value_name = "DURATION", global = true, value_parser = parse_duration)]
+    pub max_time: Option<Duration>,
+
     #[command(subcommand)]
     pub command: Option<Command>,
 }
 
+/// Whether subcommand `name` was invoked with any of its own arguments on the command line
+///
+/// Defaults and global flags (such as `--verbose`) do not count.
+pub fn subcommand_has_args(matches: &clap::ArgMatches, name: &str) -> bool {
+    let Some((invoked, sub_matches)) = matches.subcommand() else {
+        return false;
+    };
+    let cmd = Cli::command();
+    let Some(sub) = cmd.find_subcommand(name).filter(|_| invoked == name) else {
+        return false;
+    };
+    sub.get_arguments().any(|arg| {
+        sub_matches.value_source(arg.get_id().as_str())
+            == Some(clap::parser::ValueSource::CommandLine)
+    })
+}
+
+/// Rewrite `runbeam harmony list ...` into the canonical `runbeam harmony:list ...`
+///
+/// The first positional argument is joined with the one after it when the
+/// resulting `group:name` is a known command. Anything else is returned unchanged,
+/// so the colon form keeps working and clap reports errors as usual.
+pub fn join_spaced_subcommand(mut args: Vec<OsString>) -> Vec<OsString> {
+    let cmd = Cli::command();
+    let takes_value = |matches: &dyn Fn(&clap::Arg) -> bool| {
+        cmd.get_arguments()
+            .any(|a| matches(a) && a.get_action().takes_values())
+    };
+
+    let mut i = 1;
+    while i < args.len() {
+        let Some(arg) = args[i].to_str() else {
+            return args;
+        };
+        if arg == "--" {
+            return args;
+        }
+        if let Some(long) = arg.strip_prefix("--") {
+            if !long.contains('=') && takes_value(&|a| a.get_long() == Some(long)) {
+                i += 1;
+            }
+        } else if let Some(short) = arg.strip_prefix('-') {
+            let mut chars = short.chars();
+            if let (Some(c), None) = (chars.next(), chars.next())
+                && takes_value(&|a| a.get_short() == Some(c))
+            {
+                i += 1;
+            }
+        } else {
+            let Some(next) = args.get(i + 1).and_then(|n| n.to_str()) else {
+                return args;
+            };
+            let joined = format!("{}:{}", arg, next);
+            if cmd.find_subcommand(&joined).is_some() {
+                args.splice(i..i + 2, [OsString::from(joined)]);
+            }
+            return args;
+        }
+        i += 1;
+    }
+    args
+}
+
+/// Parse the label given to a new or renamed instance; a blank label could not be
+/// selected with `--label` later
+pub fn parse_label(value: &str) -> Result<String, String> {
+    if value.trim().is_empty() {
+        return Err("the label must not be empty or only whitespace".to_string());
+    }
+    Ok(value.to_string())
+}
+
+/// Parse a `--resolve` override such as `harmony.internal:10.0.0.5`
+///
+/// IPv6 addresses may be given bare or in brackets (`host:[::1]`). The port is not
+/// part of the override; requests keep the port from their URL.
+pub fn parse_resolve(value: &str) -> Result<(String, IpAddr), String> {
+    let invalid = || {
+        format!(
+            "invalid --resolve '{}': expected HOST:IP, e.g. harmony.internal:10.0.0.5",
+            value
+        )
+    };
+    let (host, ip) = value.trim().split_once(':').ok_or_else(invalid)?;
+    let ip = ip
+        .strip_prefix('[')
+        .and_then(|ip| ip.strip_suffix(']'))
+        .unwrap_or(ip);
+    if host.is_empty() {
+        return Err(invalid());
+    }
+    let ip: IpAddr = ip.parse().map_err(|_| invalid())?;
+    Ok((host.to_ascii_lowercase(), ip))
+}
+
+/// Most instances a single `harmony:add --port-range` may register
+pub const MAX_PORT_RANGE: u32 = 100;
+
+/// Parse a `--port-range` such as `8081-8084` (both ends included)
+pub fn parse_port_range(value: &str) -> Result<(u16, u16), String> {
+    let invalid = || {
+        format!(
+            "invalid --port-range '{}': expected START-END with ports from 1 to 65535, e.g. 8081-8084",
+            value
+        )
+    };
+    let (start, end) = value.trim().split_once('-').ok_or_else(invalid)?;
+    let start: u16 = start.trim().parse().map_err(|_| invalid())?;
+    let end: u16 = end.trim().parse().map_err(|_| invalid())?;
+    if start == 0 || end < start {
+        return Err(invalid());
+    }
+    let count = u32::from(end - start) + 1;
+    if count > MAX_PORT_RANGE {
+        return Err(format!(
+            "--port-range '{}' covers {} ports; at most {} instances can be added at once",
+            value, count, MAX_PORT_RANGE
+        ));
+    }
+    Ok((start, end))
+}
+
+/// Parse a `--trace-id`, which must be usable verbatim as an HTTP header value
+pub fn parse_trace_id(value: &str) -> Result<String, String> {
+    if value.is_empty() || value.len() > 128 || !value.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err(format!(
+            "invalid --trace-id '{}': expected 1-128 printable ASCII characters without spaces",
+            value
+        ));
+    }
+    Ok(value.to_string())
+}
+
+/// Parse a `--connect-via` jump host, which is handed to `ssh` as its destination
+pub fn parse_connect_via(value: &str) -> Result<String, String> {
+    if value.is_empty() || value.starts_with('-') || value.chars().any(char::is_whitespace) {
+        return Err(format!(
+            "invalid --connect-via '{}': expected an SSH destination such as ops@bastion.example.com",
+            value
+        ));
+    }
+    Ok(value.to_string())
+}
+
+/// Parse a duration flag such as `500ms`, `30s`, `2m` or `1.5s`
+///
+/// A bare number is taken as seconds, so existing scripts using `--timeout 30` keep working.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let invalid = || {
+        format!(
+            "invalid duration '{}': expected a number with an optional unit (ms, s, m), e.g. 500ms, 30s, 2m",
+            value
+        )
+    };
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let seconds = match unit.trim() {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        _ => return Err(invalid()),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
+
+/// Format a duration in the units [`parse_duration`] accepts, to the millisecond:
+/// `500ms` below one second, else seconds such as `30s` or `1.5s`
+pub fn format_duration(d: Duration) -> String {
+    let ms = d.as_millis();
+    if ms > 0 && ms < 1000 {
+        format!("{}ms", ms)
+    } else {
+        format!("{}s", ms as f64 / 1000.0)
+    }
+}
+
+// Examples shown by `--help` (but not `-h`) for the commands with the most flag combinations
+
+const HARMONY_ADD_EXAMPLES: &str = "\
+Examples:
+  # Local instance on the default address (127.0.0.1:8081, prefix \"admin\")
+  runbeam harmony:add -l local
+
+  # Remote instance, checked before it is saved
+  runbeam harmony:add -i 10.0.0.5 -p 9090 -l prod-eu --test
+
+  # Management API behind a gateway that needs its own header
+  runbeam harmony:add -i 10.0.0.5 -p 8443 -x mgmt -l edge -H \"X-Api-Key:secret\"
+
+  # Four local instances on consecutive ports, labelled dev-8081 .. dev-8084
+  runbeam harmony:add --port-range 8081-8084 -l dev";
+
+const HARMONY_REMOVE_EXAMPLES: &str = "\
+Examples:
+  runbeam harmony:remove -l prod-eu
+  runbeam harmony:remove --id 1a2b3c4d
+  runbeam harmony:remove -i 10.0.0.5 -p 9090
+
+  # Remove every instance that does not answer within 5 seconds (asks first)
+  runbeam harmony:remove --stale --stale-timeout 5s";
+
+const HARMONY_AUTHORIZE_EXAMPLES: &str = "\
+Examples:
+  # Log in first, then authorize by label
+  runbeam login
+  runbeam harmony:authorize -l prod-eu
+
+  # Also print the machine token, to configure Harmony by hand
+  runbeam harmony:authorize --id 1a2b3c4d --print-token
+
+  # Structured result for deployment scripts
+  runbeam harmony:authorize -l prod-eu -o json";
+
+const CONFIG_SET_EXAMPLES: &str = "\
+Examples:
+  runbeam config:set api-url https://api.runbeam.com
+
+  # Refuse to save an API URL that does not answer
+  runbeam config:set api-url https://staging.runbeam.com --check
+
+  runbeam config:set clock-skew-secs 120
+
+  # Register new instances over TLS unless harmony:add --scheme says otherwise
+  runbeam config:set default-scheme https";
+
+/// `harmony:group` actions
+#[derive(Subcommand, Debug)]
+pub enum GroupAction {
+    /// Create an empty group
+    Create {
+        /// Group name (letters, digits, '-', '_' or '.')
+        name: String,
+    },
+    /// Delete a group; its instances stay registered
+    Delete {
+        /// Group name
+        name: String,
+    },
+    /// Add an instance to a group
+    Add {
+        /// Group name
+        name: String,
+        /// Select instance by short ID
+        #[arg(
+            long = "id",
+            conflicts_with = "label",
+            required_unless_present = "label"
+        )]
+        id: Option<String>,
+        /// Select instance by label
+        #[arg(short = 'l', long = "label", conflicts_with = "id")]
+        label: Option<String>,
+    },
+    /// Remove an instance from a group (by --id even if it is no longer registered)
+    Remove {
+        /// Group name
+        name: String,
+        /// Select instance by short ID
+        #[arg(
+            long = "id",
+            conflicts_with = "label",
+            required_unless_present = "label"
+        )]
+        id: Option<String>,
+        /// Select instance by label
+        #[arg(short = 'l', long = "label", conflicts_with = "id")]
+        label: Option<String>,
+    },
+    /// List groups, or the instances in one group
+    List {
+        /// Only show this group's instances
+        name: Option<String>,
+    },
+}
+
+/// Field to sort `harmony:routes` by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RouteSort {
+    #[value(name = "path")]
+    Path,
+    #[value(name = "pipeline")]
+    Pipeline,
+    #[value(name = "endpoint_name")]
+    EndpointName,
+    #[value(name = "service_type")]
+    ServiceType,
+}
+
+impl RouteSort {
+    /// The route field this key sorts by
+    pub fn field(self) -> &'static str {
+        match self {
+            RouteSort::Path => "path",
+            RouteSort::Pipeline => "pipeline",
+            RouteSort::EndpointName => "endpoint_name",
+            RouteSort::ServiceType => "service_type",
+        }
+    }
+}
+
+/// Output format selected with the global `--output` flag
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable tables and reports
+    Table,
+    /// Machine-readable JSON
+    Json,
+    /// Tables as GitHub-flavored Markdown, for pasting into issues and runbooks
+    Markdown,
+}
+
+/// The output format to use when `--output` is not given
+///
+/// `RUNBEAM_OUTPUT` (`table`, `json` or `markdown`) takes precedence; otherwise tables are used
+/// on a terminal and JSON when stdout is piped or redirected.
+pub fn resolve_output(
+    explicit: Option<OutputFormat>,
+    env: Option<&str>,
+    stdout_is_terminal: bool,
+) -> Result<OutputFormat, String> {
+    if let Some(format) = explicit {
+        return Ok(format);
+    }
+    match env.map(str::trim).filter(|v| !v.is_empty()) {
+        Some(value) => OutputFormat::from_str(value, true).map_err(|_| {
+            format!(
+                "invalid RUNBEAM_OUTPUT '{}': expected 'table', 'json' or 'markdown'",
+                value
+            )
+        }),
+        None if stdout_is_terminal => Ok(OutputFormat::Table),
+        None => Ok(OutputFormat::Json),
+    }
+}
+
 #[derive(Debug, Subcommand)]
 pub enum Command {
     /// List available commands
     List,
 
     /// Log in to Runbeam via browser authentication
-    Login,
+    Login {
+        /// Scope the token to this team (ID or name) when you belong to several
+        #[arg(long = "team", value_name = "ID_OR_NAME")]
+        team: Option<String>,
+        /// Print progress as NDJSON events (started, pending, authenticated, error) instead of text
+        #[arg(long = "events")]
+        events: bool,
+        /// How often to check whether the browser login finished, 1s to 30s (default 5s)
+        #[arg(long = "poll-interval", value_name = "DURATION", value_parser = parse_duration)]
+        poll_interval: Option<Duration>,
+    },
 
     /// Log out and clear stored authentication
     Logout,
 
     /// Verify the stored authentication token
-    Verify,
+    Verify {
+        /// Give up on fetching the signing keys (JWKS) after this long, e.g. 3s (at most 10s per attempt)
+        #[arg(long = "timeout", value_name = "DURATION", value_parser = parse_duration)]
+        timeout: Option<Duration>,
+    },
+
+    /// Exchange the stored token for a fresh one
+    #[command(name = "auth:refresh")]
+    AuthRefresh,
+
+    /// Show which token storage backend is in use and whether it works
+    #[command(name = "auth:backend")]
+    AuthBackend,
+
+    /// Decode the stored token's header and payload locally (no network)
+    #[command(name = "auth:token")]
+    #[command(group = ArgGroup::new("show").args(["decode", "raw"]).required(true).multiple(true))]
+    AuthToken {
+        /// Show the decoded header and payload and the signature length
+        #[arg(long = "decode")]
+        decode: bool,
+        /// Also print the raw token (it grants access to your account)
+        #[arg(long = "raw")]
+        raw: bool,
+    },
+
+    /// Print the stored authentication token (for use with curl or other Runbeam tools)
+    #[command(name = "token:export", visible_alias = "token:cat")]
+    TokenExport {
+        /// Print as an `Authorization: Bearer <token>` header line
+        #[arg(long = "header")]
+        header: bool,
+        /// Omit the trailing newline, for tools that read the token from stdin verbatim
+        #[arg(short = 'n', long = "no-newline")]
+        no_newline: bool,
+    },
+
+    /// Diagnose common problems with the local setup
+    Doctor {
+        /// Take corrective action where it is safe to do so
+        #[arg(long = "fix")]
+        fix: bool,
+    },
 
     /// Add a new Harmony instance via the management API
-    #[command(name = "harmony:add")]
+    #[command(name = "harmony:add", after_long_help = HARMONY_ADD_EXAMPLES)]
     HarmonyAdd {
         /// IP address of the instance
         #[arg(short = 'i', long = "ip", default_value = "127.0.0.1")]
@@ -43,23 +504,62 @@ pub enum Command {
         /// Port of the instance
         #[arg(short = 'p', long = "port", default_value_t = 8081)]
         port: u16,
+        /// Register one instance per port in this range (e.g. 8081-8084), labelled LABEL-<port>
+        #[arg(long = "port-range", value_name = "START-END", value_parser = parse_port_range, conflicts_with = "port")]
+        port_range: Option<(u16, u16)>,
         /// Internal label; defaults to "ip:port" if not provided
-        #[arg(short = 'l', long = "label")]
+        #[arg(short = 'l', long = "label", value_parser = parse_label)]
         label: Option<String>,
         /// Path prefix for the management API (e.g. "admin")
         #[arg(short = 'x', long = "path-prefix", default_value = "admin")]
         path_prefix: String,
+        /// Scheme of the management API (http or https); defaults to the default-scheme config key
+        #[arg(long = "scheme", value_name = "SCHEME")]
+        scheme: Option<String>,
         /// Base64-encoded encryption key for token storage (optional)
         #[arg(short = 'k', long = "key")]
         encryption_key: Option<String>,
+        /// Request header sent with every management API call (repeatable, "NAME:VALUE")
+        #[arg(short = 'H', long = "header", value_name = "NAME:VALUE")]
+        headers: Vec<String>,
+        /// Check that the instance answers GET /{prefix}/info before saving
+        #[arg(long = "test")]
+        test: bool,
+        /// Save even if --test fails, and skip the duplicate-address warning
+        #[arg(long = "force")]
+        force: bool,
+        /// Inherit path prefix, scheme, headers and tags from this instance ID
+        #[arg(long = "clone-from", value_name = "ID", conflicts_with = "path_prefix")]
+        clone_from: Option<String>,
     },
 
     /// List registered Harmony instances
     #[command(name = "harmony:list")]
-    HarmonyList,
+    HarmonyList {
+        /// Only list instances carrying this tag
+        #[arg(short = 't', long = "tag")]
+        tag: Option<String>,
+        /// Output JSON, including each instance's management base_url, instead of a table
+        #[arg(long = "json")]
+        json: bool,
+        /// Add an AUTH column with each instance's authorization state (offline, from the stored token expiry)
+        #[arg(long = "check-auth")]
+        check_auth: bool,
+    },
+
+    /// Check the instance registry for problems without contacting any instance
+    #[command(name = "harmony:validate")]
+    HarmonyValidate,
+
+    /// Manage named groups of instances for batch commands (--group)
+    #[command(name = "harmony:group")]
+    HarmonyGroup {
+        #[command(subcommand)]
+        action: GroupAction,
+    },
 
     /// Remove a registered Harmony instance by ID, label, or ip:port
-    #[command(name = "harmony:remove")]
+    #[command(name = "harmony:remove", after_long_help = HARMONY_REMOVE_EXAMPLES)]
     HarmonyRemove {
         /// Remove by ID (conflicts with --label/--ip/--port)
         #[arg(long = "id", conflicts_with_all = ["label", "ip", "port"])]
@@ -73,10 +573,17 @@ pub enum Command {
         /// Remove by port (requires --ip)
         #[arg(short = 'p', long = "port", requires = "ip")]
         port: Option<u16>,
+        /// Probe every instance and remove those that do not respond (asks for confirmation)
+        #[arg(long = "stale", conflicts_with_all = ["id", "label", "ip", "port"])]
+        stale: bool,
+        /// With --stale, how long each instance has to answer (e.g. 500ms, 3s)
+        #[arg(long = "stale-timeout", value_name = "DURATION", default_value = "3s", value_parser = parse_duration, requires = "stale")]
+        stale_timeout: Duration,
     },
 
     /// Call management API: GET /{prefix}/info
     #[command(name = "harmony:info")]
+    #[command(group = ArgGroup::new("batch").args(["all", "group"]))]
     HarmonyInfo {
         /// Select instance by short ID
         #[arg(long = "id", conflicts_with = "label")]
@@ -84,6 +591,39 @@ pub enum Command {
         /// Select instance by label
         #[arg(short = 'l', long = "label", conflicts_with = "id")]
         label: Option<String>,
+        /// Print the unparsed response body (status and content type go to stderr)
+        #[arg(long = "raw-body")]
+        raw_body: bool,
+        /// Fail unless the instance reports at least this Harmony version (e.g. 1.2.0)
+        #[arg(long = "since-version", value_name = "VERSION")]
+        since_version: Option<String>,
+        /// Query every registered instance
+        #[arg(long = "all", conflicts_with_all = ["id", "label"])]
+        all: bool,
+        /// Query every member of this group (see harmony:group)
+        #[arg(long = "group", value_name = "NAME", conflicts_with_all = ["id", "label", "all"])]
+        group: Option<String>,
+        /// With --all or --group, only the instances carrying this tag
+        #[arg(short = 't', long = "tag", requires = "batch")]
+        tag: Option<String>,
+        /// With --all or --group, stop at the first failing instance
+        #[arg(long = "fail-fast", requires = "batch")]
+        fail_fast: bool,
+        /// Only show these table columns, in this order (comma-separated, e.g. "path,methods")
+        #[arg(long = "columns", value_name = "COLS", value_delimiter = ',')]
+        columns: Vec<String>,
+        /// Keep polling /info until Ctrl-C
+        #[arg(long = "watch", conflicts_with_all = ["all", "group", "raw_body", "since_version"])]
+        watch: bool,
+        /// With --watch, time between polls (e.g. 500ms, 5s; bare numbers are seconds)
+        #[arg(long = "interval", value_name = "DURATION", default_value = "2s", value_parser = parse_duration)]
+        interval: Duration,
+        /// With --watch, show only the fields that changed since the previous poll
+        #[arg(long = "diff", requires = "watch")]
+        diff: bool,
+        /// Check /info, /routes, /pipelines and the data-plane root, with latencies
+        #[arg(long = "probe-all-endpoints", conflicts_with_all = ["raw_body", "since_version", "columns", "watch"])]
+        probe_all_endpoints: bool,
     },
 
     /// Call management API: GET /{prefix}/pipelines
@@ -95,6 +635,15 @@ pub enum Command {
         /// Select instance by label
         #[arg(short = 'l', long = "label", conflicts_with = "id")]
         label: Option<String>,
+        /// Print the unparsed response body (status and content type go to stderr)
+        #[arg(long = "raw-body")]
+        raw_body: bool,
+        /// Only show these table columns, in this order (comma-separated, e.g. "path,methods")
+        #[arg(long = "columns", value_name = "COLS", value_delimiter = ',')]
+        columns: Vec<String>,
+        /// Only show the pipeline with this exact name, with its stages as a numbered list
+        #[arg(long = "pipeline", value_name = "NAME", conflicts_with_all = ["raw_body", "columns"])]
+        pipeline: Option<String>,
     },
 
     /// Call management API: GET /{prefix}/routes
@@ -109,10 +658,49 @@ pub enum Command {
         /// Output raw JSON instead of table
         #[arg(long = "json")]
         json: bool,
+        /// Print the unparsed response body (status and content type go to stderr)
+        #[arg(long = "raw-body")]
+        raw_body: bool,
+        /// Only show these table columns, in this order (comma-separated, e.g. "path,methods")
+        #[arg(long = "columns", value_name = "COLS", value_delimiter = ',')]
+        columns: Vec<String>,
+        /// With JSON output, write one route per line (NDJSON) instead of a single document
+        #[arg(long = "stream")]
+        stream: bool,
+        /// Compare live routes with a snapshot saved by --json; exits non-zero on differences
+        #[arg(long = "diff", value_name = "FILE", conflicts_with_all = ["raw_body", "stream"])]
+        diff: Option<String>,
+        /// Show only routes with this exact endpoint_name, one key/value table per route
+        #[arg(long = "endpoint", value_name = "NAME", conflicts_with_all = ["raw_body", "stream", "diff", "columns"])]
+        endpoint: Option<String>,
+        /// Sort routes by this field (case-insensitive, stable) before rendering
+        #[arg(long = "sort", value_name = "FIELD", value_enum, conflicts_with_all = ["raw_body", "diff"])]
+        sort: Option<RouteSort>,
+        /// With --sort, sort in descending order
+        #[arg(long = "reverse", requires = "sort")]
+        reverse: bool,
+        /// Print only the number of routes (after --endpoint), or {"count": N} as JSON
+        #[arg(long = "count-only", conflicts_with_all = ["raw_body", "columns", "stream", "diff", "sort"])]
+        count_only: bool,
+    },
+
+    /// Capture /info, /routes and /pipelines in one JSON document (for bug reports)
+    #[command(name = "harmony:snapshot")]
+    HarmonySnapshot {
+        /// Select instance by short ID
+        #[arg(long = "id", conflicts_with = "label")]
+        id: Option<String>,
+        /// Select instance by label
+        #[arg(short = 'l', long = "label", conflicts_with = "id")]
+        label: Option<String>,
+        /// Write the snapshot to this file instead of stdout
+        #[arg(long = "out", value_name = "FILE")]
+        out: Option<String>,
     },
 
     /// Reload the Harmony instance configuration
     #[command(name = "harmony:reload")]
+    #[command(group = ArgGroup::new("batch").args(["all", "group"]))]
     HarmonyReload {
         /// Select instance by short ID
         #[arg(long = "id", conflicts_with = "label")]
@@ -120,10 +708,45 @@ pub enum Command {
         /// Select instance by label
         #[arg(short = 'l', long = "label", conflicts_with = "id")]
         label: Option<String>,
+        /// Reload every registered instance
+        #[arg(long = "all", conflicts_with_all = ["id", "label"])]
+        all: bool,
+        /// Reload every member of this group (see harmony:group)
+        #[arg(long = "group", value_name = "NAME", conflicts_with_all = ["id", "label", "all"])]
+        group: Option<String>,
+        /// With --all or --group, only the instances carrying this tag
+        #[arg(short = 't', long = "tag", requires = "batch")]
+        tag: Option<String>,
+        /// With --all or --group, stop at the first failing instance
+        #[arg(long = "fail-fast", requires = "batch")]
+        fail_fast: bool,
+        /// Poll /info until the instance reports the new configuration
+        #[arg(long = "wait")]
+        wait: bool,
+        /// With --wait, give up after this long (e.g. 500ms, 30s, 2m)
+        #[arg(long = "wait-timeout", value_name = "DURATION", default_value = "60s", value_parser = parse_duration, requires = "wait")]
+        wait_timeout: Duration,
+    },
+
+    /// Wait until a Harmony instance answers GET /{prefix}/info
+    #[command(name = "harmony:wait")]
+    HarmonyWait {
+        /// Select instance by short ID
+        #[arg(long = "id", conflicts_with = "label")]
+        id: Option<String>,
+        /// Select instance by label
+        #[arg(short = 'l', long = "label", conflicts_with = "id")]
+        label: Option<String>,
+        /// Give up after this long (e.g. 500ms, 30s, 2m; bare numbers are seconds)
+        #[arg(long = "timeout", value_name = "DURATION", default_value = "60s", value_parser = parse_duration)]
+        timeout: Duration,
+        /// Time between health checks (same units as --timeout)
+        #[arg(long = "interval", value_name = "DURATION", default_value = "2s", value_parser = parse_duration)]
+        interval: Duration,
     },
 
     /// Authorize a Harmony instance to communicate with Runbeam Cloud
-    #[command(name = "harmony:authorize")]
+    #[command(name = "harmony:authorize", after_long_help = HARMONY_AUTHORIZE_EXAMPLES)]
     HarmonyAuthorize {
         /// Select instance by short ID
         #[arg(long = "id", conflicts_with = "label")]
@@ -131,6 +754,12 @@ pub enum Command {
         /// Select instance by label
         #[arg(short = 'l', long = "label", conflicts_with = "id")]
         label: Option<String>,
+        /// Also print the machine token so it can be configured in Harmony manually
+        #[arg(long = "print-token")]
+        print_token: bool,
+        /// Re-authorize even if the instance already holds a machine token that is still valid
+        #[arg(long = "force")]
+        force: bool,
     },
 
     /// Set or update the encryption key for a Harmony instance
@@ -160,8 +789,101 @@ pub enum Command {
         id: String,
     },
 
+    /// Store a request header sent with every management API call to an instance
+    #[command(name = "harmony:set-header")]
+    HarmonySetHeader {
+        /// Harmony instance ID
+        #[arg(long = "id")]
+        id: String,
+        /// Header to store, as "NAME:VALUE"
+        #[arg(short = 'H', long = "header", value_name = "NAME:VALUE")]
+        header: String,
+    },
+
+    /// Remove a stored request header from an instance
+    #[command(name = "harmony:unset-header")]
+    HarmonyUnsetHeader {
+        /// Harmony instance ID
+        #[arg(long = "id")]
+        id: String,
+        /// Header name to remove (case-insensitive)
+        #[arg(short = 'n', long = "name")]
+        name: String,
+    },
+
+    /// Change the management API path prefix of a registered instance (its ID is kept)
+    #[command(name = "harmony:set-prefix")]
+    HarmonySetPrefix {
+        /// Harmony instance ID
+        #[arg(long = "id")]
+        id: String,
+        /// New path prefix (e.g. "admin"; "" for the root)
+        prefix: String,
+    },
+
+    /// Change the URL scheme (http or https) of a registered instance (its ID is kept)
+    #[command(name = "harmony:set-scheme")]
+    HarmonySetScheme {
+        /// Harmony instance ID
+        #[arg(long = "id")]
+        id: String,
+        /// New scheme: "http" or "https"
+        scheme: String,
+    },
+
+    /// Duplicate a registered Harmony instance under a new label
+    #[command(name = "harmony:copy")]
+    HarmonyCopy {
+        /// ID or label of the instance to copy
+        #[arg(long = "from", value_name = "ID_OR_LABEL")]
+        from_id_or_label: String,
+        /// Label for the new instance
+        #[arg(short = 'l', long = "label", value_parser = parse_label)]
+        new_label: String,
+        /// IP address for the new instance (defaults to the source's)
+        #[arg(short = 'i', long = "ip")]
+        ip: Option<String>,
+        /// Port for the new instance (defaults to the source's)
+        #[arg(short = 'p', long = "port")]
+        port: Option<u16>,
+    },
+
+    /// Add tags to a registered Harmony instance
+    #[command(name = "harmony:tag")]
+    HarmonyTag {
+        /// Harmony instance ID
+        #[arg(long = "id")]
+        id: String,
+        /// Tags to add
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+
+    /// Remove tags from a registered Harmony instance
+    #[command(name = "harmony:untag")]
+    HarmonyUntag {
+        /// Harmony instance ID
+        #[arg(long = "id")]
+        id: String,
+        /// Tags to remove
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+
+    /// Change the label of a registered Harmony instance (its ID is kept)
+    #[command(name = "harmony:rename-by-label")]
+    HarmonyRenameByLabel {
+        /// Current label of the instance
+        #[arg(short = 'l', long = "label")]
+        label: String,
+        /// New label to assign
+        #[arg(long = "to", value_parser = parse_label)]
+        to: String,
+    },
+
     /// Trigger Harmony to upload its configuration to Runbeam Cloud
     #[command(name = "harmony:update")]
+    #[command(group = ArgGroup::new("batch").args(["all", "group"]))]
     HarmonyUpdate {
         /// Select instance by short ID
         #[arg(long = "id", conflicts_with = "label")]
@@ -169,6 +891,18 @@ pub enum Command {
         /// Select instance by label
         #[arg(short = 'l', long = "label", conflicts_with = "id")]
         label: Option<String>,
+        /// Update every authorized instance
+        #[arg(long = "all", conflicts_with_all = ["id", "label"])]
+        all: bool,
+        /// Update every authorized member of this group (see harmony:group)
+        #[arg(long = "group", value_name = "NAME", conflicts_with_all = ["id", "label", "all"])]
+        group: Option<String>,
+        /// With --all or --group, only the instances carrying this tag
+        #[arg(short = 't', long = "tag", requires = "batch")]
+        tag: Option<String>,
+        /// With --all or --group, stop at the first failing instance
+        #[arg(long = "fail-fast", requires = "batch")]
+        fail_fast: bool,
     },
 
     /// Test browser opening (development only)
@@ -176,12 +910,18 @@ pub enum Command {
     TestBrowser,
 
     /// Set a configuration value
-    #[command(name = "config:set")]
+    #[command(name = "config:set", after_long_help = CONFIG_SET_EXAMPLES)]
     ConfigSet {
         /// Configuration key (e.g., "api-url")
         key: String,
         /// Configuration value
         value: String,
+        /// Check that the API URL is reachable before saving (api-url only)
+        #[arg(long = "check")]
+        check: bool,
+        /// Save even if --check fails
+        #[arg(long = "force", requires = "check")]
+        force: bool,
     },
 
     /// Get a configuration value or show all config
@@ -189,6 +929,12 @@ pub enum Command {
     ConfigGet {
         /// Configuration key (optional, shows all if not provided)
         key: Option<String>,
+        /// Show which recognized environment variables are set (values masked)
+        #[arg(long = "env", conflicts_with = "key")]
+        env: bool,
+        /// With --env, show the values instead of masking them
+        #[arg(long = "reveal", requires = "env")]
+        reveal: bool,
     },
 
     /// Unset a configuration value
@@ -197,4 +943,12 @@ pub enum Command {
         /// Configuration key to unset
         key: String,
     },
+
+    /// List all valid configuration keys with their current values
+    #[command(name = "config:list-keys")]
+    ConfigListKeys,
+
+    /// Swap the config file with the backup taken by the last config:set / config:unset
+    #[command(name = "config:restore")]
+    ConfigRestore,
 }
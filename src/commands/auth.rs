@@ -1,12 +1,25 @@
 use anyhow::{Context, Result};
-use runbeam_sdk::{RunbeamClient, UserInfo, validate_jwt_token as sdk_validate_jwt};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use runbeam_sdk::{
+    JwtClaims, RunbeamClient, RunbeamError, TeamInfo, UserInfo,
+    validate_jwt_token as sdk_validate_jwt,
+};
 use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tracing::{debug, info, warn};
 
+use crate::cli::{self, OutputFormat};
 use crate::commands::config;
+use crate::http;
+use crate::output;
+use crate::retry::{self, RetryOn, Retryable};
 use crate::storage::{self, CliAuth};
+use crate::style::{self, Color};
+use crate::timings;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct StartLoginResponse {
@@ -21,13 +34,28 @@ struct CheckLoginResponse {
     #[serde(default)]
     token: Option<String>,
     #[serde(default)]
-    expires_in: Option<i64>,
+    expires_in: Option<f64>,
     #[serde(default)]
     user: Option<UserInfo>,
     #[serde(default)]
     message: Option<String>,
 }
 
+/// Body of `POST /api/cli/start-login`, sent only when `--team` is given
+#[derive(Debug, Serialize)]
+struct StartLoginRequest<'a> {
+    team: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct RefreshResponse {
+    token: String,
+    #[serde(default)]
+    expires_in: Option<f64>,
+    #[serde(default)]
+    user: Option<UserInfo>,
+}
+
 #[derive(Debug, Deserialize)]
 #[allow(dead_code)]
 struct HarmonyErrorResponse {
@@ -35,29 +63,142 @@ struct HarmonyErrorResponse {
     message: String,
 }
 
+/// Progress of `login --events`, printed one JSON object per line
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum LoginEvent<'a> {
+    Started {
+        verification_url: &'a str,
+        expires_in_seconds: f64,
+    },
+    Pending,
+    Authenticated {
+        user: Option<&'a UserInfo>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// Where login progress goes: decorated text, or NDJSON events with `--events`
+struct LoginReporter {
+    events: bool,
+}
+
+impl LoginReporter {
+    /// Print a line of human-readable progress (suppressed with `--events`)
+    fn say(&self, line: impl std::fmt::Display) {
+        if !self.events {
+            println!("{}", line);
+        }
+    }
+
+    /// Print an event line (only with `--events`)
+    fn emit(&self, event: &LoginEvent) {
+        if self.events
+            && let Ok(line) = serde_json::to_string(event)
+        {
+            println!("{}", line);
+            std::io::Write::flush(&mut std::io::stdout()).ok();
+        }
+    }
+}
+
 /// Get the API base URL from config, environment, or use default
 fn api_base_url() -> Result<String> {
     config::get_api_url()
 }
 
+/// Unix timestamp `seconds` from now, for `expires_in` fields
+fn expires_at_from_now(seconds: f64) -> i64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs_f64();
+    (now + seconds).round() as i64
+}
+
+/// Whether a token's team claim matches a `--team` selector (ID, or name ignoring case)
+fn team_matches(team: &TeamInfo, selector: &str) -> bool {
+    let selector = selector.trim();
+    team.id == selector || team.name.eq_ignore_ascii_case(selector)
+}
+
+/// Warn when the token is not scoped to the team requested with `--team`
+///
+/// Older APIs ignore the parameter, so this never fails the login.
+fn check_selected_team(out: &LoginReporter, claims: &JwtClaims, selector: &str) {
+    match &claims.team {
+        Some(team) if team_matches(team, selector) => {
+            out.say(format_args!("   Team: {} ({})", team.name, team.id));
+        }
+        Some(team) => {
+            warn!(requested = selector, team = %team.id, "token scoped to a different team");
+            out.say(format_args!(
+                "   ⚠  Requested team '{}', but the token is scoped to {} ({})",
+                selector, team.name, team.id
+            ));
+        }
+        None => {
+            warn!(requested = selector, "token has no team claim");
+            out.say(format_args!(
+                "   ⚠  Requested team '{}', but the token has no team; the server may not support --team",
+                selector
+            ));
+        }
+    }
+}
+
 /// Perform the login flow: start login, open browser, poll for completion
-pub fn login() -> Result<()> {
+///
+/// `team` is forwarded to `start-login` so the token is scoped to that team.
+/// With `events`, progress is printed as NDJSON [`LoginEvent`]s instead of text.
+/// `poll_interval` (`--poll-interval`) overrides `RUNBEAM_LOGIN_POLL_INTERVAL`.
+pub fn login(team: Option<&str>, events: bool, poll_interval: Option<Duration>) -> Result<()> {
+    let out = LoginReporter { events };
+    let poll_interval = login_poll_interval(poll_interval);
+    let result = run_login(team, poll_interval, &out);
+    if let Err(e) = &result {
+        out.emit(&LoginEvent::Error {
+            message: format!("{:#}", e),
+        });
+    }
+    result
+}
+
+fn run_login(team: Option<&str>, poll_interval: Duration, out: &LoginReporter) -> Result<()> {
     info!("Starting CLI login process...");
+    http::require_network()?;
 
     // Check if already logged in with a valid token
     if let Some(existing_auth) = storage::load_auth()? {
         // Verify the token is still valid
-        let validation_result = tokio::runtime::Runtime::new()
-            .expect("Failed to create Tokio runtime")
-            .block_on(sdk_validate_jwt(&existing_auth.token, 24));
-
-        if validation_result.is_ok() {
-            println!("✓ Already logged in with a valid token.");
-            println!("  Run `runbeam logout` first if you want to login with a different account.");
+        let validation_result = validate_token(&existing_auth.token);
+
+        let other_team = match (&validation_result, team) {
+            (Ok(claims), Some(selector)) => !claims
+                .team
+                .as_ref()
+                .is_some_and(|t| team_matches(t, selector)),
+            _ => false,
+        };
+
+        if other_team {
+            out.say(format_args!(
+                "ℹ️  Existing token is not scoped to team '{}'. Logging in again...",
+                team.unwrap_or_default()
+            ));
+            debug!("Valid token for another team found, proceeding with login");
+        } else if validation_result.is_ok() {
+            out.say("✓ Already logged in with a valid token.");
+            out.emit(&LoginEvent::Authenticated {
+                user: existing_auth.user.as_ref(),
+            });
+            out.say("  Run `runbeam logout` first if you want to login with a different account.");
             debug!("Valid token found, skipping login");
             return Ok(());
         } else {
-            println!("ℹ️  Existing token is invalid or expired. Logging in again...");
+            out.say("ℹ️  Existing token is invalid or expired. Logging in again...");
             debug!("Invalid/expired token found, proceeding with login");
         }
     }
@@ -68,11 +209,18 @@ pub fn login() -> Result<()> {
 
     debug!("Requesting device token from {}", start_url);
 
-    let client = reqwest::blocking::Client::new();
-    let response = client
-        .post(&start_url)
-        .send()
-        .with_context(|| format!("failed to connect to {}", start_url))?;
+    let client = http::blocking_client()?;
+    let phase = format!("POST {}", start_url);
+    let response = timings::time(&phase, || {
+        retry::with_retries(&retry::policy_for(&reqwest::Method::POST), &phase, || {
+            let mut request = client.post(&start_url);
+            if let Some(team) = team {
+                request = request.json(&StartLoginRequest { team });
+            }
+            request.send()
+        })
+    })
+    .with_context(|| format!("failed to connect to {}", start_url))?;
 
     if !response.status().is_success() {
         anyhow::bail!(
@@ -92,54 +240,69 @@ pub fn login() -> Result<()> {
     );
 
     // Step 2: Open the browser (detached to avoid capturing browser output)
-    println!("\n🔐 Opening browser for authentication...");
+    out.say("\n🔐 Opening browser for authentication...");
 
     match open::that_detached(&start_data.verification_url) {
         Ok(_) => {
-            println!("   Browser opened successfully.");
-            println!(
+            out.say("   Browser opened successfully.");
+            out.say(format_args!(
                 "   If the browser didn't open, visit: {}",
                 start_data.verification_url
-            );
-            println!();
+            ));
+            out.say("");
         }
         Err(e) => {
             warn!("Could not open browser automatically: {}", e);
-            println!("\n⚠  Could not open browser automatically.");
-            println!("   Please open this URL manually in your browser:");
-            println!("   {}\n", start_data.verification_url);
+            out.say("\n⚠  Could not open browser automatically.");
+            out.say("   Please open this URL manually in your browser:");
+            out.say(format_args!("   {}\n", start_data.verification_url));
         }
     }
 
     // Step 3: Poll for authentication
-    println!("⏳ Waiting for authentication in browser...");
+    out.say("⏳ Waiting for authentication in browser...");
 
     if start_data.expires_in_seconds <= 0.0 {
         anyhow::bail!("Device token has already expired. Please try again.");
     }
 
-    println!(
+    out.say(format_args!(
         "   (This will timeout in {} seconds)",
         start_data.expires_in_seconds.round()
-    );
-    println!();
+    ));
+    out.say("");
 
     let check_url = format!(
         "{}/api/cli/check-login/{}",
         base_url, start_data.device_token
     );
-    let poll_interval = Duration::from_secs(5);
-    let max_attempts = ((start_data.expires_in_seconds as u64) / poll_interval.as_secs()) + 2;
+    out.emit(&LoginEvent::Started {
+        verification_url: &start_data.verification_url,
+        expires_in_seconds: start_data.expires_in_seconds,
+    });
+
+    debug!("Polling every {:?}", poll_interval);
+    let max_attempts = max_poll_attempts(start_data.expires_in_seconds, poll_interval);
+
+    install_login_interrupt_handler();
+    LOGIN_POLLING.store(true, Ordering::SeqCst);
 
     for attempt in 1..=max_attempts {
         debug!("Polling attempt {} of {}", attempt, max_attempts);
 
-        thread::sleep(poll_interval);
+        if sleep_unless_cancelled(poll_interval) {
+            out.say("");
+            out.say("Login cancelled. Run `runbeam login` to try again.");
+            out.emit(&LoginEvent::Error {
+                message: "login cancelled".to_string(),
+            });
+            std::process::exit(130);
+        }
 
-        let response = client
-            .get(&check_url)
-            .send()
-            .with_context(|| "failed to check login status".to_string())?;
+        let response = timings::time(format!("GET {}", check_url), || {
+            client.get(&check_url).send()
+        })
+        .with_context(|| "failed to check login status".to_string())?;
 
         let status_code = response.status();
 
@@ -163,6 +326,8 @@ pub fn login() -> Result<()> {
 
         match check_data.status.as_str() {
             "authenticated" => {
+                LOGIN_POLLING.store(false, Ordering::SeqCst);
+
                 // Success! Extract JWT and metadata
                 let token = check_data
                     .token
@@ -170,13 +335,7 @@ pub fn login() -> Result<()> {
                 let token_clone = token.clone();
 
                 // Calculate expiration timestamp
-                let expires_at = check_data.expires_in.map(|seconds| {
-                    let now = SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs() as i64;
-                    now + seconds
-                });
+                let expires_at = check_data.expires_in.map(expires_at_from_now);
 
                 // User info is already in the correct format (UserInfo from SDK)
                 let user = check_data.user.clone();
@@ -186,37 +345,44 @@ pub fn login() -> Result<()> {
                     expires_at,
                     user: user.clone(),
                 };
-                storage::save_auth(&auth)?;
+                let store = storage::save_auth(&auth)?;
 
-                println!("✅ Authentication successful!");
+                out.say("✅ Authentication successful!");
                 if let Some(user_info) = user {
-                    println!("   Logged in as: {} ({})", user_info.name, user_info.email);
+                    out.say(format_args!(
+                        "   Logged in as: {} ({})",
+                        user_info.name, user_info.email
+                    ));
                 }
-                println!("   Token saved to secure storage");
-                if let Some(exp) = expires_at {
-                    println!(
-                        "   Token expires in {} hours",
-                        (exp - SystemTime::now()
-                            .duration_since(UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs() as i64)
-                            / 3600
-                    );
+                match store.reduced_security_warning() {
+                    None => out.say("   Token saved to secure storage"),
+                    Some(warning) => eprintln!("⚠️  {}", warning),
+                }
+                if let Some(seconds) = check_data.expires_in {
+                    out.say(format_args!(
+                        "   Token expires in {:.0} hours",
+                        seconds / 3600.0
+                    ));
                 }
 
+                out.emit(&LoginEvent::Authenticated {
+                    user: auth.user.as_ref(),
+                });
+
                 // Verify the token using SDK (RS256 with JWKS)
-                let validation_result = tokio::runtime::Runtime::new()
-                    .expect("Failed to create Tokio runtime")
-                    .block_on(sdk_validate_jwt(&token_clone, 24));
+                let validation_result = validate_token(&token_clone);
 
                 match validation_result {
                     Ok(jwt_claims) => {
                         debug!("JWT verification successful: iss={}", jwt_claims.iss);
-                        println!("   Token verified using RS256 ✓");
+                        out.say("   Token verified using RS256 ✓");
+                        if let Some(selector) = team {
+                            check_selected_team(out, &jwt_claims, selector);
+                        }
                     }
                     Err(e) => {
                         warn!("JWT verification failed: {}", e);
-                        println!("   ⚠  Token verification failed: {}", e);
+                        out.say(format_args!("   ⚠  Token verification failed: {}", e));
                     }
                 }
 
@@ -225,8 +391,12 @@ pub fn login() -> Result<()> {
             }
             "pending" => {
                 // Still waiting
-                print!(".");
-                std::io::Write::flush(&mut std::io::stdout()).ok();
+                if out.events {
+                    out.emit(&LoginEvent::Pending);
+                } else {
+                    print!(".");
+                    std::io::Write::flush(&mut std::io::stdout()).ok();
+                }
                 continue;
             }
             "expired" => {
@@ -246,10 +416,397 @@ pub fn login() -> Result<()> {
         }
     }
 
-    println!();
+    out.say("");
     anyhow::bail!("Authentication timed out. Please run `runbeam login` again.");
 }
 
+/// Default pause between `check-login` polls
+const DEFAULT_LOGIN_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Bounds for `--poll-interval` / `RUNBEAM_LOGIN_POLL_INTERVAL`
+const MIN_LOGIN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_LOGIN_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Poll interval from the flag, else `RUNBEAM_LOGIN_POLL_INTERVAL`, else the default
+fn login_poll_interval(flag: Option<Duration>) -> Duration {
+    let env = std::env::var("RUNBEAM_LOGIN_POLL_INTERVAL").ok();
+    resolve_poll_interval(flag, env.as_deref())
+}
+
+/// Pick and clamp the poll interval; an unparseable env value is ignored with a warning
+fn resolve_poll_interval(flag: Option<Duration>, env: Option<&str>) -> Duration {
+    let chosen = flag.or_else(|| {
+        let raw = env?.trim();
+        match crate::cli::parse_duration(raw) {
+            Ok(interval) => Some(interval),
+            Err(e) => {
+                warn!("ignoring RUNBEAM_LOGIN_POLL_INTERVAL: {}", e);
+                None
+            }
+        }
+    });
+    let Some(interval) = chosen else {
+        return DEFAULT_LOGIN_POLL_INTERVAL;
+    };
+    let clamped = interval.clamp(MIN_LOGIN_POLL_INTERVAL, MAX_LOGIN_POLL_INTERVAL);
+    if clamped != interval {
+        warn!(
+            "login poll interval {} is outside {}..={}; using {}",
+            cli::format_duration(interval),
+            cli::format_duration(MIN_LOGIN_POLL_INTERVAL),
+            cli::format_duration(MAX_LOGIN_POLL_INTERVAL),
+            cli::format_duration(clamped)
+        );
+    }
+    clamped
+}
+
+/// The shared retry policy, with `RUNBEAM_JWKS_RETRIES` / `RUNBEAM_JWKS_RETRY_DELAY_MS`
+/// overriding its retry count and delay for JWKS fetches
+fn jwks_retry_policy() -> retry::RetryPolicy {
+    let env = |name: &str| {
+        std::env::var(name)
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+    };
+    let mut policy = retry::policy();
+    if let Some(retries) = env("RUNBEAM_JWKS_RETRIES") {
+        policy.max_retries = u32::try_from(retries).unwrap_or(u32::MAX);
+    }
+    if let Some(ms) = env("RUNBEAM_JWKS_RETRY_DELAY_MS") {
+        policy.base_delay = Duration::from_millis(ms);
+    }
+    policy
+}
+
+/// JWKS cache duration when `RUNBEAM_JWKS_TTL` is not set (one hour)
+const DEFAULT_JWKS_TTL_SECS: u64 = 3600;
+
+/// The JWKS cache duration in seconds: `RUNBEAM_JWKS_TTL`, or one hour when it is not set
+pub fn jwks_ttl_secs() -> Result<u64> {
+    match std::env::var("RUNBEAM_JWKS_TTL") {
+        Ok(v) => v
+            .trim()
+            .parse()
+            .with_context(|| format!("RUNBEAM_JWKS_TTL must be a number of seconds, got '{}'", v)),
+        Err(_) => Ok(DEFAULT_JWKS_TTL_SECS),
+    }
+}
+
+/// How long runbeam-sdk may cache the JWKS: [`jwks_ttl_secs`], rounded up to the
+/// whole hours the SDK accepts (`0` fetches it for every validation)
+fn jwks_cache_hours() -> u64 {
+    let secs = jwks_ttl_secs().unwrap_or_else(|e| {
+        warn!("{:#}; using {}s", e, DEFAULT_JWKS_TTL_SECS);
+        DEFAULT_JWKS_TTL_SECS
+    });
+    ttl_hours(secs)
+}
+
+fn ttl_hours(secs: u64) -> u64 {
+    secs.div_ceil(3600)
+}
+
+/// The kind of JWKS fetch failure (connection error, timeout or 5xx) behind a
+/// validation error, or `None` when the token itself was rejected
+///
+/// runbeam-sdk reports every JWKS problem as a `JwtValidation` message, so this and
+/// [`missing_signing_key`] match its wording; `test_jwks_error_wording_matches_sdk`
+/// runs the SDK to catch a change when it is upgraded.
+fn jwks_failure(err: &RunbeamError) -> Option<RetryOn> {
+    let RunbeamError::JwtValidation(msg) = err else {
+        return None;
+    };
+    if msg.starts_with("JWKS endpoint timeout") {
+        Some(RetryOn::Timeout)
+    } else if msg.starts_with("Failed to connect to JWKS endpoint")
+        || msg.starts_with("Network error fetching JWKS")
+    {
+        Some(RetryOn::Connect)
+    } else if msg.starts_with("JWKS endpoint returned HTTP 5") {
+        Some(RetryOn::ServerError)
+    } else {
+        None
+    }
+}
+
+/// The key ID a validation error says the JWKS did not contain, if that is why it failed
+///
+/// An empty key set during rotation fails the same way.
+fn missing_signing_key(err: &RunbeamError) -> Option<String> {
+    let RunbeamError::JwtValidation(msg) = err else {
+        return None;
+    };
+    let rest = msg.strip_prefix("Key ID '")?;
+    let (kid, rest) = rest.split_once('\'')?;
+    rest.starts_with(" not found in JWKS")
+        .then(|| kid.to_string())
+}
+
+impl Retryable for Result<JwtClaims, RunbeamError> {
+    fn failure(&self) -> Option<RetryOn> {
+        self.as_ref().err().and_then(jwks_failure)
+    }
+}
+
+/// Validate a token with the SDK (RS256 with JWKS), retrying transient JWKS fetch failures
+///
+/// The SDK fetches the JWKS with its own client, without the run's `X-Request-Id`.
+///
+/// The SDK does not check `nbf`, so it is enforced here with the
+/// `clock-skew-secs` leeway once the signature has been verified.
+fn validate_token(token: &str) -> Result<JwtClaims, RunbeamError> {
+    validate_token_within(token, None)
+}
+
+/// [`validate_token`] with an overall deadline covering the JWKS fetch and its retries
+///
+/// The SDK gives each fetch a fixed 10s timeout of its own, so `timeout` can shorten
+/// the wait but a single attempt never runs longer than that.
+fn validate_token_within(
+    token: &str,
+    timeout: Option<Duration>,
+) -> Result<JwtClaims, RunbeamError> {
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+    let deadline = timeout.map(|t| tokio::time::Instant::now() + t);
+    let policy = jwks_retry_policy();
+    let cache_hours = jwks_cache_hours();
+    let validate = || {
+        retry::with_retries(&policy, "JWKS fetch", || {
+            timings::time("validate token (JWKS)", || match deadline {
+                None => runtime.block_on(sdk_validate_jwt(token, cache_hours)),
+                Some(deadline) => runtime.block_on(async {
+                    tokio::time::timeout_at(deadline, sdk_validate_jwt(token, cache_hours))
+                        .await
+                        .unwrap_or_else(|_| Err(jwks_timeout_error(token, timeout)))
+                }),
+            })
+        })
+    };
+
+    // A key set fetched mid-rotation may be empty or not list the new key yet. The SDK
+    // does not cache a set that lacked the key, so one more attempt fetches it afresh.
+    let claims = match validate() {
+        Ok(claims) => claims,
+        Err(e) => {
+            let Some(kid) = missing_signing_key(&e) else {
+                return Err(e);
+            };
+            warn!(
+                "signing key {} is not in the JWKS; fetching the keys again in {}",
+                kid,
+                cli::format_duration(policy.base_delay)
+            );
+            std::thread::sleep(policy.base_delay);
+            validate().map_err(|e| match missing_signing_key(&e) {
+                Some(kid) => RunbeamError::JwtValidation(format!(
+                    "signing key {} not found even after refresh; the token may be from a different environment",
+                    kid
+                )),
+                None => e,
+            })?
+        }
+    };
+
+    let leeway = config::get_clock_skew_secs().unwrap_or_else(|e| {
+        warn!("could not read clock-skew-secs, using default: {:#}", e);
+        config::DEFAULT_CLOCK_SKEW_SECS
+    });
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    check_not_before(token_not_before(token), now, leeway)?;
+    Ok(claims)
+}
+
+/// The claims of a JWT, read from its payload without verification
+///
+/// Only use this for claims the SDK does not expose, after the token has been validated.
+fn token_payload(token: &str) -> Option<serde_json::Value> {
+    let payload = token.split('.').nth(1)?;
+    let bytes = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// The API base URL that issued `token`, read from its `iss` claim without verification
+fn token_issuer(token: &str) -> Option<String> {
+    let payload = token_payload(token)?;
+    // Let the SDK reduce the issuer to its origin, exactly as it does before fetching
+    let claims = JwtClaims {
+        iss: payload.get("iss")?.as_str()?.to_string(),
+        sub: String::new(),
+        aud: None,
+        exp: 0,
+        iat: 0,
+        user: None,
+        team: None,
+    };
+    Some(claims.api_base_url())
+}
+
+/// The JWKS endpoint the SDK fetches to validate `token`, derived from its issuer
+fn jwks_url(token: &str) -> Option<String> {
+    Some(format!(
+        "{}/api/.well-known/jwks.json",
+        token_issuer(token)?
+    ))
+}
+
+/// The warning for a token issued by a different API than `api_url`, if it was
+fn issuer_mismatch(issuer: &str, api_url: &str) -> Option<String> {
+    let normalize = |url: &str| url.trim().trim_end_matches('/').to_ascii_lowercase();
+    (normalize(issuer) != normalize(api_url)).then(|| {
+        format!(
+            "Your stored token was issued by {} but your api-url is {}; run `runbeam login`.",
+            issuer, api_url
+        )
+    })
+}
+
+/// Warn on stderr, before the token is used, when it came from another API than the
+/// configured `api-url` (e.g. after switching environments), since the failures that
+/// follow are otherwise confusing
+///
+/// Decoded offline; silent under `--quiet` or when the issuer cannot be read.
+fn warn_on_issuer_mismatch(token: &str) {
+    if output::quiet() {
+        return;
+    }
+    let Some(issuer) = token_issuer(token) else {
+        return;
+    };
+    match api_base_url() {
+        Ok(api_url) => {
+            if let Some(message) = issuer_mismatch(&issuer, &api_url) {
+                eprintln!("⚠️  {}", message);
+            }
+        }
+        Err(e) => debug!("could not read api-url for the issuer check: {:#}", e),
+    }
+}
+
+fn jwks_timeout_error(token: &str, timeout: Option<Duration>) -> RunbeamError {
+    let url = jwks_url(token).unwrap_or_else(|| "the JWKS endpoint".to_string());
+    RunbeamError::JwtValidation(format!(
+        "timed out after {} fetching signing keys from {}",
+        cli::format_duration(timeout.unwrap_or_default()),
+        url
+    ))
+}
+
+/// The `nbf` claim of a JWT
+fn token_not_before(token: &str) -> Option<i64> {
+    token_payload(token)?.get("nbf")?.as_i64()
+}
+
+/// Ability needed to exchange a user token for a gateway token (`harmony:authorize`)
+const AUTHORIZE_ABILITY: &str = "harmony:authorize";
+
+/// The abilities granted to a token: its `abilities` or `scopes` array, or a
+/// space-separated `scope` string. `None` when the token lists none of them.
+fn token_abilities(token: &str) -> Option<Vec<String>> {
+    let claims = token_payload(token)?;
+    if let Some(list) = claims
+        .get("abilities")
+        .or_else(|| claims.get("scopes"))
+        .and_then(|v| v.as_array())
+    {
+        return Some(
+            list.iter()
+                .filter_map(|a| a.as_str().map(str::to_string))
+                .collect(),
+        );
+    }
+    claims
+        .get("scope")
+        .and_then(|v| v.as_str())
+        .map(|scope| scope.split_whitespace().map(str::to_string).collect())
+}
+
+/// Whether `abilities` grants `required`, directly or through `*` / `group:*`
+fn has_ability(abilities: &[String], required: &str) -> bool {
+    abilities.iter().any(|ability| {
+        ability == required
+            || ability == "*"
+            || ability
+                .strip_suffix('*')
+                .is_some_and(|prefix| prefix.ends_with(':') && required.starts_with(prefix))
+    })
+}
+
+/// Fail early when the token lists its abilities and `required` is not among them
+///
+/// Tokens that carry no ability claims are left for the server to judge.
+fn require_ability(token: &str, required: &str) -> Result<()> {
+    let Some(abilities) = token_abilities(token) else {
+        debug!("token lists no abilities; not checking '{}'", required);
+        return Ok(());
+    };
+    if !has_ability(&abilities, required) {
+        anyhow::bail!(
+            "this operation requires the '{}' ability; your token has [{}]",
+            required,
+            abilities.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Reject a token whose `nbf` is more than `leeway` seconds after `now`
+fn check_not_before(nbf: Option<i64>, now: i64, leeway: u64) -> Result<(), RunbeamError> {
+    match nbf {
+        Some(nbf) if nbf > now.saturating_add(leeway as i64) => {
+            Err(RunbeamError::JwtValidation(format!(
+                "Token is not valid yet: nbf is {}s in the future (clock skew tolerance is {}s; see `runbeam config:set clock-skew-secs`)",
+                nbf - now,
+                leeway
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Set while the login poll loop is running, so Ctrl-C cancels the poll
+static LOGIN_POLLING: AtomicBool = AtomicBool::new(false);
+
+/// Set by the Ctrl-C handler when the user interrupts login polling
+static LOGIN_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Install a Ctrl-C handler that cancels login polling gracefully
+///
+/// Outside of polling the handler exits immediately with the conventional
+/// SIGINT status, matching the default behaviour.
+fn install_login_interrupt_handler() {
+    let result = ctrlc::set_handler(|| {
+        if LOGIN_POLLING.load(Ordering::SeqCst) {
+            LOGIN_CANCELLED.store(true, Ordering::SeqCst);
+        } else {
+            std::process::exit(130);
+        }
+    });
+    if let Err(e) = result {
+        debug!("Could not install Ctrl-C handler: {}", e);
+    }
+}
+
+/// Sleep for `duration`, waking early if login was cancelled
+///
+/// Returns `true` if the user pressed Ctrl-C.
+fn sleep_unless_cancelled(duration: Duration) -> bool {
+    let deadline = Instant::now() + duration;
+    loop {
+        if LOGIN_CANCELLED.load(Ordering::SeqCst) {
+            return true;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return false;
+        }
+        thread::sleep((deadline - now).min(Duration::from_millis(100)));
+    }
+}
+
 /// Log out by removing the stored token
 pub fn logout() -> Result<()> {
     info!("Logging out...");
@@ -268,43 +825,219 @@ pub fn logout() -> Result<()> {
     Ok(())
 }
 
-/// Authorize a Harmony instance to communicate with Runbeam Cloud
-pub fn authorize_harmony(instance_id: Option<&str>, instance_label: Option<&str>) -> Result<()> {
-    info!("Starting Harmony instance authorization...");
+/// Report which token storage backend is active and whether each one works
+pub fn storage_backend(preference: storage::BackendPreference, output: OutputFormat) -> Result<()> {
+    let report = storage::check_token_backends(preference)?;
+    let status = |probe: &std::result::Result<(), String>| match probe {
+        Ok(()) => "healthy".to_string(),
+        Err(e) => format!("unavailable ({})", e),
+    };
+    let keyring_status = match &report.keyring {
+        Some(probe) => status(probe),
+        None => "disabled".to_string(),
+    };
 
-    // Load user authentication token
-    let auth = storage::load_auth()?.context("Not logged in. Please run `runbeam login` first.")?;
+    if output == OutputFormat::Json {
+        let json = serde_json::json!({
+            "preference": report.preference.as_str(),
+            "active": report.active,
+            "fallback_reason": report.fallback_reason,
+            "keyring": {
+                "enabled": report.keyring.is_some(),
+                "healthy": matches!(report.keyring, Some(Ok(()))),
+                "error": report.keyring.as_ref().and_then(|p| p.as_ref().err()),
+            },
+            "file": {
+                "path": report.file_path.display().to_string(),
+                "healthy": report.file.is_ok(),
+                "error": report.file.as_ref().err(),
+            },
+            "token_location": report.token_location,
+        });
+        output::print_json(&json)?;
+        return Ok(());
+    }
 
-    // Validate the JWT token before attempting authorization
-    debug!("Validating JWT token before authorization...");
-    let validation_result = tokio::runtime::Runtime::new()
-        .expect("Failed to create Tokio runtime")
-        .block_on(sdk_validate_jwt(&auth.token, 24));
+    let active = match report.active {
+        "keyring" => "OS keyring".to_string(),
+        _ => format!("encrypted file ({})", report.file_path.display()),
+    };
+    println!("Active backend:  {}", active);
+    if let Some(reason) = &report.fallback_reason {
+        println!("   Reason:       {}", reason);
+    }
+    println!("Keyring:         {}", keyring_status);
+    println!("Encrypted file:  {}", status(&report.file));
+    println!(
+        "Stored token:    {}",
+        report.token_location.unwrap_or("none")
+    );
 
-    match validation_result {
+    if report.active == "keyring" && matches!(report.keyring, Some(Err(_))) {
+        println!();
+        println!("⚠️  The keyring is selected but not working, so tokens may not persist.");
+        println!("   Set RUNBEAM_STORAGE_BACKEND=file to use the encrypted file store instead.");
+    }
+    Ok(())
+}
+
+/// `verify` with `--output json`: the validated claims, or `{"valid":false,"error":...}`
+fn verify_token_json(timeout: Option<Duration>) -> Result<()> {
+    let result = storage::load_auth()
+        .and_then(|auth| {
+            auth.context("No authentication token found. Please run `runbeam login` first.")
+        })
+        .and_then(|auth| {
+            warn_on_issuer_mismatch(&auth.token);
+            http::require_network()?;
+            Ok(validate_token_within(&auth.token, timeout)?)
+        });
+
+    match result {
         Ok(claims) => {
-            debug!("Token valid: sub={}, exp={}", claims.sub, claims.exp);
-            // Check if token is about to expire (within 1 hour)
-            let now = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
                 .unwrap()
                 .as_secs() as i64;
-            let time_remaining = claims.exp - now;
-            if time_remaining < 3600 {
-                println!("⚠️  Warning: Your token expires in {} minutes.", time_remaining / 60);
-                println!("   Consider running `runbeam login` to refresh your token.");
-                println!();
-            }
+            output::print_json(&claims_json(&claims, now)?)?;
+            Ok(())
         }
         Err(e) => {
-            println!("❌ Your authentication token is invalid or expired.");
-            println!();
-            println!("Error: {}", e);
-            println!();
-            println!("Please run `runbeam login` to authenticate again.");
-            anyhow::bail!("Token validation failed: {}", e);
+            warn!("Token verification failed: {:#}", e);
+            output::print_json(&serde_json::json!({
+                "valid": false,
+                "error": format!("{:#}", e),
+            }))?;
+            anyhow::bail!("Token verification failed")
         }
     }
+}
+
+/// Validated claims plus `valid` and `expires_in_seconds`, for `verify --output json`
+fn claims_json(claims: &JwtClaims, now: i64) -> Result<serde_json::Value> {
+    let mut value = serde_json::to_value(claims)?;
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("valid".to_string(), true.into());
+        obj.insert("expires_in_seconds".to_string(), (claims.exp - now).into());
+    }
+    Ok(value)
+}
+
+/// Print the stored user token to stdout for use with other tools
+///
+/// The security warning goes to stderr so stdout can be captured directly,
+/// e.g. `TOKEN=$(runbeam token:export)`. The token itself is never logged.
+pub fn export_token(header: bool, no_newline: bool) -> Result<()> {
+    let auth = storage::load_auth()?.context("Not logged in. Please run `runbeam login` first.")?;
+    warn_on_issuer_mismatch(&auth.token);
+
+    eprintln!(
+        "⚠️  This token grants access to your Runbeam account. Do not share it or paste it into logs."
+    );
+    let line = if header {
+        format!("Authorization: Bearer {}", auth.token)
+    } else {
+        auth.token
+    };
+    let mut stdout = std::io::stdout();
+    if no_newline {
+        write!(stdout, "{}", line)?;
+    } else {
+        writeln!(stdout, "{}", line)?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+/// The segments of a JWT, decoded without verifying the signature
+#[derive(Debug, Serialize)]
+struct DecodedToken {
+    header: serde_json::Value,
+    payload: serde_json::Value,
+    signature_bytes: usize,
+}
+
+/// Split a JWT into its three segments and decode them
+fn decode_token(token: &str) -> Result<DecodedToken> {
+    let segments: Vec<&str> = token.trim().split('.').collect();
+    let [header, payload, signature] = segments[..] else {
+        anyhow::bail!(
+            "stored token is not a JWT: expected 3 segments separated by '.', found {}",
+            segments.len()
+        );
+    };
+    let decode = |segment: &str| URL_SAFE_NO_PAD.decode(segment.trim_end_matches('='));
+    let json = |segment: &str, name: &str| -> Result<serde_json::Value> {
+        let bytes = decode(segment).with_context(|| format!("{} is not valid base64url", name))?;
+        serde_json::from_slice(&bytes).with_context(|| format!("{} is not a JSON object", name))
+    };
+    Ok(DecodedToken {
+        header: json(header, "header")?,
+        payload: json(payload, "payload")?,
+        signature_bytes: decode(signature)
+            .context("signature is not valid base64url")?
+            .len(),
+    })
+}
+
+/// `auth:token`: decode the stored token locally and/or print it with `--raw`
+///
+/// Unlike `verify`, nothing is validated or fetched, so this also works offline
+/// and on expired or malformed tokens.
+pub fn show_token(decode: bool, raw: bool, output: OutputFormat) -> Result<()> {
+    let auth = storage::load_auth()?.context("Not logged in. Please run `runbeam login` first.")?;
+    if raw {
+        eprintln!(
+            "⚠️  This token grants access to your Runbeam account. Do not share it or paste it into logs."
+        );
+    }
+    if !decode {
+        println!("{}", auth.token);
+        return Ok(());
+    }
+
+    let decoded = decode_token(&auth.token)?;
+    if output == OutputFormat::Json {
+        let mut doc = serde_json::to_value(&decoded)?;
+        if raw {
+            doc["token"] = auth.token.clone().into();
+        }
+        return Ok(output::print_json(&doc)?);
+    }
+
+    if raw {
+        println!("Token:\n{}\n", auth.token);
+    }
+    println!(
+        "Header:\n{}\n",
+        serde_json::to_string_pretty(&decoded.header)?
+    );
+    println!(
+        "Payload:\n{}\n",
+        serde_json::to_string_pretty(&decoded.payload)?
+    );
+    println!("Signature: {} bytes", decoded.signature_bytes);
+    Ok(())
+}
+
+/// Authorize a Harmony instance to communicate with Runbeam Cloud
+///
+/// When `print_token` is set, the machine token is also printed so it can be
+/// delivered to Harmony by hand (e.g. when the proxy cannot be reached). With
+/// `--output json` a single structured result object is printed instead of the
+/// human-readable report.
+///
+/// An instance whose stored machine token is still well within its lifetime is
+/// left alone unless `force` is set, so re-running the command does not churn tokens.
+pub fn authorize_harmony(
+    instance_id: Option<&str>,
+    instance_label: Option<&str>,
+    print_token: bool,
+    force: bool,
+    output: OutputFormat,
+) -> Result<()> {
+    info!("Starting Harmony instance authorization...");
+    let human = output != OutputFormat::Json;
 
     // Load the Harmony instance from storage
     let instances = storage::load_harmony_instances()?;
@@ -321,58 +1054,129 @@ pub fn authorize_harmony(instance_id: Option<&str>, instance_label: Option<&str>
         "Harmony instance not found. Use `runbeam harmony:list` to see available instances.",
     )?;
 
-    println!(
-        "\n🔐 Authorizing Gateway (Harmony instance): {}",
-        instance.label
-    );
-    println!("   Instance ID: {}", instance.id);
-    println!("   Address: {}:{}", instance.ip, instance.port);
-    println!();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    if !force
+        && let Some(expires_at) = instance.machine_token_expires_at
+        && let Some(days) = authorization_days_left(expires_at, now)
+    {
+        if human {
+            println!(
+                "Already authorized (expires in {} days); use --force to re-authorize",
+                days
+            );
+        } else {
+            output::print_json(&serde_json::json!({
+                "instance_id": instance.id,
+                "already_authorized": true,
+                "expires_at": expires_at,
+                "expires_in_days": days,
+            }))?;
+        }
+        return Ok(());
+    }
+
+    http::require_network()?;
+
+    // Load user authentication token
+    let auth = storage::load_auth()?.context("Not logged in. Please run `runbeam login` first.")?;
+    warn_on_issuer_mismatch(&auth.token);
+
+    // Validate the JWT token before attempting authorization
+    debug!("Validating JWT token before authorization...");
+    let validation_result = validate_token(&auth.token);
+
+    match validation_result {
+        Ok(claims) => {
+            debug!("Token valid: sub={}, exp={}", claims.sub, claims.exp);
+            require_ability(&auth.token, AUTHORIZE_ABILITY)?;
+            // Check if token is about to expire (within 1 hour)
+            let time_remaining = claims.exp - now;
+            if time_remaining < 3600 && human {
+                println!(
+                    "⚠️  Warning: Your token expires in {} minutes.",
+                    time_remaining / 60
+                );
+                println!("   Consider running `runbeam login` to refresh your token.");
+                println!();
+            }
+        }
+        Err(e) => {
+            if human {
+                println!("❌ Your authentication token is invalid or expired.");
+                println!();
+                println!("Error: {}", e);
+                println!();
+                println!("Please run `runbeam login` to authenticate again.");
+            }
+            anyhow::bail!("Token validation failed: {}", e);
+        }
+    }
+
+    if human {
+        println!(
+            "\n🔐 Authorizing Gateway (Harmony instance): {}",
+            instance.label
+        );
+        println!("   Instance ID: {}", instance.id);
+        println!("   Address: {}:{}", instance.ip, instance.port);
+        println!();
+    }
+
+    // Reach the instance before Runbeam Cloud issues a token, so a failing
+    // --connect-via tunnel does not leave an undelivered token behind
+    crate::tunnel::open(instance)?;
 
     // Use SDK's RunbeamClient to authorize the gateway
     // Get API base URL from config
     let api_url = api_base_url()?;
     debug!("Using API URL: {}", api_url);
 
-    // Create SDK client and authorize gateway
+    // Create SDK client and authorize gateway. The SDK sends its own requests, so
+    // this one carries no X-Request-Id (see `http`)
     let client = RunbeamClient::new(api_url);
 
     // Create Tokio runtime for async operations
     let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
 
-    let auth_response = runtime
-        .block_on(client.authorize_gateway(
+    let auth_response = timings::time("authorize gateway (Runbeam Cloud)", || {
+        runtime.block_on(client.authorize_gateway(
             &auth.token,
             &instance.id,
             None, // machine_public_key
             None, // metadata
         ))
-        .context("Failed to authorize gateway with Runbeam Cloud")?;
+    })
+    .context("Failed to authorize gateway with Runbeam Cloud")?;
 
-    println!("✅ Gateway authorized with Runbeam Cloud!");
-    println!();
-    println!(
-        "   Gateway: {} ({})",
-        auth_response.gateway.name, auth_response.gateway.code
-    );
-    println!("   Gateway ID: {}", auth_response.gateway.id);
-    println!("   Machine token expires at: {}", auth_response.expires_at);
+    if human {
+        println!("✅ Gateway authorized with Runbeam Cloud!");
+        println!();
+        println!(
+            "   Gateway: {} ({})",
+            auth_response.gateway.name, auth_response.gateway.code
+        );
+        println!("   Gateway ID: {}", auth_response.gateway.id);
+        println!("   Machine token expires at: {}", auth_response.expires_at);
 
-    // Calculate and display expiry in days
-    let expires_in_days = (auth_response.expires_in / 86400.0).round() as i64;
-    println!("   Machine token expires in {} days", expires_in_days);
+        // Calculate and display expiry in days
+        let expires_in_days = (auth_response.expires_in / 86400.0).round() as i64;
+        println!("   Machine token expires in {} days", expires_in_days);
 
-    if !auth_response.abilities.is_empty() {
-        println!("   Token abilities: {}", auth_response.abilities.join(", "));
-    }
+        if !auth_response.abilities.is_empty() {
+            println!("   Token abilities: {}", auth_response.abilities.join(", "));
+        }
 
-    if let Some(authorized_by) = &auth_response.gateway.authorized_by {
-        println!(
-            "   Authorized by: {} ({})",
-            authorized_by.name, authorized_by.email
-        );
+        if let Some(authorized_by) = &auth_response.gateway.authorized_by {
+            println!(
+                "   Authorized by: {} ({})",
+                authorized_by.name, authorized_by.email
+            );
+        }
+        println!();
     }
-    println!();
 
     info!("Gateway authorized: {}", auth_response.gateway.id);
 
@@ -381,19 +1185,25 @@ pub fn authorize_harmony(instance_id: Option<&str>, instance_label: Option<&str>
     if let Some(stored_instance) = instances.iter_mut().find(|i| i.id == instance.id) {
         stored_instance.gateway_id = Some(auth_response.gateway.id.clone());
         storage::save_harmony_instances(&instances)?;
-        debug!("Stored gateway_id {} for instance {}", auth_response.gateway.id, instance.id);
+        debug!(
+            "Stored gateway_id {} for instance {}",
+            auth_response.gateway.id, instance.id
+        );
     }
 
     // Send machine token to Harmony proxy instance
-    println!(
-        "\n📡 Sending token to Harmony proxy at {}:{}...",
-        instance.ip, instance.port
-    );
+    if human {
+        println!(
+            "\n📡 Sending token to Harmony proxy at {}:{}...",
+            instance.ip, instance.port
+        );
+    }
 
     let harmony_url = format!(
-        "http://{}:{}/{}/token",
-        instance.ip, instance.port, instance.path_prefix
+        "{}/token",
+        crate::commands::harmony::management::base_url(instance)
     );
+    // Never log the payload itself: it carries the machine token
     debug!("Posting token to: {}", harmony_url);
 
     let token_payload = serde_json::json!({
@@ -404,28 +1214,83 @@ pub fn authorize_harmony(instance_id: Option<&str>, instance_label: Option<&str>
         "abilities": auth_response.abilities,
     });
 
-    let http_client = reqwest::Client::new();
-    let post_result: Result<(reqwest::StatusCode, Option<String>), reqwest::Error> = runtime
-        .block_on(async {
-            let response = http_client
-                .post(&harmony_url)
-                .json(&token_payload)
-                .timeout(std::time::Duration::from_secs(5))
-                .send()
-                .await?;
+    let http_client = http::async_client()?;
+    let delivery_phase = format!("POST {}", harmony_url);
+    let post_result: Result<(reqwest::StatusCode, Option<String>), reqwest::Error> =
+        timings::time(&delivery_phase, || {
+            let response = retry::with_retries(
+                &retry::policy_for(&reqwest::Method::POST),
+                &delivery_phase,
+                || {
+                    runtime.block_on(async {
+                        let mut request = http_client.post(&harmony_url);
+                        for (name, value) in &instance.headers {
+                            request = request.header(name, value);
+                        }
+                        request
+                            .json(&token_payload)
+                            .timeout(std::time::Duration::from_secs(5))
+                            .send()
+                            .await
+                    })
+                },
+            )?;
 
             let status = response.status();
             if status.is_success() {
                 Ok((status, None))
             } else {
-                let error_text = response
-                    .text()
-                    .await
+                let error_text = runtime
+                    .block_on(response.text())
                     .unwrap_or_else(|_| "Unknown error".to_string());
                 Ok((status, Some(error_text)))
             }
         });
 
+    // Only a token that reached the instance counts towards the idempotency check
+    if matches!(post_result, Ok((status, _)) if status.is_success()) {
+        storage::set_machine_token_expiry(
+            &instance.id,
+            expires_at_from_now(auth_response.expires_in),
+        )?;
+    }
+
+    let delivery = match &post_result {
+        Ok((status, None)) => serde_json::json!({
+            "delivered": true,
+            "status": status.as_u16(),
+        }),
+        Ok((status, Some(text))) => serde_json::json!({
+            "delivered": false,
+            "status": status.as_u16(),
+            "error": text,
+        }),
+        Err(e) => serde_json::json!({
+            "delivered": false,
+            "error": e.to_string(),
+        }),
+    };
+
+    if !human {
+        let mut result = serde_json::json!({
+            "instance_id": instance.id,
+            "gateway": {
+                "id": auth_response.gateway.id,
+                "code": auth_response.gateway.code,
+                "name": auth_response.gateway.name,
+            },
+            "expires_at": auth_response.expires_at,
+            "expires_in": auth_response.expires_in,
+            "abilities": auth_response.abilities,
+            "delivery": delivery,
+        });
+        if print_token {
+            result["machine_token"] = serde_json::json!(auth_response.machine_token);
+        }
+        output::print_json(&result)?;
+        return Ok(());
+    }
+
     match post_result {
         Ok((status, error_text)) => {
             if status.is_success() {
@@ -434,32 +1299,14 @@ pub fn authorize_harmony(instance_id: Option<&str>, instance_label: Option<&str>
                 println!("🎉 Authorization complete! Harmony is ready to use.");
             } else if status == reqwest::StatusCode::FORBIDDEN {
                 // Handle 403 Forbidden - check if it's the runbeam.enabled issue
-                let is_runbeam_disabled = if let Some(ref text) = error_text {
-                    // Try to parse as JSON error response
-                    if let Ok(error_response) = serde_json::from_str::<HarmonyErrorResponse>(text) {
-                        error_response
-                            .message
-                            .contains("Runbeam Cloud integration is disabled")
-                            || error_response.message.contains("runbeam.enabled")
-                    } else {
-                        // Fallback: check raw text
-                        text.contains("Runbeam Cloud integration is disabled")
-                            || text.contains("runbeam.enabled")
-                    }
-                } else {
-                    false
-                };
+                let is_runbeam_disabled = error_text.as_deref().is_some_and(is_runbeam_disabled);
 
                 if is_runbeam_disabled {
                     println!("⚠️  Harmony proxy rejected the authorization (HTTP 403):");
-                    println!("   Runbeam Cloud integration is disabled on the Harmony instance.");
-                    println!();
-                    println!("   To fix this:");
-                    println!("   1. Edit your Harmony configuration file (config.toml)");
-                    println!("   2. Set: [runbeam]\n      enabled = true");
-                    println!("   3. Restart Harmony and try again:");
-                    println!("      runbeam harmony:authorize --id {}", instance.id);
-                    println!();
+                    print_runbeam_disabled_help(&format!(
+                        "runbeam harmony:authorize --id {}",
+                        instance.id
+                    ));
                     println!(
                         "The gateway is authorized with Runbeam Cloud, but the token could not"
                     );
@@ -504,24 +1351,148 @@ pub fn authorize_harmony(instance_id: Option<&str>, instance_label: Option<&str>
     }
     println!();
 
+    if print_token {
+        print_machine_token(&auth_response.machine_token, &auth_response.expires_at);
+    }
+
+    Ok(())
+}
+
+/// A stored machine token with less than this long left is renewed by `harmony:authorize`
+const REAUTHORIZE_WITHIN_SECS: i64 = 7 * 86400;
+
+/// Whole days left on a machine token expiring at `expires_at`, or `None` if it is
+/// close enough to expiry (or past it) that it should be renewed
+fn authorization_days_left(expires_at: i64, now: i64) -> Option<i64> {
+    let remaining = expires_at - now;
+    (remaining > REAUTHORIZE_WITHIN_SECS).then_some(remaining / 86400)
+}
+
+/// Print a machine token for manual delivery, with a security warning
+fn print_machine_token(machine_token: &str, expires_at: &str) {
+    println!("🔑 Machine token (for manual configuration of Harmony):");
+    println!("   ⚠️  Treat this like a password: anyone holding it can act as this gateway.");
+    println!("   Do not paste it into shared logs, tickets, or chat.");
+    println!();
+    println!("   Token:      {}", machine_token);
+    println!("   Expires at: {}", expires_at);
+    println!();
+}
+
+/// Check whether a Harmony error body indicates that Runbeam Cloud integration is disabled
+///
+/// Accepts either the JSON error envelope returned by Harmony or raw text.
+pub(crate) fn is_runbeam_disabled(error_text: &str) -> bool {
+    let message = match serde_json::from_str::<HarmonyErrorResponse>(error_text) {
+        Ok(error_response) => error_response.message,
+        Err(_) => error_text.to_string(),
+    };
+    message.contains("Runbeam Cloud integration is disabled") || message.contains("runbeam.enabled")
+}
+
+/// Print guidance for enabling Runbeam Cloud integration on a Harmony instance
+///
+/// `retry_command` is the command the user should run once the setting is fixed.
+pub(crate) fn print_runbeam_disabled_help(retry_command: &str) {
+    println!("   Runbeam Cloud integration is disabled on the Harmony instance.");
+    println!();
+    println!("   To fix this:");
+    println!("   1. Edit your Harmony configuration file (config.toml)");
+    println!("   2. Set: [runbeam]\n      enabled = true");
+    println!("   3. Restart Harmony and try again:");
+    println!("      {}", retry_command);
+    println!();
+}
+
+/// Exchange the stored token for a fresh one via `POST /api/cli/refresh`
+pub fn refresh_token() -> Result<()> {
+    info!("Refreshing stored authentication token...");
+
+    let auth = storage::load_auth()?
+        .context("No authentication token found. Please run `runbeam login` first.")?;
+    warn_on_issuer_mismatch(&auth.token);
+
+    let expired_message = "Stored token has expired and can no longer be refreshed. Run `runbeam login` to sign in again.";
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    if auth.expires_at.is_some_and(|exp| exp <= now) {
+        anyhow::bail!(expired_message);
+    }
+
+    let refresh_url = format!("{}/api/cli/refresh", api_base_url()?);
+    let client = http::blocking_client()?;
+    let phase = format!("POST {}", refresh_url);
+    let response = timings::time(&phase, || {
+        retry::with_retries(&retry::policy_for(&reqwest::Method::POST), &phase, || {
+            client.post(&refresh_url).bearer_auth(&auth.token).send()
+        })
+    })
+    .with_context(|| format!("failed to connect to {}", refresh_url))?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED {
+        anyhow::bail!(expired_message);
+    }
+    if !status.is_success() {
+        anyhow::bail!(
+            "Failed to refresh token: HTTP {} - {}",
+            status,
+            response.text().unwrap_or_default()
+        );
+    }
+
+    let data: RefreshResponse = response
+        .json()
+        .context("failed to parse refresh response")?;
+    let expires_at = data.expires_in.map(expires_at_from_now);
+
+    let store = storage::save_auth(&CliAuth {
+        token: data.token,
+        expires_at,
+        user: data.user.or(auth.user),
+    })?;
+
+    match store.reduced_security_warning() {
+        None => println!("✅ Token refreshed and saved to secure storage"),
+        Some(warning) => {
+            println!("✅ Token refreshed");
+            eprintln!("⚠️  {}", warning);
+        }
+    }
+    if let (Some(seconds), Some(exp)) = (data.expires_in, expires_at) {
+        println!(
+            "   New token expires in {:.0} hours (at {}, Unix timestamp)",
+            seconds / 3600.0,
+            exp
+        );
+    }
     Ok(())
 }
 
 /// Verify the stored authentication token
-pub fn verify_token() -> Result<()> {
+pub fn verify_token(timeout: Option<Duration>, output: OutputFormat) -> Result<()> {
+    if output == OutputFormat::Json {
+        return verify_token_json(timeout);
+    }
     info!("Verifying stored authentication token...");
 
     // Load authentication from storage
     let auth = storage::load_auth()?
         .context("No authentication token found. Please run `runbeam login` first.")?;
+    warn_on_issuer_mismatch(&auth.token);
+    // The JWKS fetch happens inside the SDK, which does not use http::blocking_client
+    http::require_network()?;
 
     println!("\n🔐 Verifying JWT token...");
+    if let Some(url) = jwks_url(&auth.token) {
+        println!("   Fetching signing keys from {}...", url);
+    }
     println!();
 
     // Validate the token using SDK (async)
-    let validation_result = tokio::runtime::Runtime::new()
-        .expect("Failed to create Tokio runtime")
-        .block_on(sdk_validate_jwt(&auth.token, 24));
+    let validation_result = validate_token_within(&auth.token, timeout);
 
     match validation_result {
         Ok(claims) => {
@@ -559,21 +1530,35 @@ pub fn verify_token() -> Result<()> {
                 .as_secs() as i64;
             let time_remaining = claims.exp - now;
 
+            let color = style::color_enabled();
+            let status = ExpiryStatus::from_remaining(time_remaining);
+
             if time_remaining > 0 {
                 let hours = time_remaining / 3600;
                 let minutes = (time_remaining % 3600) / 60;
-                println!("Expiration:");
-                println!("  Expires at:   {} (Unix timestamp)", claims.exp);
-                if hours > 24 {
-                    println!("  Time left:    {} days, {} hours", hours / 24, hours % 24);
+                let time_left = if hours > 24 {
+                    format!("{} days, {} hours", hours / 24, hours % 24)
                 } else if hours > 0 {
-                    println!("  Time left:    {} hours, {} minutes", hours, minutes);
+                    format!("{} hours, {} minutes", hours, minutes)
                 } else {
-                    println!("  Time left:    {} minutes", minutes);
-                }
+                    format!("{} minutes", minutes)
+                };
+                println!("Expiration:");
+                println!("  Expires at:   {} (Unix timestamp)", claims.exp);
+                println!(
+                    "  Time left:    {}",
+                    style::paint(&time_left, status.color(), color)
+                );
             } else {
                 println!("⚠️  Warning: Token has expired!");
-                println!("  Expired at:   {} (Unix timestamp)", claims.exp);
+                println!(
+                    "  Expired at:   {}",
+                    style::paint(
+                        &format!("{} (Unix timestamp)", claims.exp),
+                        status.color(),
+                        color
+                    )
+                );
                 println!();
                 println!("Please run `runbeam login` to get a new token.");
             }
@@ -599,10 +1584,197 @@ pub fn verify_token() -> Result<()> {
     }
 }
 
+/// Tokens with less than this many seconds left are shown as expiring soon
+const EXPIRY_WARNING_SECS: i64 = 24 * 3600;
+
+/// Session health derived from a token's remaining lifetime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExpiryStatus {
+    Healthy,
+    ExpiringSoon,
+    Expired,
+}
+
+impl ExpiryStatus {
+    fn from_remaining(seconds: i64) -> Self {
+        if seconds <= 0 {
+            ExpiryStatus::Expired
+        } else if seconds < EXPIRY_WARNING_SECS {
+            ExpiryStatus::ExpiringSoon
+        } else {
+            ExpiryStatus::Healthy
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            ExpiryStatus::Healthy => Color::Green,
+            ExpiryStatus::ExpiringSoon => Color::Yellow,
+            ExpiryStatus::Expired => Color::Red,
+        }
+    }
+}
+
+/// Number of polls needed to cover an `expires_in_seconds` window, plus slack
+///
+/// Fractional windows are rounded up so a short expiry still gets polled.
+fn max_poll_attempts(expires_in_seconds: f64, poll_interval: Duration) -> u64 {
+    (expires_in_seconds / poll_interval.as_secs_f64()).ceil() as u64 + 2
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_jwks_ttl_rounds_up_to_sdk_hours() {
+        assert_eq!(ttl_hours(0), 0);
+        assert_eq!(ttl_hours(1), 1);
+        assert_eq!(ttl_hours(DEFAULT_JWKS_TTL_SECS), 1);
+        assert_eq!(ttl_hours(3601), 2);
+        assert_eq!(ttl_hours(86_400), 24);
+    }
+
+    #[test]
+    fn test_login_event_json() {
+        let line = |event: LoginEvent| serde_json::to_string(&event).unwrap();
+        assert_eq!(
+            line(LoginEvent::Started {
+                verification_url: "https://runbeam.test/verify",
+                expires_in_seconds: 300.0,
+            }),
+            r#"{"event":"started","verification_url":"https://runbeam.test/verify","expires_in_seconds":300.0}"#
+        );
+        assert_eq!(line(LoginEvent::Pending), r#"{"event":"pending"}"#);
+        assert_eq!(
+            line(LoginEvent::Authenticated { user: None }),
+            r#"{"event":"authenticated","user":null}"#
+        );
+        assert_eq!(
+            line(LoginEvent::Error {
+                message: "expired".to_string()
+            }),
+            r#"{"event":"error","message":"expired"}"#
+        );
+    }
+
+    #[test]
+    fn test_expiry_status_thresholds() {
+        assert_eq!(ExpiryStatus::from_remaining(-10), ExpiryStatus::Expired);
+        assert_eq!(ExpiryStatus::from_remaining(0), ExpiryStatus::Expired);
+        assert_eq!(ExpiryStatus::from_remaining(60), ExpiryStatus::ExpiringSoon);
+        assert_eq!(
+            ExpiryStatus::from_remaining(EXPIRY_WARNING_SECS - 1),
+            ExpiryStatus::ExpiringSoon
+        );
+        assert_eq!(
+            ExpiryStatus::from_remaining(EXPIRY_WARNING_SECS),
+            ExpiryStatus::Healthy
+        );
+        assert_eq!(ExpiryStatus::Healthy.color(), Color::Green);
+        assert_eq!(ExpiryStatus::ExpiringSoon.color(), Color::Yellow);
+        assert_eq!(ExpiryStatus::Expired.color(), Color::Red);
+    }
+
+    #[test]
+    fn test_sleep_unless_cancelled() {
+        assert!(!sleep_unless_cancelled(Duration::from_millis(10)));
+
+        LOGIN_CANCELLED.store(true, Ordering::SeqCst);
+        let start = Instant::now();
+        assert!(sleep_unless_cancelled(Duration::from_secs(5)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        LOGIN_CANCELLED.store(false, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_claims_json() {
+        let claims = JwtClaims {
+            iss: "https://api.runbeam.test".to_string(),
+            sub: "u1".to_string(),
+            aud: Some("runbeam-cli".to_string()),
+            exp: 10_003_600,
+            iat: 10_000_000,
+            user: None,
+            team: Some(TeamInfo {
+                id: "t1".to_string(),
+                name: "Ops".to_string(),
+            }),
+        };
+        let value = claims_json(&claims, 10_000_000).unwrap();
+        assert_eq!(value["valid"], true);
+        assert_eq!(value["expires_in_seconds"], 3600);
+        assert_eq!(value["sub"], "u1");
+        assert_eq!(value["aud"], "runbeam-cli");
+        assert_eq!(value["team"]["name"], "Ops");
+        assert!(value["user"].is_null());
+    }
+
+    #[test]
+    fn test_issuer_mismatch() {
+        assert_eq!(
+            issuer_mismatch("https://api.runbeam.io", "https://API.runbeam.io/"),
+            None
+        );
+        assert_eq!(
+            issuer_mismatch("https://api.runbeam.io", "https://staging.runbeam.io").as_deref(),
+            Some(
+                "Your stored token was issued by https://api.runbeam.io but your api-url is https://staging.runbeam.io; run `runbeam login`."
+            )
+        );
+    }
+
+    #[test]
+    fn test_jwks_url_from_issuer() {
+        let token = format!(
+            "e30.{}.sig",
+            URL_SAFE_NO_PAD.encode(r#"{"iss":"https://api.runbeam.test/api/cli/check-login/x"}"#)
+        );
+        assert_eq!(
+            jwks_url(&token).as_deref(),
+            Some("https://api.runbeam.test/api/.well-known/jwks.json")
+        );
+        assert_eq!(jwks_url("not-a-jwt"), None);
+        assert_eq!(
+            jwks_timeout_error(&token, Some(Duration::from_secs(2))).to_string(),
+            "JWT validation failed: timed out after 2s fetching signing keys from https://api.runbeam.test/api/.well-known/jwks.json"
+        );
+    }
+
+    #[test]
+    fn test_resolve_poll_interval() {
+        let secs = Duration::from_secs;
+        assert_eq!(resolve_poll_interval(None, None), secs(5));
+        assert_eq!(resolve_poll_interval(None, Some("10")), secs(10));
+        assert_eq!(resolve_poll_interval(Some(secs(2)), Some("10")), secs(2));
+        assert_eq!(resolve_poll_interval(None, Some("soon")), secs(5));
+        // Clamped to 1..=30 seconds
+        assert_eq!(resolve_poll_interval(None, Some("200ms")), secs(1));
+        assert_eq!(resolve_poll_interval(Some(secs(120)), None), secs(30));
+        // The attempt count follows the chosen interval
+        assert_eq!(max_poll_attempts(300.0, secs(30)), 12);
+    }
+
+    #[test]
+    fn test_max_poll_attempts_fractional_expiry() {
+        let interval = Duration::from_secs(5);
+        assert_eq!(max_poll_attempts(3.5, interval), 3);
+        assert_eq!(max_poll_attempts(0.2, interval), 3);
+        assert_eq!(max_poll_attempts(300.0, interval), 62);
+        assert_eq!(max_poll_attempts(301.5, interval), 63);
+    }
+
+    #[test]
+    fn test_check_login_response_fractional_expires_in() {
+        let json = r#"{"status":"authenticated","token":"t","expires_in":3599.5}"#;
+        let data: CheckLoginResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(data.expires_in, Some(3599.5));
+
+        let json = r#"{"status":"authenticated","token":"t","expires_in":3600}"#;
+        let data: CheckLoginResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(data.expires_in, Some(3600.0));
+    }
+
     #[test]
     fn test_harmony_error_response_parsing() {
         // Test parsing valid JSON error response
@@ -672,4 +1844,220 @@ mod tests {
             || raw_text.contains("runbeam.enabled");
         assert!(is_runbeam_disabled);
     }
+
+    #[test]
+    fn test_is_runbeam_disabled_json_and_raw() {
+        let json = r#"{"error":"Forbidden","message":"Runbeam Cloud integration is disabled. Set runbeam.enabled=true in configuration to use this endpoint."}"#;
+        assert!(is_runbeam_disabled(json));
+        assert!(is_runbeam_disabled("Set runbeam.enabled=true"));
+        assert!(!is_runbeam_disabled(
+            r#"{"error":"Forbidden","message":"Access denied"}"#
+        ));
+        assert!(!is_runbeam_disabled("This is not JSON"));
+    }
+
+    #[test]
+    fn test_missing_signing_key() {
+        let jwt = |msg: &str| RunbeamError::JwtValidation(msg.to_string());
+        assert_eq!(
+            missing_signing_key(&jwt(
+                "Key ID 'k-2024' not found in JWKS from issuer https://api.runbeam.test"
+            ))
+            .as_deref(),
+            Some("k-2024")
+        );
+        assert_eq!(
+            missing_signing_key(&jwt("JWKS endpoint returned HTTP 503")),
+            None
+        );
+        assert_eq!(
+            missing_signing_key(&jwt("Missing 'kid' (key ID) in JWT header")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_jwks_error_wording_matches_sdk() {
+        let token_for = |issuer: &str, kid: &str| {
+            format!(
+                "{}.{}.sig",
+                URL_SAFE_NO_PAD.encode(format!(r#"{{"alg":"RS256","kid":"{}"}}"#, kid)),
+                URL_SAFE_NO_PAD.encode(format!(
+                    r#"{{"iss":"{}","sub":"u1","exp":4102444800,"iat":1}}"#,
+                    issuer
+                )),
+            )
+        };
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let validate = |token: String| runtime.block_on(sdk_validate_jwt(&token, 0)).unwrap_err();
+
+        let mut server = mockito::Server::new();
+        let empty = server
+            .mock("GET", "/api/.well-known/jwks.json")
+            .with_body(r#"{"keys":[]}"#)
+            .create();
+        let err = validate(token_for(&server.url(), "k-new"));
+        assert_eq!(
+            missing_signing_key(&err).as_deref(),
+            Some("k-new"),
+            "{}",
+            err
+        );
+        empty.remove();
+
+        server
+            .mock("GET", "/api/.well-known/jwks.json")
+            .with_status(503)
+            .create();
+        let err = validate(token_for(&server.url(), "k1"));
+        assert_eq!(jwks_failure(&err), Some(RetryOn::ServerError), "{}", err);
+
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let issuer = format!("http://{}", closed.local_addr().unwrap());
+        drop(closed);
+        let err = validate(token_for(&issuer, "k1"));
+        assert_eq!(jwks_failure(&err), Some(RetryOn::Connect), "{}", err);
+    }
+
+    #[test]
+    fn test_transient_jwks_errors_are_retried() {
+        let jwt = |msg: &str| RunbeamError::JwtValidation(msg.to_string());
+        assert_eq!(
+            jwks_failure(&jwt(
+                "Failed to connect to JWKS endpoint: http://x/api/.well-known/jwks.json"
+            )),
+            Some(RetryOn::Connect)
+        );
+        assert_eq!(
+            jwks_failure(&jwt("JWKS endpoint timeout: http://x")),
+            Some(RetryOn::Timeout)
+        );
+        assert_eq!(
+            jwks_failure(&jwt("JWKS endpoint returned HTTP 503")),
+            Some(RetryOn::ServerError)
+        );
+
+        assert_eq!(jwks_failure(&jwt("JWKS endpoint returned HTTP 404")), None);
+        assert_eq!(jwks_failure(&jwt("Invalid JWT header: bad")), None);
+        assert_eq!(
+            jwks_failure(&RunbeamError::Config(
+                "Failed to connect to JWKS endpoint".to_string()
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn test_team_matches_id_or_name() {
+        let team = TeamInfo {
+            id: "team-42".to_string(),
+            name: "Platform Ops".to_string(),
+        };
+        assert!(team_matches(&team, "team-42"));
+        assert!(team_matches(&team, "platform ops"));
+        assert!(team_matches(&team, " Platform Ops "));
+        assert!(!team_matches(&team, "TEAM-42x"));
+        assert!(!team_matches(&team, "Platform"));
+    }
+
+    #[test]
+    fn test_authorization_days_left() {
+        let now = 1_700_000_000;
+        assert_eq!(authorization_days_left(now + 30 * 86400, now), Some(30));
+        assert_eq!(authorization_days_left(now + 8 * 86400 + 5, now), Some(8));
+        assert_eq!(authorization_days_left(now + 7 * 86400, now), None);
+        assert_eq!(authorization_days_left(now - 60, now), None);
+    }
+
+    fn token_with_payload(payload: &str) -> String {
+        format!(
+            "eyJhbGciOiJSUzI1NiJ9.{}.c2ln",
+            URL_SAFE_NO_PAD.encode(payload)
+        )
+    }
+
+    #[test]
+    fn test_decode_token() {
+        // Header {"alg":"RS256"}, signature "sig"
+        let decoded = decode_token(&token_with_payload(r#"{"sub":"u1"}"#)).unwrap();
+        assert_eq!(decoded.header, serde_json::json!({"alg": "RS256"}));
+        assert_eq!(decoded.payload, serde_json::json!({"sub": "u1"}));
+        assert_eq!(decoded.signature_bytes, 3);
+
+        let err = decode_token("a.b").unwrap_err();
+        assert!(err.to_string().contains("found 2"));
+        let err = decode_token("!!.e30.c2ln").unwrap_err();
+        assert_eq!(err.to_string(), "header is not valid base64url");
+    }
+
+    #[test]
+    fn test_token_not_before() {
+        let token = token_with_payload(r#"{"sub":"u1","nbf":1700000000}"#);
+        assert_eq!(token_not_before(&token), Some(1_700_000_000));
+        assert_eq!(
+            token_not_before(&token_with_payload(r#"{"sub":"u1"}"#)),
+            None
+        );
+        assert_eq!(token_not_before("not-a-jwt"), None);
+    }
+
+    #[test]
+    fn test_check_not_before_leeway() {
+        let now = 1_700_000_000;
+        // Missing, past and current nbf are always fine
+        assert!(check_not_before(None, now, 0).is_ok());
+        assert!(check_not_before(Some(now - 10), now, 0).is_ok());
+        assert!(check_not_before(Some(now), now, 0).is_ok());
+        // Near future: accepted within the leeway, rejected beyond it
+        assert!(check_not_before(Some(now + 30), now, 60).is_ok());
+        assert!(check_not_before(Some(now + 60), now, 60).is_ok());
+        let err = check_not_before(Some(now + 90), now, 60).unwrap_err();
+        assert!(matches!(err, RunbeamError::JwtValidation(_)));
+        assert!(err.to_string().contains("nbf is 90s in the future"));
+        assert!(check_not_before(Some(now + 1), now, 0).is_err());
+    }
+
+    #[test]
+    fn test_token_abilities_from_abilities_scopes_or_scope() {
+        let abilities = |payload: &str| token_abilities(&token_with_payload(payload));
+        assert_eq!(
+            abilities(r#"{"abilities":["harmony:authorize","read"]}"#),
+            Some(vec!["harmony:authorize".to_string(), "read".to_string()])
+        );
+        assert_eq!(
+            abilities(r#"{"scopes":["read"]}"#),
+            Some(vec!["read".to_string()])
+        );
+        assert_eq!(
+            abilities(r#"{"scope":"read  write"}"#),
+            Some(vec!["read".to_string(), "write".to_string()])
+        );
+        assert_eq!(abilities(r#"{"sub":"u1"}"#), None);
+    }
+
+    #[test]
+    fn test_has_ability_wildcards() {
+        let list = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(has_ability(
+            &list(&["harmony:authorize"]),
+            "harmony:authorize"
+        ));
+        assert!(has_ability(&list(&["*"]), "harmony:authorize"));
+        assert!(has_ability(&list(&["harmony:*"]), "harmony:authorize"));
+        assert!(!has_ability(&list(&["harmony:read"]), "harmony:authorize"));
+        assert!(!has_ability(&list(&["harm*"]), "harmony:authorize"));
+        assert!(!has_ability(&[], "harmony:authorize"));
+    }
+
+    #[test]
+    fn test_require_ability_message() {
+        let token = token_with_payload(r#"{"abilities":["harmony:read","config:read"]}"#);
+        let err = require_ability(&token, AUTHORIZE_ABILITY).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "this operation requires the 'harmony:authorize' ability; your token has [harmony:read, config:read]"
+        );
+        // No ability claims at all: leave it to the server
+        assert!(require_ability(&token_with_payload(r#"{"sub":"u1"}"#), AUTHORIZE_ABILITY).is_ok());
+    }
 }
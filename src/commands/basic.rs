@@ -1,10 +1,24 @@
 use clap::CommandFactory;
 
+use crate::cli::OutputFormat;
+use crate::output;
+
 /// List available commands (from clap) in a table
-pub fn list_commands() -> anyhow::Result<()> {
+///
+/// With `--output json`, emits a JSON array of `{name, description}` objects instead.
+/// Adding `--verbose` includes each command's arguments (and nested subcommands), giving
+/// wrappers a machine-readable spec of the CLI.
+pub fn list_commands(output: OutputFormat, verbose: bool) -> anyhow::Result<()> {
     let cmd = crate::cli::Cli::command();
     let subs: Vec<_> = cmd.get_subcommands().collect();
 
+    if output == OutputFormat::Json {
+        let commands: Vec<serde_json::Value> =
+            subs.iter().map(|sc| command_spec(sc, verbose)).collect();
+        output::print_json(&commands)?;
+        return Ok(());
+    }
+
     if subs.is_empty() {
         println!("No commands available.");
         return Ok(());
@@ -56,3 +70,96 @@ pub fn list_commands() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// JSON description of a command; with `verbose`, also its arguments and subcommands
+fn command_spec(cmd: &clap::Command, verbose: bool) -> serde_json::Value {
+    let mut spec = serde_json::json!({
+        "name": cmd.get_name(),
+        "description": cmd.get_about().map(|s| s.to_string()).unwrap_or_default(),
+    });
+    if verbose {
+        let args: Vec<serde_json::Value> = cmd
+            .get_arguments()
+            .filter(|a| !a.is_hide_set())
+            .map(arg_spec)
+            .collect();
+        spec["args"] = serde_json::json!(args);
+        let subcommands: Vec<serde_json::Value> = cmd
+            .get_subcommands()
+            .map(|sc| command_spec(sc, verbose))
+            .collect();
+        if !subcommands.is_empty() {
+            spec["subcommands"] = serde_json::json!(subcommands);
+        }
+    }
+    spec
+}
+
+/// JSON description of one argument: how it is spelled, whether it is required and its help
+fn arg_spec(arg: &clap::Arg) -> serde_json::Value {
+    let takes_value = arg.get_num_args().is_some_and(|n| n.takes_values())
+        || (arg.get_num_args().is_none() && arg.get_action().takes_values());
+    serde_json::json!({
+        "name": arg.get_id().as_str(),
+        "short": arg.get_short().map(|c| c.to_string()),
+        "long": arg.get_long(),
+        "positional": arg.is_positional(),
+        "required": arg.is_required_set(),
+        "takes_value": takes_value,
+        "help": arg.get_help().map(|h| h.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find<'a>(cmd: &'a clap::Command, name: &str) -> &'a clap::Command {
+        cmd.get_subcommands()
+            .find(|sc| sc.get_name() == name)
+            .unwrap()
+    }
+
+    fn arg<'a>(spec: &'a serde_json::Value, name: &str) -> &'a serde_json::Value {
+        spec["args"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|a| a["name"] == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_command_spec_omits_args_unless_verbose() {
+        let cli = crate::cli::Cli::command();
+        let spec = command_spec(find(&cli, "harmony:add"), false);
+        assert_eq!(spec["name"], "harmony:add");
+        assert!(spec.get("args").is_none());
+    }
+
+    #[test]
+    fn test_command_spec_describes_args() {
+        let cli = crate::cli::Cli::command();
+        let spec = command_spec(find(&cli, "harmony:authorize"), true);
+        let label = arg(&spec, "label");
+        assert_eq!(label["short"], "l");
+        assert_eq!(label["long"], "label");
+        assert_eq!(label["takes_value"], true);
+        assert_eq!(label["required"], false);
+        assert_eq!(label["positional"], false);
+        let force = arg(&spec, "force");
+        assert_eq!(force["takes_value"], false);
+        assert!(force["short"].is_null());
+
+        let spec = command_spec(find(&cli, "harmony:group"), true);
+        let create = spec["subcommands"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|sc| sc["name"] == "create")
+            .unwrap();
+        let name = arg(create, "name");
+        assert_eq!(name["positional"], true);
+        assert_eq!(name["required"], true);
+    }
+}
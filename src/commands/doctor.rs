@@ -0,0 +1,377 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info};
+
+use crate::cli::OutputFormat;
+use crate::commands::harmony::management;
+use crate::commands::{auth, config};
+use crate::http;
+use crate::output;
+use crate::prompt;
+use crate::storage;
+
+/// Timeout used when probing registered Harmony instances
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Checks that read files in the data directory, skipped while it is missing
+const DATA_DIR_CHECKS: [&str; 5] = [
+    "config_valid",
+    "registry_valid",
+    "legacy_auth_migrated",
+    "logged_in",
+    "instances_reachable",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// Result of a single diagnostic check
+#[derive(Debug, Serialize)]
+struct Check {
+    name: &'static str,
+    status: CheckStatus,
+    detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Check {
+            name,
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// The `doctor --output json` document
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    healthy: bool,
+    checks: &'a [Check],
+}
+
+/// Diagnose common problems with the local CLI setup
+///
+/// With `fix`, safe corrective actions are taken and reported. Destructive fixes
+/// (removing unreachable instances) require confirmation, or the global `--yes`.
+/// With JSON output the report is a single `{"healthy": .., "checks": [..]}` object
+/// and repair notices go to stderr; the exit status reflects health either way.
+pub fn doctor(fix: bool, output: OutputFormat) -> Result<()> {
+    info!(fix, "Running diagnostics...");
+
+    let json = output == OutputFormat::Json;
+    let data_dir = check_data_dir(fix, json);
+    let checks =
+        if data_dir.status == CheckStatus::Fail {
+            // Reading the config, registry or token creates a missing data directory,
+            // which would hide the problem from the next run
+            let mut checks = vec![data_dir];
+            checks.extend(DATA_DIR_CHECKS.iter().map(|name| {
+                Check::new(name, CheckStatus::Warn, "skipped: data directory unusable")
+            }));
+            checks.push(check_jwks_cache());
+            checks
+        } else {
+            vec![
+                data_dir,
+                check_config(),
+                check_registry(),
+                check_legacy_auth(fix, json),
+                check_auth(),
+                check_jwks_cache(),
+                check_instances(fix, json)?,
+            ]
+        };
+
+    let failures = checks
+        .iter()
+        .filter(|c| c.status == CheckStatus::Fail)
+        .count();
+    if json {
+        output::print_json(&Report {
+            healthy: failures == 0,
+            checks: &checks,
+        })?;
+        if failures > 0 {
+            anyhow::bail!("doctor found {} problem(s)", failures);
+        }
+        return Ok(());
+    }
+
+    println!();
+    for check in &checks {
+        let icon = match check.status {
+            CheckStatus::Pass => "✓",
+            CheckStatus::Warn => "⚠",
+            CheckStatus::Fail => "✗",
+        };
+        println!("{} {:<20} {}", icon, check.name, check.detail);
+    }
+    println!();
+
+    if failures > 0 {
+        if !fix {
+            println!("Run `runbeam doctor --fix` to attempt automatic repairs.");
+        }
+        anyhow::bail!("doctor found {} problem(s)", failures);
+    }
+
+    println!("No problems found.");
+    Ok(())
+}
+
+/// Announce a repair made by `--fix`; on stderr with JSON output, so stdout stays parseable
+fn report_fix(json: bool, message: &str) {
+    if json {
+        eprintln!("🔧 {}", message);
+    } else {
+        println!("🔧 {}", message);
+    }
+}
+
+fn check_data_dir(fix: bool, json: bool) -> Check {
+    const NAME: &str = "data_dir_writable";
+
+    let dir = match storage::base_dir() {
+        Ok(dir) => dir,
+        Err(e) => return Check::new(NAME, CheckStatus::Fail, e.to_string()),
+    };
+
+    if !dir.exists() {
+        if !fix {
+            return Check::new(
+                NAME,
+                CheckStatus::Fail,
+                format!("{} does not exist", dir.display()),
+            );
+        }
+        if let Err(e) = storage::data_dir() {
+            return Check::new(NAME, CheckStatus::Fail, format!("{:#}", e));
+        }
+        report_fix(json, &format!("Created data directory {}", dir.display()));
+    }
+
+    if !dir.is_dir() {
+        return Check::new(
+            NAME,
+            CheckStatus::Fail,
+            format!("{} is not a directory", dir.display()),
+        );
+    }
+
+    let probe = dir.join(".doctor-probe");
+    match fs::write(&probe, b"ok") {
+        Ok(()) => {
+            fs::remove_file(&probe).ok();
+            Check::new(NAME, CheckStatus::Pass, dir.display().to_string())
+        }
+        Err(e) => Check::new(
+            NAME,
+            CheckStatus::Fail,
+            format!("{} is not writable: {}", dir.display(), e),
+        ),
+    }
+}
+
+fn check_config() -> Check {
+    const NAME: &str = "config_valid";
+
+    match config::load_config().and_then(|_| config::get_api_url()) {
+        Ok(url) => Check::new(NAME, CheckStatus::Pass, format!("api-url: {}", url)),
+        Err(e) => Check::new(NAME, CheckStatus::Fail, format!("{:#}", e)),
+    }
+}
+
+fn check_registry() -> Check {
+    const NAME: &str = "registry_valid";
+
+    match storage::load_harmony_instances() {
+        Ok(list) => Check::new(
+            NAME,
+            CheckStatus::Pass,
+            format!("{} instance(s) registered", list.len()),
+        ),
+        Err(e) => Check::new(NAME, CheckStatus::Fail, format!("{:#}", e)),
+    }
+}
+
+fn check_legacy_auth(fix: bool, json: bool) -> Check {
+    const NAME: &str = "legacy_auth_migrated";
+
+    let legacy_path = match storage::auth_file_path() {
+        Ok(path) => path,
+        Err(e) => return Check::new(NAME, CheckStatus::Fail, e.to_string()),
+    };
+
+    if !legacy_path.exists() {
+        return Check::new(NAME, CheckStatus::Pass, "no legacy plaintext token");
+    }
+
+    if !fix {
+        return Check::new(
+            NAME,
+            CheckStatus::Warn,
+            format!("plaintext token found at {}", legacy_path.display()),
+        );
+    }
+    if storage::auth_migration_disabled() {
+        return Check::new(
+            NAME,
+            CheckStatus::Warn,
+            format!(
+                "plaintext token kept at {}; migration is disabled (--no-migrate)",
+                legacy_path.display()
+            ),
+        );
+    }
+
+    // Loading the auth triggers the migration into secure storage
+    if let Err(e) = storage::load_auth() {
+        return Check::new(NAME, CheckStatus::Fail, format!("{:#}", e));
+    }
+
+    if legacy_path.exists() {
+        Check::new(
+            NAME,
+            CheckStatus::Warn,
+            "migration to secure storage failed; legacy file kept",
+        )
+    } else {
+        report_fix(json, "Migrated legacy plaintext token to secure storage");
+        Check::new(NAME, CheckStatus::Pass, "migrated to secure storage")
+    }
+}
+
+fn check_auth() -> Check {
+    const NAME: &str = "logged_in";
+
+    match storage::load_auth() {
+        Ok(Some(auth)) => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64;
+            match auth.expires_at {
+                Some(exp) if exp <= now => Check::new(
+                    NAME,
+                    CheckStatus::Warn,
+                    "stored token has expired; run `runbeam login`",
+                ),
+                _ => Check::new(NAME, CheckStatus::Pass, "token present"),
+            }
+        }
+        Ok(None) => Check::new(
+            NAME,
+            CheckStatus::Warn,
+            "not logged in; run `runbeam login`",
+        ),
+        Err(e) => Check::new(NAME, CheckStatus::Fail, format!("{:#}", e)),
+    }
+}
+
+/// runbeam-sdk keeps the JWKS in memory for one command only, so there is no stale
+/// cache on disk to clear; this reports how long keys are kept
+fn check_jwks_cache() -> Check {
+    const NAME: &str = "jwks_cache";
+
+    match auth::jwks_ttl_secs() {
+        Ok(0) => Check::new(
+            NAME,
+            CheckStatus::Pass,
+            "disabled; keys are fetched for every validation",
+        ),
+        Ok(secs) => Check::new(
+            NAME,
+            CheckStatus::Pass,
+            format!("in memory for one command; keys expire after {}s", secs),
+        ),
+        Err(e) => Check::new(NAME, CheckStatus::Warn, format!("{:#}", e)),
+    }
+}
+
+fn check_instances(fix: bool, json: bool) -> Result<Check> {
+    const NAME: &str = "instances_reachable";
+
+    let Ok(list) = storage::load_harmony_instances() else {
+        return Ok(Check::new(NAME, CheckStatus::Warn, "registry unreadable"));
+    };
+    if list.is_empty() {
+        return Ok(Check::new(
+            NAME,
+            CheckStatus::Pass,
+            "no instances registered",
+        ));
+    }
+    if http::offline() {
+        return Ok(Check::new(
+            NAME,
+            CheckStatus::Warn,
+            format!("skipped {} instance(s) (--offline)", list.len()),
+        ));
+    }
+
+    // Without the tunnel every instance would look unreachable, and --fix would remove them
+    if let Err(e) = list.iter().try_for_each(crate::tunnel::open) {
+        return Ok(Check::new(
+            NAME,
+            CheckStatus::Warn,
+            format!("skipped {} instance(s): {:#}", list.len(), e),
+        ));
+    }
+
+    let unreachable: Vec<_> = list
+        .iter()
+        .zip(management::probe_all(&list, PROBE_TIMEOUT))
+        .filter(|(inst, result)| {
+            debug!(id = %inst.id, ok = result.is_ok(), "probed instance");
+            result.is_err()
+        })
+        .map(|(inst, _)| inst)
+        .collect();
+
+    if unreachable.is_empty() {
+        return Ok(Check::new(
+            NAME,
+            CheckStatus::Pass,
+            format!("{} of {} reachable", list.len(), list.len()),
+        ));
+    }
+
+    let labels: Vec<&str> = unreachable.iter().map(|i| i.label.as_str()).collect();
+    if fix
+        && prompt::confirm(&format!(
+            "Remove {} unreachable instance(s): {}?",
+            unreachable.len(),
+            labels.join(", ")
+        ))?
+    {
+        for inst in &unreachable {
+            storage::remove_harmony_instance_by_id(&inst.id)?;
+            report_fix(
+                json,
+                &format!(
+                    "Removed unreachable instance {} ({}:{})",
+                    inst.label, inst.ip, inst.port
+                ),
+            );
+        }
+        return Ok(Check::new(
+            NAME,
+            CheckStatus::Pass,
+            format!("removed {} unreachable instance(s)", unreachable.len()),
+        ));
+    }
+
+    Ok(Check::new(
+        NAME,
+        CheckStatus::Warn,
+        format!("unreachable: {}", labels.join(", ")),
+    ))
+}
@@ -0,0 +1,221 @@
+//! Shared runner for fleet commands that act on every registered instance (`--all`)
+//! or on the members of a group (`--group`)
+//!
+//! By default every instance is attempted and failures are summarized at the end;
+//! `--fail-fast` stops at the first failure. Either way the command exits non-zero
+//! if any instance failed.
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::storage::HarmonyInstance;
+
+/// Per-instance results of a batch run
+#[derive(Debug, Default, PartialEq, Eq)]
+struct BatchSummary {
+    succeeded: usize,
+    /// Labels of the instances that failed, in run order
+    failed: Vec<String>,
+    /// Instances not attempted because `--fail-fast` stopped the run
+    skipped: usize,
+}
+
+impl BatchSummary {
+    fn render(&self) -> String {
+        let mut out = format!("{} succeeded, {} failed", self.succeeded, self.failed.len());
+        if self.skipped > 0 {
+            out.push_str(&format!(", {} skipped (--fail-fast)", self.skipped));
+        }
+        if !self.failed.is_empty() {
+            out.push_str(&format!(": {}", self.failed.join(", ")));
+        }
+        out
+    }
+}
+
+fn run<F>(instances: &[HarmonyInstance], fail_fast: bool, mut f: F) -> BatchSummary
+where
+    F: FnMut(&HarmonyInstance) -> Result<()>,
+{
+    let mut summary = BatchSummary::default();
+    for (i, inst) in instances.iter().enumerate() {
+        println!("== {} ({}) ==", inst.label, inst.id);
+        match f(inst) {
+            Ok(()) => summary.succeeded += 1,
+            Err(e) => {
+                eprintln!("✗ {}: {:#}", inst.label, e);
+                summary.failed.push(inst.label.clone());
+                if fail_fast {
+                    summary.skipped = instances.len() - i - 1;
+                    break;
+                }
+            }
+        }
+        println!();
+    }
+    summary
+}
+
+/// Run `f` against each instance, print a summary, and fail if any instance failed
+///
+/// Each instance's `--connect-via` tunnel is opened before `f` runs.
+pub fn run_over_instances<F>(instances: &[HarmonyInstance], fail_fast: bool, mut f: F) -> Result<()>
+where
+    F: FnMut(&HarmonyInstance) -> Result<()>,
+{
+    if instances.is_empty() {
+        println!("No Harmony instances registered.");
+        return Ok(());
+    }
+
+    let summary = run(instances, fail_fast, |inst| {
+        crate::tunnel::open(inst)?;
+        f(inst)
+    });
+    println!("{}", summary.render());
+    if !summary.failed.is_empty() {
+        anyhow::bail!(
+            "{} of {} instances failed",
+            summary.failed.len(),
+            instances.len()
+        );
+    }
+    Ok(())
+}
+
+/// Outcome of each attempted instance, as `--output json` entries, and how many failed
+fn collect<F>(instances: &[HarmonyInstance], fail_fast: bool, mut f: F) -> (Vec<Value>, usize)
+where
+    F: FnMut(&HarmonyInstance) -> Result<Value>,
+{
+    let mut entries = Vec::with_capacity(instances.len());
+    let mut failed = 0;
+    for inst in instances {
+        let mut entry = serde_json::json!({ "id": inst.id, "label": inst.label });
+        match f(inst) {
+            Ok(result) => {
+                entry["ok"] = Value::Bool(true);
+                entry["result"] = result;
+            }
+            Err(e) => {
+                failed += 1;
+                entry["ok"] = Value::Bool(false);
+                entry["error"] = Value::String(format!("{:#}", e));
+            }
+        }
+        entries.push(entry);
+        if failed > 0 && fail_fast {
+            break;
+        }
+    }
+    (entries, failed)
+}
+
+/// Like [`run_over_instances`], but for `--output json`: print one array of
+/// `{"id", "label", "ok", "result" | "error"}` entries instead of per-instance
+/// sections and a summary
+///
+/// Instances skipped by `--fail-fast` have no entry.
+pub fn collect_json<F>(instances: &[HarmonyInstance], fail_fast: bool, mut f: F) -> Result<()>
+where
+    F: FnMut(&HarmonyInstance) -> Result<Value>,
+{
+    let (entries, failed) = collect(instances, fail_fast, |inst| {
+        crate::tunnel::open(inst)?;
+        f(inst)
+    });
+    crate::output::print_json(&entries)?;
+    if failed > 0 {
+        anyhow::bail!("{} of {} instances failed", failed, instances.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instances(labels: &[&str]) -> Vec<HarmonyInstance> {
+        labels
+            .iter()
+            .map(|label| HarmonyInstance {
+                id: format!("id-{}", label),
+                ip: "127.0.0.1".to_string(),
+                port: 8081,
+                label: label.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn fail_on_b(inst: &HarmonyInstance) -> Result<()> {
+        if inst.label == "b" {
+            anyhow::bail!("connection refused");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_continue_on_error_attempts_every_instance() {
+        let list = instances(&["a", "b", "c"]);
+        let mut seen = Vec::new();
+        let summary = run(&list, false, |inst| {
+            seen.push(inst.label.clone());
+            fail_on_b(inst)
+        });
+        assert_eq!(seen, ["a", "b", "c"]);
+        assert_eq!(
+            summary,
+            BatchSummary {
+                succeeded: 2,
+                failed: vec!["b".to_string()],
+                skipped: 0,
+            }
+        );
+        assert_eq!(summary.render(), "2 succeeded, 1 failed: b");
+    }
+
+    #[test]
+    fn test_fail_fast_stops_at_first_failure() {
+        let list = instances(&["a", "b", "c", "d"]);
+        let summary = run(&list, true, fail_on_b);
+        assert_eq!(summary.succeeded, 1);
+        assert_eq!(summary.skipped, 2);
+        assert_eq!(
+            summary.render(),
+            "1 succeeded, 1 failed, 2 skipped (--fail-fast): b"
+        );
+    }
+
+    #[test]
+    fn test_run_over_instances_errors_when_any_failed() {
+        let list = instances(&["a", "b"]);
+        let err = run_over_instances(&list, false, fail_on_b).unwrap_err();
+        assert_eq!(err.to_string(), "1 of 2 instances failed");
+        assert!(run_over_instances(&instances(&["a"]), false, fail_on_b).is_ok());
+    }
+
+    #[test]
+    fn test_collect_reports_each_instance_as_json() {
+        let list = instances(&["a", "b", "c"]);
+        let (entries, failed) = collect(&list, false, |inst| {
+            fail_on_b(inst).map(|()| serde_json::json!({ "version": "1.0.0" }))
+        });
+        assert_eq!(failed, 1);
+        assert_eq!(
+            entries[0],
+            serde_json::json!({
+                "id": "id-a",
+                "label": "a",
+                "ok": true,
+                "result": { "version": "1.0.0" },
+            })
+        );
+        assert_eq!(entries[1]["ok"], false);
+        assert_eq!(entries[1]["error"], "connection refused");
+        assert_eq!(entries[2]["ok"], true);
+
+        let (entries, failed) = collect(&list, true, |inst| fail_on_b(inst).map(|()| Value::Null));
+        assert_eq!((entries.len(), failed), (2, 1));
+    }
+}
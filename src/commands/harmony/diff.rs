@@ -0,0 +1,192 @@
+//! Route drift detection for `harmony:routes --diff`
+//!
+//! [`changed_fields`] is also used by `harmony:info --watch --diff` to compare polls.
+//! Routes are identified by `path` plus their (sorted) `methods`; two routes with
+//! the same identity are compared field by field, ignoring object key order.
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::style::{self, Color};
+
+/// Differences between a saved snapshot and the live routes
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RouteDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Route identity and one `field: old -> new` line per changed field
+    pub changed: Vec<(String, Vec<String>)>,
+}
+
+impl RouteDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    pub fn render(&self, color: bool) -> String {
+        if self.is_empty() {
+            return "No differences.\n".to_string();
+        }
+        let mut out = String::new();
+        for key in &self.added {
+            out.push_str(&style::paint(&format!("+ {}", key), Color::Green, color));
+            out.push('\n');
+        }
+        for key in &self.removed {
+            out.push_str(&style::paint(&format!("- {}", key), Color::Red, color));
+            out.push('\n');
+        }
+        for (key, fields) in &self.changed {
+            out.push_str(&style::paint(&format!("~ {}", key), Color::Yellow, color));
+            out.push('\n');
+            for field in fields {
+                out.push_str(&format!("    {}\n", field));
+            }
+        }
+        out.push_str(&format!(
+            "{} added, {} removed, {} changed\n",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len()
+        ));
+        out
+    }
+}
+
+/// The routes in a `/routes` response or snapshot: its `routes` array, or the value itself
+pub fn routes_of(value: &Value) -> Option<&[Value]> {
+    value
+        .get("routes")
+        .unwrap_or(value)
+        .as_array()
+        .map(|a| a.as_slice())
+}
+
+/// Identity of a route, e.g. `GET,POST /api/users`
+fn route_key(route: &Value) -> String {
+    let path = route.get("path").and_then(|p| p.as_str()).unwrap_or("?");
+    let mut methods: Vec<String> = match route.get("methods").or_else(|| route.get("method")) {
+        Some(Value::Array(arr)) => arr
+            .iter()
+            .map(|m| m.as_str().unwrap_or_default().to_ascii_uppercase())
+            .collect(),
+        Some(Value::String(m)) => vec![m.to_ascii_uppercase()],
+        _ => Vec::new(),
+    };
+    methods.sort();
+    if methods.is_empty() {
+        path.to_string()
+    } else {
+        format!("{} {}", methods.join(","), path)
+    }
+}
+
+/// Routes by identity, with `methods` sorted so their order is not reported as a change
+fn index(routes: &[Value]) -> BTreeMap<String, Value> {
+    routes
+        .iter()
+        .map(|r| {
+            let mut route = r.clone();
+            if let Some(Value::Array(methods)) = route.get_mut("methods") {
+                methods.sort_by_key(|m| m.as_str().unwrap_or_default().to_ascii_uppercase());
+            }
+            (route_key(r), route)
+        })
+        .collect()
+}
+
+/// `field: old -> new` for every top-level field that differs between two objects
+pub fn changed_fields(old: &Value, new: &Value) -> Vec<String> {
+    let (Some(old), Some(new)) = (old.as_object(), new.as_object()) else {
+        return vec![format!("{} -> {}", old, new)];
+    };
+    let mut fields: Vec<&String> = old.keys().chain(new.keys()).collect();
+    fields.sort();
+    fields.dedup();
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let (a, b) = (old.get(field), new.get(field));
+            if a == b {
+                return None;
+            }
+            let show = |v: Option<&Value>| v.map_or("(none)".to_string(), |v| v.to_string());
+            Some(format!("{}: {} -> {}", field, show(a), show(b)))
+        })
+        .collect()
+}
+
+/// Compare snapshot routes (`old`) with live routes (`new`)
+pub fn diff_routes(old: &[Value], new: &[Value]) -> RouteDiff {
+    let (old, new) = (index(old), index(new));
+    let mut diff = RouteDiff::default();
+    for (key, route) in &new {
+        match old.get(key) {
+            None => diff.added.push(key.clone()),
+            Some(before) if before != route => {
+                diff.changed
+                    .push((key.clone(), changed_fields(before, route)));
+            }
+            Some(_) => {}
+        }
+    }
+    diff.removed = old
+        .keys()
+        .filter(|key| !new.contains_key(*key))
+        .cloned()
+        .collect();
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_route_key_sorts_methods() {
+        assert_eq!(
+            route_key(&json!({"path": "/a", "methods": ["post", "GET"]})),
+            "GET,POST /a"
+        );
+        assert_eq!(route_key(&json!({"path": "/b", "method": "get"})), "GET /b");
+        assert_eq!(route_key(&json!({"path": "/c"})), "/c");
+    }
+
+    #[test]
+    fn test_diff_routes_added_removed_changed() {
+        let old = json!([
+            {"path": "/keep", "methods": ["GET"], "pipeline": "p1"},
+            {"path": "/gone", "methods": ["GET"]},
+            {"path": "/edit", "methods": ["POST"], "pipeline": "p1"}
+        ]);
+        let new = json!([
+            {"pipeline": "p1", "methods": ["GET"], "path": "/keep"},
+            {"path": "/edit", "methods": ["POST"], "pipeline": "p2"},
+            {"path": "/new", "methods": ["GET", "DELETE"]}
+        ]);
+        let diff = diff_routes(old.as_array().unwrap(), new.as_array().unwrap());
+        assert_eq!(diff.added, ["DELETE,GET /new"]);
+        assert_eq!(diff.removed, ["GET /gone"]);
+        assert_eq!(
+            diff.changed,
+            [(
+                "POST /edit".to_string(),
+                vec![r#"pipeline: "p1" -> "p2""#.to_string()]
+            )]
+        );
+        assert!(
+            diff.render(false)
+                .ends_with("1 added, 1 removed, 1 changed\n")
+        );
+    }
+
+    #[test]
+    fn test_diff_routes_identical_ignores_order() {
+        let old = json!({"routes": [{"path": "/a", "methods": ["GET", "POST"]}, {"path": "/b"}]});
+        let new = json!([{"path": "/b"}, {"methods": ["POST", "GET"], "path": "/a"}]);
+        let diff = diff_routes(routes_of(&old).unwrap(), routes_of(&new).unwrap());
+        assert!(diff.is_empty());
+        assert_eq!(diff.render(false), "No differences.\n");
+    }
+}
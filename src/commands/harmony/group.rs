@@ -0,0 +1,294 @@
+//! Named instance groups (`harmony:group`) and `--group` targeting for batch commands
+//!
+//! Groups live in `groups.json` next to the registry and hold instance IDs. Members
+//! that have since been removed from the registry are reported, not silently dropped.
+
+use anyhow::{Result, anyhow};
+use std::io::Write;
+use tracing::warn;
+
+use crate::storage::{self, Groups, HarmonyInstance};
+
+use super::management;
+
+/// Error for a group that does not exist, with the command that creates it
+fn no_such_group(name: &str) -> anyhow::Error {
+    anyhow!(
+        "no group named '{}'; create it with `runbeam harmony:group create {}`",
+        name,
+        name
+    )
+}
+
+/// `harmony:group create`
+pub fn create(name: &str) -> Result<()> {
+    storage::validate_group_name(name)?;
+    let mut groups = storage::load_groups()?;
+    if groups.contains_key(name) {
+        anyhow::bail!("group '{}' already exists", name);
+    }
+    groups.insert(name.to_string(), Vec::new());
+    storage::save_groups(&groups)?;
+    println!("Created group '{}'.", name);
+    Ok(())
+}
+
+/// `harmony:group delete`; the instances themselves are not touched
+pub fn delete(name: &str) -> Result<()> {
+    let mut groups = storage::load_groups()?;
+    if groups.remove(name).is_none() {
+        return Err(no_such_group(name));
+    }
+    storage::save_groups(&groups)?;
+    println!("Deleted group '{}'.", name);
+    Ok(())
+}
+
+/// `harmony:group add`: add the instance selected by `--id` / `--label`
+pub fn add(name: &str, id: Option<&str>, label: Option<&str>) -> Result<()> {
+    let mut groups = storage::load_groups()?;
+    let members = groups.get_mut(name).ok_or_else(|| no_such_group(name))?;
+    let inst = management::resolve_instance(id, label)?;
+    if members.contains(&inst.id) {
+        println!("'{}' is already in group '{}'.", inst.label, name);
+        return Ok(());
+    }
+    members.push(inst.id.clone());
+    storage::save_groups(&groups)?;
+    println!(
+        "Added '{}' (ID: {}) to group '{}'.",
+        inst.label, inst.id, name
+    );
+    Ok(())
+}
+
+/// `harmony:group remove`: remove a member by `--id` (even if it is no longer
+/// registered) or by `--label`
+pub fn remove(name: &str, id: Option<&str>, label: Option<&str>) -> Result<()> {
+    let mut groups = storage::load_groups()?;
+    let members = groups.get_mut(name).ok_or_else(|| no_such_group(name))?;
+    let id = match (id, label) {
+        (Some(id), _) => id.to_string(),
+        (None, label) => management::resolve_instance(None, label)?.id,
+    };
+    let before = members.len();
+    members.retain(|m| *m != id);
+    if members.len() == before {
+        anyhow::bail!("instance '{}' is not in group '{}'", id, name);
+    }
+    storage::save_groups(&groups)?;
+    println!("Removed '{}' from group '{}'.", id, name);
+    Ok(())
+}
+
+/// `harmony:group list`: every group with its members, or the instances of one group
+pub fn list(out: &mut dyn Write, name: Option<&str>) -> Result<()> {
+    let groups = storage::load_groups()?;
+    let instances = storage::load_harmony_instances()?;
+    match name {
+        Some(name) => {
+            let (members, missing) = resolve_members(&groups, &instances, name)?;
+            for id in &missing {
+                writeln!(out, "⚠️  Member {} is no longer registered.", id)?;
+            }
+            if members.is_empty() && missing.is_empty() {
+                writeln!(out, "Group '{}' has no members.", name)?;
+                return Ok(());
+            }
+            super::harmony::write_instance_table(out, members, None, None)
+        }
+        None => write_group_table(out, &groups, &instances),
+    }
+}
+
+/// The instances a batch command runs over: a group's members with `--group`, else all,
+/// keeping only those carrying `tag` with `--tag`
+///
+/// Instances from the `RUNBEAM_INVENTORY` file count as registered here, after the
+/// local entries, as they do for `--id` / `--label`.
+pub fn batch_targets(group: Option<&str>, tag: Option<&str>) -> Result<Vec<HarmonyInstance>> {
+    let targets = group_targets(group)?;
+    match tag {
+        Some(tag) => only_tagged(targets, group, tag),
+        None => Ok(targets),
+    }
+}
+
+/// The instances in `targets` that carry `tag`; fails if none do
+fn only_tagged(
+    targets: Vec<HarmonyInstance>,
+    group: Option<&str>,
+    tag: &str,
+) -> Result<Vec<HarmonyInstance>> {
+    let tagged: Vec<HarmonyInstance> = targets
+        .into_iter()
+        .filter(|inst| inst.tags.iter().any(|t| t == tag))
+        .collect();
+    if tagged.is_empty() {
+        match group {
+            Some(name) => anyhow::bail!("no member of group '{}' is tagged '{}'", name, tag),
+            None => anyhow::bail!("no registered instance is tagged '{}'", tag),
+        }
+    }
+    Ok(tagged)
+}
+
+/// A group's registered members, or every registered instance without `group`
+fn group_targets(group: Option<&str>) -> Result<Vec<HarmonyInstance>> {
+    let instances = storage::merge_inventory(
+        storage::load_harmony_instances()?,
+        storage::load_inventory()?,
+    );
+    let Some(name) = group else {
+        return Ok(instances);
+    };
+    let (members, missing) = resolve_members(&storage::load_groups()?, &instances, name)?;
+    for id in missing {
+        warn!(
+            group = name,
+            id, "skipping group member that is no longer registered"
+        );
+    }
+    if members.is_empty() {
+        anyhow::bail!("group '{}' has no registered members", name);
+    }
+    Ok(members)
+}
+
+/// Registered members of `name` in group order, and the IDs of members that are not registered
+fn resolve_members(
+    groups: &Groups,
+    instances: &[HarmonyInstance],
+    name: &str,
+) -> Result<(Vec<HarmonyInstance>, Vec<String>)> {
+    let ids = groups.get(name).ok_or_else(|| no_such_group(name))?;
+    let mut members = Vec::new();
+    let mut missing = Vec::new();
+    for id in ids {
+        match instances.iter().find(|i| i.id == *id) {
+            Some(inst) => members.push(inst.clone()),
+            None => missing.push(id.clone()),
+        }
+    }
+    Ok((members, missing))
+}
+
+fn write_group_table(
+    out: &mut dyn Write,
+    groups: &Groups,
+    instances: &[HarmonyInstance],
+) -> Result<()> {
+    if groups.is_empty() {
+        writeln!(out, "No groups defined.")?;
+        return Ok(());
+    }
+    let rows: Vec<(&str, String)> = groups
+        .iter()
+        .map(|(name, ids)| {
+            let members: Vec<String> = ids
+                .iter()
+                .map(|id| match instances.iter().find(|i| i.id == *id) {
+                    Some(inst) => inst.label.clone(),
+                    None => format!("{} (missing)", id),
+                })
+                .collect();
+            (name.as_str(), members.join(", "))
+        })
+        .collect();
+
+    let w_name = rows
+        .iter()
+        .map(|(name, _)| name.len())
+        .chain(["GROUP".len()])
+        .max()
+        .unwrap_or_default();
+    writeln!(out, "{:<w$} | MEMBERS", "GROUP", w = w_name)?;
+    writeln!(out, "{:-<w$}-+--------", "", w = w_name)?;
+    for (name, members) in rows {
+        writeln!(out, "{:<w$} | {}", name, members, w = w_name)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance(id: &str, label: &str) -> HarmonyInstance {
+        HarmonyInstance {
+            id: id.to_string(),
+            ip: "127.0.0.1".to_string(),
+            port: 8081,
+            label: label.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn groups() -> Groups {
+        let mut groups = Groups::new();
+        groups.insert(
+            "us-east".to_string(),
+            vec!["b2".to_string(), "gone".to_string(), "a1".to_string()],
+        );
+        groups.insert("empty".to_string(), Vec::new());
+        groups
+    }
+
+    #[test]
+    fn test_resolve_members_keeps_group_order() {
+        let instances = [instance("a1", "one"), instance("b2", "two")];
+        let (members, missing) = resolve_members(&groups(), &instances, "us-east").unwrap();
+        let labels: Vec<&str> = members.iter().map(|i| i.label.as_str()).collect();
+        assert_eq!(labels, ["two", "one"]);
+        assert_eq!(missing, ["gone"]);
+
+        let err = resolve_members(&groups(), &instances, "eu").unwrap_err();
+        assert!(err.to_string().starts_with("no group named 'eu'"));
+    }
+
+    #[test]
+    fn test_only_tagged_filters_batch_targets() {
+        let tagged = |id: &str, tags: &[&str]| HarmonyInstance {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..instance(id, id)
+        };
+        let targets = vec![
+            tagged("a1", &["prod", "eu"]),
+            tagged("b2", &["staging"]),
+            tagged("c3", &["prod"]),
+        ];
+        let ids = |list: Vec<HarmonyInstance>| -> Vec<String> {
+            list.into_iter().map(|i| i.id).collect()
+        };
+        assert_eq!(
+            ids(only_tagged(targets.clone(), None, "prod").unwrap()),
+            ["a1", "c3"]
+        );
+
+        let err = only_tagged(targets.clone(), None, "dev").unwrap_err();
+        assert_eq!(err.to_string(), "no registered instance is tagged 'dev'");
+        let err = only_tagged(targets, Some("us-east"), "dev").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no member of group 'us-east' is tagged 'dev'"
+        );
+    }
+
+    #[test]
+    fn test_write_group_table() {
+        let instances = [instance("a1", "one"), instance("b2", "two")];
+        let mut out = Vec::new();
+        write_group_table(&mut out, &groups(), &instances).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "GROUP   | MEMBERS\n\
+             --------+--------\n\
+             empty   | \n\
+             us-east | two, gone (missing), one\n"
+        );
+
+        let mut out = Vec::new();
+        write_group_table(&mut out, &Groups::new(), &instances).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "No groups defined.\n");
+    }
+}
@@ -1,5 +1,77 @@
+use anyhow::Context;
+use std::io::Write;
+use std::ops::RangeInclusive;
+use std::time::Duration;
 use tracing::info;
 
+use super::management;
+
+/// How long `harmony:add --test` waits for the instance to answer
+const ADD_PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Instance details collected by the interactive `harmony:add` wizard
+#[derive(Debug, PartialEq, Eq)]
+pub struct AddDetails {
+    pub ip: String,
+    pub port: u16,
+    pub label: String,
+    pub path_prefix: String,
+    pub scheme: String,
+}
+
+/// Ask for the details of a new instance, with the usual defaults shown in brackets
+///
+/// Used by `harmony:add` when it is run with no flags on a terminal.
+pub fn add_wizard() -> anyhow::Result<AddDetails> {
+    let default_scheme = crate::commands::config::get_default_scheme()?;
+    add_wizard_with(
+        &mut std::io::stdin().lock(),
+        &mut std::io::stdout(),
+        &default_scheme,
+    )
+}
+
+fn add_wizard_with(
+    input: &mut dyn std::io::BufRead,
+    out: &mut dyn Write,
+    default_scheme: &str,
+) -> anyhow::Result<AddDetails> {
+    use crate::prompt::ask;
+
+    writeln!(
+        out,
+        "Add a Harmony instance (press Enter to accept the value in brackets)"
+    )?;
+    let ip = ask(input, out, "IP address", "127.0.0.1")?;
+    let port = loop {
+        match ask(input, out, "Port", "8081")?.parse::<u16>() {
+            Ok(port) if port > 0 => break port,
+            _ => writeln!(out, "  Port must be a number from 1 to 65535.")?,
+        }
+    };
+    let label = ask(input, out, "Label", &format!("{}:{}", ip, port))?;
+    let path_prefix = loop {
+        let answer = ask(input, out, "Path prefix", "admin")?;
+        match crate::storage::normalize_path_prefix(&answer) {
+            Ok(_) => break answer,
+            Err(e) => writeln!(out, "  {}", e)?,
+        }
+    };
+    let scheme = loop {
+        match crate::storage::normalize_scheme(&ask(input, out, "Scheme", default_scheme)?) {
+            Ok(scheme) => break scheme,
+            Err(e) => writeln!(out, "  {}", e)?,
+        }
+    };
+    Ok(AddDetails {
+        ip,
+        port,
+        label,
+        path_prefix,
+        scheme,
+    })
+}
+
 /// Add a new Harmony instance via the management API
 ///
 /// Persists to the runbeam data directory. When the live management API exists,
@@ -8,16 +80,52 @@ use tracing::info;
 /// # Arguments
 ///
 /// * `encryption_key` - Optional base64-encoded encryption key to use for token storage
+/// * `headers` - Request headers (`NAME:VALUE`) to send with every management API call
+/// * `test` - Probe `GET /{prefix}/info` first and refuse to save an unreachable instance
+/// * `force` - With `test`, save even if the probe fails
+/// * `clone_from` - ID of an instance whose path prefix, scheme, headers and tags are
+///   inherited; `scheme` and `headers` still override them
+#[allow(clippy::too_many_arguments)]
 pub fn harmony_add(
     ip: &str,
     port: u16,
     label: Option<&str>,
     path_prefix: &str,
+    scheme: Option<&str>,
     encryption_key: Option<&str>,
+    headers: &[String],
+    test: bool,
+    force: bool,
+    clone_from: Option<&str>,
 ) -> anyhow::Result<()> {
     let final_label = label
         .map(|s| s.to_string())
         .unwrap_or_else(|| format!("{}:{}", ip, port));
+    let headers = headers
+        .iter()
+        .map(|h| parse_header(h))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let source = clone_from
+        .map(|id| clone_source(id, ip, port, &final_label))
+        .transpose()?;
+    let path_prefix = match &source {
+        Some(source) => &source.path_prefix,
+        None => &crate::storage::normalize_path_prefix(path_prefix)?,
+    };
+    let existing = crate::storage::load_harmony_instances()?;
+    let scheme = match (scheme, &source) {
+        (Some(scheme), _) => crate::storage::normalize_scheme(scheme)?,
+        (None, Some(source)) => source.scheme.clone(),
+        // Re-adding an instance keeps its scheme unless --scheme says otherwise
+        (None, None) => match crate::storage::merge_target(&existing, &final_label, ip, port) {
+            Some(current) => current.scheme.clone(),
+            None => crate::commands::config::get_default_scheme()?,
+        },
+    };
+    let (headers, tags) = match &source {
+        Some(source) => (merge_headers(&source.headers, headers), source.tags.clone()),
+        None => (headers, Vec::new()),
+    };
 
     info!(ip = %ip, port = %port, label = %final_label, path_prefix = %path_prefix, "harmony:add");
 
@@ -28,8 +136,40 @@ pub fn harmony_add(
         port,
         label: final_label.clone(),
         path_prefix: path_prefix.to_string(),
-        gateway_id: None,  // Will be set after authorization
+        gateway_id: None, // Will be set after authorization
+        headers,
+        tags,
+        scheme,
+        version: None,
+        machine_token_expires_at: None,
     };
+
+    if test {
+        crate::tunnel::open(&instance)?;
+        let url = format!("{}/info", management::base_url(&instance));
+        match management::probe(&instance, ADD_PROBE_TIMEOUT) {
+            Ok(()) => println!("✓ Instance is reachable at {}", url),
+            Err(e) if force => {
+                println!("⚠️  Reachability test failed: {:#}", e);
+                println!("   Saving anyway (--force).");
+            }
+            Err(e) => {
+                anyhow::bail!(
+                    "reachability test failed: {:#}\nThe instance was not saved. Check the address and port, or pass --force to save it anyway.",
+                    e
+                );
+            }
+        }
+    }
+
+    if !force && let Some(other) = address_conflict(&existing, ip, port, &final_label) {
+        println!(
+            "⚠️  An instance with a different label already targets {}:{} (label={})",
+            ip, port, other.label
+        );
+        println!("   Saving anyway; pass --force to silence this warning.");
+    }
+
     crate::storage::add_harmony_instance(instance.clone())?;
 
     // Get the instance ID (it was generated during add)
@@ -52,10 +192,406 @@ pub fn harmony_add(
     Ok(())
 }
 
-pub fn harmony_list() -> anyhow::Result<()> {
+/// `harmony:add --port-range`: register one instance per port, in order
+///
+/// With a `label`, each instance is labelled `<label>-<port>`; without one, each gets
+/// the usual `ip:port` default. Stops at the first port that cannot be added, keeping
+/// the instances added before it.
+#[allow(clippy::too_many_arguments)]
+pub fn harmony_add_range(
+    ip: &str,
+    ports: RangeInclusive<u16>,
+    label: Option<&str>,
+    path_prefix: &str,
+    scheme: Option<&str>,
+    encryption_key: Option<&str>,
+    headers: &[String],
+    test: bool,
+    force: bool,
+    clone_from: Option<&str>,
+) -> anyhow::Result<()> {
+    let total = ports.clone().count();
+    for (added, port) in ports.clone().enumerate() {
+        let label = label.map(|base| format!("{}-{}", base, port));
+        harmony_add(
+            ip,
+            port,
+            label.as_deref(),
+            path_prefix,
+            scheme,
+            encryption_key,
+            headers,
+            test,
+            force,
+            clone_from,
+        )
+        .with_context(|| {
+            format!(
+                "port {}: {} of {} instances were added before this failure",
+                port, added, total
+            )
+        })?;
+    }
+    println!(
+        "Added {} Harmony instances on ports {}-{}.",
+        total,
+        ports.start(),
+        ports.end()
+    );
+    Ok(())
+}
+
+/// Look up the `--clone-from` source, which the new instance must not overwrite
+///
+/// `add_harmony_instance` merges on a matching label or address, so reusing either
+/// would update the source instead of adding a clone.
+fn clone_source(
+    id: &str,
+    ip: &str,
+    port: u16,
+    label: &str,
+) -> anyhow::Result<crate::storage::HarmonyInstance> {
+    let source = crate::storage::load_harmony_instances()?
+        .into_iter()
+        .find(|i| i.id == id)
+        .ok_or_else(|| anyhow::anyhow!("no instance with id '{}' to clone from", id))?;
+    if source.label == label {
+        anyhow::bail!(
+            "--clone-from: the new instance needs a label other than '{}' (pass --label)",
+            label
+        );
+    }
+    if source.ip == ip && source.port == port {
+        anyhow::bail!(
+            "--clone-from: the new instance needs an address other than {}:{} (pass --ip and/or --port)",
+            ip,
+            port
+        );
+    }
+    Ok(source)
+}
+
+/// Inherited headers with `overrides` applied; names match case-insensitively
+fn merge_headers(
+    inherited: &[(String, String)],
+    overrides: Vec<(String, String)>,
+) -> Vec<(String, String)> {
+    let mut merged: Vec<(String, String)> = inherited
+        .iter()
+        .filter(|(name, _)| !overrides.iter().any(|(o, _)| o.eq_ignore_ascii_case(name)))
+        .cloned()
+        .collect();
+    merged.extend(overrides);
+    merged
+}
+
+/// An instance under another label that already uses `ip:port`, if any
+fn address_conflict<'a>(
+    instances: &'a [crate::storage::HarmonyInstance],
+    ip: &str,
+    port: u16,
+    label: &str,
+) -> Option<&'a crate::storage::HarmonyInstance> {
+    if instances.iter().any(|i| i.label == label) {
+        return None;
+    }
+    instances.iter().find(|i| i.ip == ip && i.port == port)
+}
+
+/// Parse a `NAME:VALUE` (or `NAME=VALUE`) header argument
+///
+/// The first `:` or `=` separates name and value (neither may appear in a
+/// header name), so values may contain either character.
+pub fn parse_header(raw: &str) -> anyhow::Result<(String, String)> {
+    let (name, value) = raw
+        .find([':', '='])
+        .map(|i| (&raw[..i], &raw[i + 1..]))
+        .ok_or_else(|| anyhow::anyhow!("invalid header '{}': expected NAME:VALUE", raw))?;
+    let (name, value) = (name.trim(), value.trim());
+    if reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_err() {
+        anyhow::bail!("invalid header name in '{}'", raw);
+    }
+    if reqwest::header::HeaderValue::from_str(value).is_err() {
+        anyhow::bail!("invalid header value in '{}'", raw);
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Store a request header on a Harmony instance
+pub fn harmony_set_header(id: &str, header: &str) -> anyhow::Result<()> {
+    let (name, value) = parse_header(header)?;
+    if crate::storage::set_harmony_instance_header(id, &name, &value)? {
+        println!("Set header '{}' on Harmony instance '{}'.", name, id);
+        Ok(())
+    } else {
+        anyhow::bail!("no instance with id '{}'", id)
+    }
+}
+
+/// Change the management API path prefix of a Harmony instance
+pub fn harmony_set_prefix(id: &str, prefix: &str) -> anyhow::Result<()> {
+    match crate::storage::set_harmony_instance_prefix(id, prefix)? {
+        Some(old) => {
+            let new = crate::storage::normalize_path_prefix(prefix)?;
+            println!(
+                "Path prefix of Harmony instance '{}': '{}' -> '{}'",
+                id, old, new
+            );
+            Ok(())
+        }
+        None => anyhow::bail!("no instance with id '{}'", id),
+    }
+}
+
+/// Change the URL scheme of a Harmony instance
+pub fn harmony_set_scheme(id: &str, scheme: &str) -> anyhow::Result<()> {
+    match crate::storage::set_harmony_instance_scheme(id, scheme)? {
+        Some(old) => {
+            let new = crate::storage::normalize_scheme(scheme)?;
+            println!("Scheme of Harmony instance '{}': {} -> {}", id, old, new);
+            Ok(())
+        }
+        None => anyhow::bail!("no instance with id '{}'", id),
+    }
+}
+
+/// Remove a stored request header from a Harmony instance
+pub fn harmony_unset_header(id: &str, name: &str) -> anyhow::Result<()> {
+    if crate::storage::unset_harmony_instance_header(id, name)? {
+        println!("Removed header '{}' from Harmony instance '{}'.", name, id);
+    } else {
+        println!(
+            "No header '{}' found on Harmony instance with id '{}'.",
+            name, id
+        );
+    }
+    Ok(())
+}
+
+/// Duplicate an existing instance under a new label
+///
+/// The source is looked up by ID first, then by label. Path prefix and stored
+/// headers are inherited; `ip` and `port` override the source address. The copy
+/// gets a fresh ID and no gateway ID (it must be authorized separately).
+pub fn harmony_copy(
+    from: &str,
+    new_label: &str,
+    ip: Option<&str>,
+    port: Option<u16>,
+) -> anyhow::Result<()> {
+    let instances = crate::storage::load_harmony_instances()?;
+    let source = instances
+        .iter()
+        .find(|i| i.id == from)
+        .or_else(|| instances.iter().find(|i| i.label == from))
+        .ok_or_else(|| anyhow::anyhow!("No Harmony instance found with ID or label '{}'", from))?;
+
+    let copy = crate::storage::HarmonyInstance {
+        id: String::new(),
+        ip: ip.unwrap_or(&source.ip).to_string(),
+        port: port.unwrap_or(source.port),
+        label: new_label.to_string(),
+        path_prefix: source.path_prefix.clone(),
+        gateway_id: None,
+        headers: source.headers.clone(),
+        tags: source.tags.clone(),
+        scheme: source.scheme.clone(),
+        version: None,
+        machine_token_expires_at: None,
+    };
+
+    // add_harmony_instance merges into an existing entry on a label or address
+    // match, which would silently overwrite instead of copying
+    if instances.iter().any(|i| i.label == copy.label) {
+        anyhow::bail!("An instance with label '{}' already exists", copy.label);
+    }
+    if instances
+        .iter()
+        .any(|i| i.ip == copy.ip && i.port == copy.port)
+    {
+        anyhow::bail!(
+            "An instance at {}:{} is already registered; pass --ip and/or --port for the copy",
+            copy.ip,
+            copy.port
+        );
+    }
+
+    info!(from = %from, label = %new_label, ip = %copy.ip, port = %copy.port, "harmony:copy");
+    crate::storage::add_harmony_instance(copy.clone())?;
+
+    let instances = crate::storage::load_harmony_instances()?;
+    let saved = instances
+        .iter()
+        .find(|i| i.label == copy.label)
+        .ok_or_else(|| anyhow::anyhow!("Failed to retrieve saved instance"))?;
+
+    println!(
+        "Copied Harmony instance '{}' to {}:{} (ID: {}) label={} prefix={}",
+        source.label, saved.ip, saved.port, saved.id, saved.label, saved.path_prefix
+    );
+    Ok(())
+}
+
+pub fn harmony_rename_by_label(label: &str, new_label: &str) -> anyhow::Result<()> {
+    if crate::storage::rename_harmony_instance(label, new_label)? {
+        println!(
+            "Renamed Harmony instance '{}' to '{}' (ID unchanged).",
+            label, new_label
+        );
+    } else {
+        println!("No Harmony instance found with label '{}'.", label);
+    }
+    Ok(())
+}
+
+pub fn harmony_tag(id: &str, tags: &[String]) -> anyhow::Result<()> {
+    if crate::storage::tag_harmony_instance(id, tags)? {
+        println!("Tagged Harmony instance '{}' with: {}", id, tags.join(", "));
+    } else {
+        println!("No Harmony instance found with id '{}'.", id);
+    }
+    Ok(())
+}
+
+pub fn harmony_untag(id: &str, tags: &[String]) -> anyhow::Result<()> {
+    if crate::storage::untag_harmony_instance(id, tags)? {
+        println!(
+            "Removed tags from Harmony instance '{}': {}",
+            id,
+            tags.join(", ")
+        );
+    } else {
+        println!("No Harmony instance found with id '{}'.", id);
+    }
+    Ok(())
+}
+
+/// List registered instances, optionally only those carrying `tag`
+pub fn harmony_list(
+    out: &mut dyn Write,
+    tag: Option<&str>,
+    json: bool,
+    check_auth: bool,
+) -> anyhow::Result<()> {
     let list = crate::storage::load_harmony_instances()?;
+    let auth_now = check_auth.then(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0)
+    });
+    if json {
+        return write_instance_json(out, list, tag, auth_now);
+    }
+    write_instance_table(out, list, tag, auth_now)
+}
+
+/// Authorization state for `harmony:list --check-auth`, from the stored machine
+/// token expiry as of `now`: `authorized (exp in Nd)`, `expired` or `no`
+///
+/// Instances authorized by a CLI version that did not record the expiry show
+/// as plain `authorized`.
+fn auth_state(inst: &crate::storage::HarmonyInstance, now: i64) -> String {
+    match inst.machine_token_expires_at {
+        Some(expires_at) if expires_at <= now => "expired".to_string(),
+        Some(expires_at) => match (expires_at - now) / 86400 {
+            0 => "authorized (exp in <1d)".to_string(),
+            days => format!("authorized (exp in {}d)", days),
+        },
+        None if inst.gateway_id.is_some() => "authorized".to_string(),
+        None => "no".to_string(),
+    }
+}
+
+/// Shown instead of stored header values in `harmony:list` JSON
+const MASKED_HEADER_VALUE: &str = "********";
+
+/// Write `list` (optionally filtered by `tag`) as a JSON array for `harmony:list --json`
+///
+/// Each entry has the stored fields plus the computed management `base_url`, and
+/// the `auth` state when `auth_now` is given. Header values are masked, since they
+/// usually carry credentials.
+fn write_instance_json(
+    out: &mut dyn Write,
+    mut list: Vec<crate::storage::HarmonyInstance>,
+    tag: Option<&str>,
+    auth_now: Option<i64>,
+) -> anyhow::Result<()> {
+    if let Some(tag) = tag {
+        list.retain(|i| i.tags.iter().any(|t| t == tag));
+    }
+    let entries = list
+        .into_iter()
+        .map(|mut inst| {
+            let base_url = management::base_url(&inst);
+            let auth = auth_now.map(|now| auth_state(&inst, now));
+            for (_, value) in &mut inst.headers {
+                *value = MASKED_HEADER_VALUE.to_string();
+            }
+            let mut entry = serde_json::to_value(&inst)?;
+            entry["base_url"] = serde_json::Value::String(base_url);
+            if let Some(auth) = auth {
+                entry["auth"] = serde_json::Value::String(auth);
+            }
+            Ok(entry)
+        })
+        .collect::<serde_json::Result<Vec<_>>>()?;
+    writeln!(out, "{}", crate::output::to_json(&entries)?)?;
+    Ok(())
+}
+
+/// Write `list` (optionally filtered by `tag`) as the `harmony:list` table
+///
+/// With `auth_now`, an AUTH column shows each instance's authorization state as of
+/// that time (see [`auth_state`]).
+pub(super) fn write_instance_table(
+    out: &mut dyn Write,
+    mut list: Vec<crate::storage::HarmonyInstance>,
+    tag: Option<&str>,
+    auth_now: Option<i64>,
+) -> anyhow::Result<()> {
+    if let Some(tag) = tag {
+        list.retain(|i| i.tags.iter().any(|t| t == tag));
+        if list.is_empty() {
+            writeln!(out, "No Harmony instances tagged '{}'.", tag)?;
+            return Ok(());
+        }
+    }
     if list.is_empty() {
-        println!("No Harmony instances registered.");
+        writeln!(out, "No Harmony instances registered.")?;
+        return Ok(());
+    }
+
+    let auth: Option<Vec<String>> =
+        auth_now.map(|now| list.iter().map(|inst| auth_state(inst, now)).collect());
+
+    if crate::output::markdown() {
+        let mut headers = ["ID", "GATEWAY_ID", "LABEL", "IP", "PORT", "PREFIX", "TAGS"]
+            .map(String::from)
+            .to_vec();
+        if auth.is_some() {
+            headers.push("AUTH".to_string());
+        }
+        let rows: Vec<Vec<String>> = list
+            .into_iter()
+            .enumerate()
+            .map(|(i, inst)| {
+                let mut row = vec![
+                    inst.id,
+                    inst.gateway_id.unwrap_or_default(),
+                    inst.label,
+                    inst.ip,
+                    inst.port.to_string(),
+                    inst.path_prefix,
+                    inst.tags.join(","),
+                ];
+                if let Some(auth) = &auth {
+                    row.push(auth[i].clone());
+                }
+                row
+            })
+            .collect();
+        crate::output::write_markdown_table(out, &headers, &rows)?;
         return Ok(());
     }
 
@@ -66,6 +602,7 @@ pub fn harmony_list() -> anyhow::Result<()> {
     let mut w_ip = "IP".len();
     let mut w_port = "PORT".len();
     let mut w_prefix = "PREFIX".len();
+    let mut w_tags = "TAGS".len();
     for inst in &list {
         if inst.id.len() > w_id {
             w_id = inst.id.len();
@@ -87,58 +624,88 @@ pub fn harmony_list() -> anyhow::Result<()> {
         if inst.path_prefix.len() > w_prefix {
             w_prefix = inst.path_prefix.len();
         }
+        let tags_len = inst.tags.join(",").len();
+        if tags_len > w_tags {
+            w_tags = tags_len;
+        }
     }
+    let w_auth = auth
+        .iter()
+        .flatten()
+        .map(String::len)
+        .fold("AUTH".len(), std::cmp::max);
 
     // Header
-    println!(
-        "{id:<id_w$} | {gateway_id:<gw_w$} | {label:<label_w$} | {ip:<ip_w$} | {port:<port_w$} | {prefix:<prefix_w$}",
+    write!(
+        out,
+        "{id:<id_w$} | {gateway_id:<gw_w$} | {label:<label_w$} | {ip:<ip_w$} | {port:<port_w$} | {prefix:<prefix_w$} | {tags:<tags_w$}",
         id = "ID",
         gateway_id = "GATEWAY_ID",
         label = "LABEL",
         ip = "IP",
         port = "PORT",
         prefix = "PREFIX",
+        tags = "TAGS",
         id_w = w_id,
         gw_w = w_gateway_id,
         label_w = w_label,
         ip_w = w_ip,
         port_w = w_port,
         prefix_w = w_prefix,
-    );
+        tags_w = w_tags,
+    )?;
+    if auth.is_some() {
+        write!(out, " | {:<w$}", "AUTH", w = w_auth)?;
+    }
+    writeln!(out)?;
     // Separator
-    println!(
-        "{id:-<id_w$}-+-{gateway_id:-<gw_w$}-+-{label:-<label_w$}-+-{ip:-<ip_w$}-+-{port:-<port_w$}-+-{prefix:-<prefix_w$}",
+    write!(
+        out,
+        "{id:-<id_w$}-+-{gateway_id:-<gw_w$}-+-{label:-<label_w$}-+-{ip:-<ip_w$}-+-{port:-<port_w$}-+-{prefix:-<prefix_w$}-+-{tags:-<tags_w$}",
         id = "",
         gateway_id = "",
         label = "",
         ip = "",
         port = "",
         prefix = "",
+        tags = "",
         id_w = w_id,
         gw_w = w_gateway_id,
         label_w = w_label,
         ip_w = w_ip,
         port_w = w_port,
         prefix_w = w_prefix,
-    );
+        tags_w = w_tags,
+    )?;
+    if auth.is_some() {
+        write!(out, "-+-{:-<w$}", "", w = w_auth)?;
+    }
+    writeln!(out)?;
     // Rows
-    for inst in list {
+    for (i, inst) in list.into_iter().enumerate() {
         let gateway_id_display = inst.gateway_id.as_deref().unwrap_or("");
-        println!(
-            "{id:<id_w$} | {gateway_id:<gw_w$} | {label:<label_w$} | {ip:<ip_w$} | {port:<port_w$} | {prefix:<prefix_w$}",
+        write!(
+            out,
+            "{id:<id_w$} | {gateway_id:<gw_w$} | {label:<label_w$} | {ip:<ip_w$} | {port:<port_w$} | {prefix:<prefix_w$} | {tags:<tags_w$}",
             id = inst.id,
             gateway_id = gateway_id_display,
             label = inst.label,
             ip = inst.ip,
             port = inst.port,
             prefix = inst.path_prefix,
+            tags = inst.tags.join(","),
             id_w = w_id,
             gw_w = w_gateway_id,
             label_w = w_label,
             ip_w = w_ip,
             port_w = w_port,
             prefix_w = w_prefix,
-        );
+            tags_w = w_tags,
+        )?;
+        if let Some(auth) = &auth {
+            write!(out, " | {:<w$}", auth[i], w = w_auth)?;
+        }
+        writeln!(out)?;
     }
 
     Ok(())
@@ -152,8 +719,11 @@ pub fn harmony_remove(
 ) -> anyhow::Result<()> {
     if let Some(id) = id {
         let removed = crate::storage::remove_harmony_instance_by_id(id)?;
-        if removed {
-            println!("Removed Harmony instance with id '{}'.", id);
+        if let Some(inst) = removed {
+            println!(
+                "Removed Harmony instance with id '{}' ({}).",
+                inst.id, inst.label
+            );
         } else {
             println!("No Harmony instance found with id '{}'.", id);
         }
@@ -186,6 +756,61 @@ pub fn harmony_remove(
     }
 }
 
+/// Remove every instance that does not answer `GET /{prefix}/info` within `timeout`
+///
+/// The unreachable instances are listed and nothing is removed without confirmation.
+pub fn harmony_remove_stale(timeout: Duration) -> anyhow::Result<()> {
+    let instances = crate::storage::load_harmony_instances()?;
+    if instances.is_empty() {
+        println!("No Harmony instances registered.");
+        return Ok(());
+    }
+
+    // A tunnel that cannot be opened says nothing about the instances, so it must
+    // not get them listed as stale
+    for inst in &instances {
+        crate::tunnel::open(inst)?;
+    }
+    println!(
+        "Probing {} instance(s) (timeout {})...",
+        instances.len(),
+        crate::cli::format_duration(timeout)
+    );
+    let stale: Vec<_> = instances
+        .iter()
+        .zip(management::probe_all(&instances, timeout))
+        .filter_map(|(inst, result)| result.err().map(|e| (inst, format!("{:#}", e))))
+        .collect();
+
+    if stale.is_empty() {
+        println!(
+            "All {} instance(s) responded; nothing to remove.",
+            instances.len()
+        );
+        return Ok(());
+    }
+
+    println!("Unreachable instances:");
+    for (inst, err) in &stale {
+        println!("  {} ({}:{}) - {}", inst.label, inst.ip, inst.port, err);
+    }
+    if !crate::prompt::confirm(&format!("Remove {} stale instance(s)?", stale.len()))? {
+        println!("Nothing removed.");
+        return Ok(());
+    }
+
+    for (inst, _) in &stale {
+        crate::storage::remove_harmony_instance_by_id(&inst.id)?;
+        info!(id = %inst.id, label = %inst.label, "removed stale instance");
+    }
+    println!(
+        "Removed {} stale instance(s); {} remaining.",
+        stale.len(),
+        instances.len() - stale.len()
+    );
+    Ok(())
+}
+
 /// Set or update the encryption key for a Harmony instance (DEPRECATED)
 ///
 /// This command is deprecated. Encryption keys are now managed automatically
@@ -242,3 +867,192 @@ pub fn harmony_delete_key(_instance_id: &str) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_wizard_defaults_and_answers() {
+        let mut out = Vec::new();
+        let mut input: &[u8] = b"\n\n\n\n\n";
+        assert_eq!(
+            add_wizard_with(&mut input, &mut out, "https").unwrap(),
+            AddDetails {
+                ip: "127.0.0.1".to_string(),
+                port: 8081,
+                label: "127.0.0.1:8081".to_string(),
+                path_prefix: "admin".to_string(),
+                scheme: "https".to_string(),
+            }
+        );
+
+        // Invalid answers are asked again
+        let mut out = Vec::new();
+        let mut input: &[u8] = b"10.0.0.5\n0\n9090\nedge\nmy admin\nmgmt\nftp\nHTTP\n";
+        let details = add_wizard_with(&mut input, &mut out, "http").unwrap();
+        assert_eq!(details.ip, "10.0.0.5");
+        assert_eq!(details.port, 9090);
+        assert_eq!(details.label, "edge");
+        assert_eq!(details.path_prefix, "mgmt");
+        assert_eq!(details.scheme, "http");
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Port must be a number from 1 to 65535."));
+        assert!(out.contains("Label [10.0.0.5:9090]: "));
+        assert!(out.contains("invalid scheme 'ftp'"));
+    }
+
+    #[test]
+    fn test_parse_header_colon_and_equals() {
+        assert_eq!(
+            parse_header("X-Api-Key: secret").unwrap(),
+            ("X-Api-Key".to_string(), "secret".to_string())
+        );
+        assert_eq!(
+            parse_header("X-Env=prod").unwrap(),
+            ("X-Env".to_string(), "prod".to_string())
+        );
+        // Only the first separator splits, so values may contain colons
+        assert_eq!(
+            parse_header("Authorization: Basic a:b").unwrap(),
+            ("Authorization".to_string(), "Basic a:b".to_string())
+        );
+        assert_eq!(
+            parse_header("X-Target=host:8080").unwrap(),
+            ("X-Target".to_string(), "host:8080".to_string())
+        );
+        assert_eq!(
+            parse_header("X-Query: a=b").unwrap(),
+            ("X-Query".to_string(), "a=b".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_header_invalid() {
+        assert!(parse_header("no-separator").is_err());
+        assert!(parse_header(": value").is_err());
+        assert!(parse_header("Bad Name: value").is_err());
+        assert!(parse_header("X-Bad(Name): value").is_err());
+        assert!(parse_header("X-Api-Key: line\u{7f}break").is_err());
+    }
+
+    #[test]
+    fn test_merge_headers_overrides_case_insensitively() {
+        let header = |name: &str, value: &str| (name.to_string(), value.to_string());
+        let merged = merge_headers(
+            &[header("X-Api-Key", "old"), header("X-Env", "prod")],
+            vec![header("x-api-key", "new")],
+        );
+        assert_eq!(
+            merged,
+            [header("X-Env", "prod"), header("x-api-key", "new")]
+        );
+    }
+
+    fn instance(label: &str, tags: &[&str]) -> crate::storage::HarmonyInstance {
+        crate::storage::HarmonyInstance {
+            id: format!("id-{}", label),
+            ip: "10.0.0.5".to_string(),
+            port: 8081,
+            label: label.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn list_output(list: Vec<crate::storage::HarmonyInstance>, tag: Option<&str>) -> String {
+        let mut out = Vec::new();
+        write_instance_table(&mut out, list, tag, None).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_write_instance_table() {
+        let output = list_output(vec![instance("prod", &["eu", "prod"])], None);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines[0],
+            "ID      | GATEWAY_ID | LABEL | IP       | PORT | PREFIX | TAGS   "
+        );
+        assert_eq!(
+            lines[2],
+            "id-prod |            | prod  | 10.0.0.5 | 8081 | admin  | eu,prod"
+        );
+        assert_eq!(lines.len(), 3);
+    }
+
+    #[test]
+    fn test_write_instance_table_with_auth_column() {
+        let now = 1_700_000_000;
+        let mut fresh = instance("fresh", &[]);
+        fresh.gateway_id = Some("gw-1".to_string());
+        fresh.machine_token_expires_at = Some(now + 30 * 86400 + 60);
+        let mut stale = instance("stale", &[]);
+        stale.gateway_id = Some("gw-2".to_string());
+        stale.machine_token_expires_at = Some(now - 1);
+        let mut out = Vec::new();
+        write_instance_table(
+            &mut out,
+            vec![fresh, stale, instance("new", &[])],
+            None,
+            Some(now),
+        )
+        .unwrap();
+        let output = String::from_utf8(out).unwrap();
+        let auth: Vec<&str> = output
+            .lines()
+            .filter(|line| !line.starts_with('-'))
+            .map(|line| line.rsplit(" | ").next().unwrap().trim_end())
+            .collect();
+        assert_eq!(auth, ["AUTH", "authorized (exp in 30d)", "expired", "no"]);
+        assert!(
+            output
+                .lines()
+                .nth(1)
+                .unwrap()
+                .ends_with("-+------------------------")
+        );
+    }
+
+    #[test]
+    fn test_auth_state() {
+        let now = 1_700_000_000;
+        let mut inst = instance("edge", &[]);
+        assert_eq!(auth_state(&inst, now), "no");
+        inst.gateway_id = Some("gw-1".to_string());
+        assert_eq!(auth_state(&inst, now), "authorized");
+        inst.machine_token_expires_at = Some(now + 3600);
+        assert_eq!(auth_state(&inst, now), "authorized (exp in <1d)");
+        inst.machine_token_expires_at = Some(now);
+        assert_eq!(auth_state(&inst, now), "expired");
+    }
+
+    #[test]
+    fn test_write_instance_table_empty_and_filtered() {
+        assert_eq!(
+            list_output(Vec::new(), None),
+            "No Harmony instances registered.\n"
+        );
+        assert_eq!(
+            list_output(vec![instance("a", &["eu"])], Some("us")),
+            "No Harmony instances tagged 'us'.\n"
+        );
+        let output = list_output(
+            vec![instance("a", &["eu"]), instance("b", &["us"])],
+            Some("us"),
+        );
+        assert!(output.contains("| b "));
+        assert!(!output.contains("| a "));
+    }
+
+    #[test]
+    fn test_address_conflict() {
+        let list = vec![instance("foo", &[])];
+        let conflict = address_conflict(&list, "10.0.0.5", 8081, "bar");
+        assert_eq!(conflict.map(|i| i.label.as_str()), Some("foo"));
+        // Re-adding the same label is an update, not a duplicate
+        assert!(address_conflict(&list, "10.0.0.5", 8081, "foo").is_none());
+        assert!(address_conflict(&list, "10.0.0.5", 8082, "bar").is_none());
+        assert!(address_conflict(&list, "10.0.0.6", 8081, "bar").is_none());
+    }
+}
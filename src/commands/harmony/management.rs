@@ -1,16 +1,35 @@
 use anyhow::{Context, Result, anyhow};
-use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use reqwest::blocking::{RequestBuilder, Response};
+use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 use std::cmp;
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
 
-fn resolve_instance(
+use super::{batch, diff, group};
+use crate::cli::{OutputFormat, RouteSort};
+use crate::commands::auth;
+use crate::http;
+use crate::output;
+use crate::retry;
+use crate::style;
+use crate::timings;
+
+pub(super) fn resolve_instance(
     id: Option<&str>,
     label: Option<&str>,
 ) -> Result<crate::storage::HarmonyInstance> {
-    let list = crate::storage::load_harmony_instances()?;
+    let list = crate::storage::merge_inventory(
+        crate::storage::load_harmony_instances()?,
+        crate::storage::load_inventory()?,
+    );
     if let Some(id) = id {
-        if let Some(inst) = list.into_iter().find(|i| i.id == id) {
-            return Ok(inst);
+        if let Some(inst) = crate::storage::find_by_id(&list, id)? {
+            return Ok(inst.clone());
         }
         return Err(anyhow!("no instance with id '{}'", id));
     }
@@ -23,57 +42,119 @@ fn resolve_instance(
     Err(anyhow!("must supply --id or --label"))
 }
 
-fn render_json_table(v: &Value) {
+/// Resolve an instance for a management call and open its `--connect-via` tunnel
+fn connect(id: Option<&str>, label: Option<&str>) -> Result<crate::storage::HarmonyInstance> {
+    let inst = resolve_instance(id, label)?;
+    crate::tunnel::open(&inst)?;
+    Ok(inst)
+}
+
+fn render_json_table(out: &mut dyn Write, v: &Value, columns: &[String]) -> io::Result<()> {
     match v {
-        Value::Object(obj) => render_kv_table(obj),
-        Value::Array(arr) => render_array_of_objects(arr),
-        _ => println!("{}", v),
+        Value::Object(obj) => render_kv_table(out, obj, columns),
+        Value::Array(arr) => render_array_of_objects(out, arr, columns),
+        _ => writeln!(out, "{}", v),
     }
 }
 
-fn render_kv_table(obj: &Map<String, Value>) {
-    let mut rows: Vec<(String, String)> = obj
-        .iter()
-        .map(|(k, v)| (k.clone(), stringify_value(v)))
+/// Print an object as key/value rows sorted by key, or only the keys named in
+/// `columns`, in that order (see [`select_columns`])
+fn render_kv_table(
+    out: &mut dyn Write,
+    obj: &Map<String, Value>,
+    columns: &[String],
+) -> io::Result<()> {
+    let mut keys: Vec<String> = obj.keys().cloned().collect();
+    keys.sort();
+    let rows: Vec<(String, String)> = select_columns(keys, columns)
+        .into_iter()
+        .map(|k| {
+            let value = stringify_value(&obj[&k]);
+            (k, value)
+        })
         .collect();
-    rows.sort_by(|a, b| a.0.cmp(&b.0));
+    print_kv_rows(out, &rows)
+}
 
+/// Print key/value rows as a two-column table, in the given order
+fn print_kv_rows(out: &mut dyn Write, rows: &[(String, String)]) -> io::Result<()> {
+    if output::markdown() {
+        let rows: Vec<Vec<String>> = rows
+            .iter()
+            .map(|(k, v)| vec![k.clone(), v.clone()])
+            .collect();
+        return output::write_markdown_table(out, &["KEY".to_string(), "VALUE".to_string()], &rows);
+    }
     let mut w_key = "KEY".len();
     let mut w_val = "VALUE".len();
-    for (k, val) in &rows {
+    for (k, val) in rows {
         w_key = cmp::max(w_key, k.len());
         w_val = cmp::max(w_val, val.len());
     }
 
-    println!(
+    writeln!(
+        out,
         "{k:<kw$} | {v:<vw$}",
         k = "KEY",
         v = "VALUE",
         kw = w_key,
         vw = w_val
-    );
-    println!(
+    )?;
+    writeln!(
+        out,
         "{k:-<kw$}-+-{v:-<vw$}",
         k = "",
         v = "",
         kw = w_key,
         vw = w_val
-    );
+    )?;
     for (k, val) in rows {
-        println!(
+        writeln!(
+            out,
             "{k:<kw$} | {v:<vw$}",
             k = k,
             v = val,
             kw = w_key,
             vw = w_val
-        );
+        )?;
+    }
+    Ok(())
+}
+
+/// Restrict `available` columns to those named in `requested`, in the requested order
+///
+/// Names match case-insensitively. Unknown names are skipped with a warning; if
+/// none of the requested names exist, every column is kept.
+fn select_columns(available: Vec<String>, requested: &[String]) -> Vec<String> {
+    if requested.is_empty() {
+        return available;
+    }
+    let mut selected: Vec<String> = Vec::new();
+    for name in requested {
+        match available.iter().find(|c| c.eq_ignore_ascii_case(name)) {
+            Some(col) if !selected.contains(col) => selected.push(col.clone()),
+            Some(_) => {}
+            None => warn!(
+                "unknown column '{}' (available: {})",
+                name,
+                available.join(", ")
+            ),
+        }
+    }
+    if selected.is_empty() {
+        warn!("none of the requested columns exist; showing all columns");
+        return available;
     }
+    selected
 }
 
-fn render_array_of_objects(arr: &[Value]) {
+fn render_array_of_objects(
+    out: &mut dyn Write,
+    arr: &[Value],
+    columns: &[String],
+) -> io::Result<()> {
     if arr.is_empty() {
-        println!("(no results)");
-        return;
+        return writeln!(out, "(no results)");
     }
     // Collect union of keys
     let mut cols: Vec<String> = Vec::new();
@@ -88,11 +169,22 @@ fn render_array_of_objects(arr: &[Value]) {
     }
     if cols.is_empty() {
         // Not objects; just print JSON
-        println!(
-            "{}",
-            serde_json::to_string_pretty(&Value::Array(arr.to_vec())).unwrap()
-        );
-        return;
+        return writeln!(out, "{}", output::to_json(arr)?);
+    }
+    let cols = select_columns(cols, columns);
+
+    if output::markdown() {
+        let headers: Vec<String> = cols.iter().map(|c| c.to_uppercase()).collect();
+        let rows: Vec<Vec<String>> = arr
+            .iter()
+            .filter_map(Value::as_object)
+            .map(|m| {
+                cols.iter()
+                    .map(|col| stringify_value(m.get(col).unwrap_or(&Value::Null)))
+                    .collect()
+            })
+            .collect();
+        return output::write_markdown_table(out, &headers, &rows);
     }
 
     // Compute widths
@@ -109,32 +201,33 @@ fn render_array_of_objects(arr: &[Value]) {
     // Header
     for (i, col) in cols.iter().enumerate() {
         if i > 0 {
-            print!(" | ");
+            write!(out, " | ")?;
         }
-        print!("{val:<w$}", val = col.to_uppercase(), w = widths[i]);
+        write!(out, "{val:<w$}", val = col.to_uppercase(), w = widths[i])?;
     }
-    println!();
+    writeln!(out)?;
     // Separator
     for (i, w) in widths.iter().copied().enumerate() {
         if i > 0 {
-            print!("-+-");
+            write!(out, "-+-")?;
         }
-        print!("{dash:-<w$}", dash = "", w = w);
+        write!(out, "{dash:-<w$}", dash = "", w = w)?;
     }
-    println!();
+    writeln!(out)?;
     // Rows
     for v in arr {
         if let Value::Object(m) = v {
             for (i, col) in cols.iter().enumerate() {
                 if i > 0 {
-                    print!(" | ");
+                    write!(out, " | ")?;
                 }
                 let s = stringify_value(m.get(col).unwrap_or(&Value::Null));
-                print!("{val:<w$}", val = s, w = widths[i]);
+                write!(out, "{val:<w$}", val = s, w = widths[i])?;
             }
-            println!();
+            writeln!(out)?;
         }
     }
+    Ok(())
 }
 
 fn stringify_value(v: &Value) -> String {
@@ -161,131 +254,2437 @@ fn stringify_value(v: &Value) -> String {
     }
 }
 
-fn base_url(inst: &crate::storage::HarmonyInstance) -> String {
-    format!(
-        "http://{}:{}/{}",
-        inst.ip,
-        inst.port,
-        inst.path_prefix.trim_matches('/')
-    )
+/// Default cap on management API response bodies (10 MiB)
+const DEFAULT_MAX_RESPONSE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Maximum response body size, overridable with `RUNBEAM_MAX_RESPONSE_BYTES`
+fn max_response_bytes() -> u64 {
+    std::env::var("RUNBEAM_MAX_RESPONSE_BYTES")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_MAX_RESPONSE_BYTES)
 }
 
-pub fn info(id: Option<&str>, label: Option<&str>) -> Result<()> {
-    let inst = resolve_instance(id, label)?;
-    let url = format!("{}/info", base_url(&inst));
-    let client = Client::new();
-    let resp = client
-        .get(&url)
-        .send()
-        .with_context(|| format!("GET {}", url))?;
+/// Read a response body, refusing to buffer more than `limit` bytes
+fn read_body_limited(resp: Response, url: &str, limit: u64) -> Result<String> {
+    if let Some(addr) = resp.remote_addr() {
+        debug!(url, %addr, "response received");
+    }
+    if resp.content_length().is_some_and(|len| len > limit) {
+        return Err(anyhow!("response from {} exceeded {} bytes", url, limit));
+    }
 
-    if !resp.status().is_success() {
-        return Err(anyhow!("{} {}", resp.status(), url));
+    let mut buf = Vec::new();
+    resp.take(limit + 1)
+        .read_to_end(&mut buf)
+        .with_context(|| format!("reading response from {}", url))?;
+    if buf.len() as u64 > limit {
+        return Err(anyhow!("response from {} exceeded {} bytes", url, limit));
+    }
+
+    String::from_utf8(buf).with_context(|| format!("response from {} is not valid UTF-8", url))
+}
+
+fn read_body(resp: Response, url: &str) -> Result<String> {
+    read_body_limited(resp, url, max_response_bytes())
+}
+
+/// Counts the bytes read through it, reporting the running total on stderr
+///
+/// On a terminal the count is redrawn in place as chunks arrive; otherwise only
+/// the final total is printed, once the body has been read. Nothing is printed
+/// with `--quiet`.
+struct Progress<R> {
+    inner: R,
+    transferred: u64,
+    total: Option<u64>,
+    live: bool,
+}
+
+impl<R: Read> Progress<R> {
+    fn new(inner: R, total: Option<u64>) -> Self {
+        use std::io::IsTerminal;
+        Self {
+            inner,
+            transferred: 0,
+            total,
+            live: io::stderr().is_terminal() && !output::quiet(),
+        }
+    }
+
+    fn line(&self) -> String {
+        match self.total {
+            Some(total) => format!("⏳ Transferred {} of {} bytes", self.transferred, total),
+            None => format!("⏳ Transferred {} bytes", self.transferred),
+        }
+    }
+
+    /// Print the final count, ending the in-place line on a terminal
+    fn finish(&self) {
+        if self.live {
+            eprintln!("\r{}", self.line());
+        } else if !output::quiet() {
+            eprintln!("{}", self.line());
+        }
+    }
+}
+
+impl<R: Read> Read for Progress<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.transferred += n as u64;
+        if self.live && n > 0 {
+            eprint!("\r{}", self.line());
+            let _ = io::stderr().flush();
+        }
+        Ok(n)
+    }
+}
+
+/// [`read_body`], reporting the number of bytes transferred as they arrive
+fn read_body_with_progress(resp: Response, url: &str) -> Result<String> {
+    if let Some(addr) = resp.remote_addr() {
+        debug!(url, %addr, "response received");
+    }
+    let limit = max_response_bytes();
+    if resp.content_length().is_some_and(|len| len > limit) {
+        return Err(anyhow!("response from {} exceeded {} bytes", url, limit));
+    }
+
+    let total = resp.content_length();
+    let mut reader = Progress::new(resp.take(limit + 1), total);
+    let mut buf = Vec::new();
+    let read = reader.read_to_end(&mut buf);
+    reader.finish();
+    read.with_context(|| format!("reading response from {}", url))?;
+    if buf.len() as u64 > limit {
+        return Err(anyhow!("response from {} exceeded {} bytes", url, limit));
     }
 
-    let json: Value = resp.json().context("parsing JSON response")?;
-    if let Some(arr) = json.get("routes").and_then(|v| v.as_array()) {
-        render_array_of_objects(arr);
+    String::from_utf8(buf).with_context(|| format!("response from {} is not valid UTF-8", url))
+}
+
+fn read_json(resp: Response, url: &str) -> Result<Value> {
+    let body = read_body(resp, url)?;
+    serde_json::from_str(&body).context("parsing JSON response")
+}
+
+/// Longest response body kept in a [`StatusError`]
+const ERROR_BODY_LIMIT: usize = 1024;
+
+/// A management API call that answered with a non-2xx status
+///
+/// Displays as `<status> <url> (trace id <id>)` (plus a hint for 401/403); in JSON output mode
+/// [`json_errors`] turns it into an `{"error": {...}}` envelope on stdout, including
+/// the (truncated) response body.
+#[derive(Debug)]
+pub struct StatusError {
+    status: StatusCode,
+    url: String,
+    body: String,
+    /// `X-Request-Id` sent with the request
+    trace_id: String,
+}
+
+impl StatusError {
+    fn new(status: StatusCode, url: &str, body: &str) -> Self {
+        StatusError {
+            status,
+            url: url.to_string(),
+            body: truncate_body(body, ERROR_BODY_LIMIT),
+            trace_id: http::trace_id().to_string(),
+        }
+    }
+
+    fn envelope(&self) -> Value {
+        let mut error = serde_json::json!({
+            "status": self.status.as_u16(),
+            "url": self.url,
+            "body": self.body,
+            "trace_id": self.trace_id,
+        });
+        if let Some(hint) = self.hint() {
+            error["hint"] = Value::String(hint);
+        }
+        serde_json::json!({ "error": error })
+    }
+
+    /// Advice for 401/403, which usually mean a proxy protects the management prefix
+    fn hint(&self) -> Option<String> {
+        matches!(
+            self.status,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+        )
+        .then(|| {
+            format!(
+                "Harmony returned {} — this instance may require an auth header (see `harmony:set-header` or `harmony:add --header`) or the management API may be protected.",
+                self.status.as_u16()
+            )
+        })
+    }
+}
+
+impl std::fmt::Display for StatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} (trace id {})",
+            self.status, self.url, self.trace_id
+        )?;
+        if let Some(hint) = self.hint() {
+            write!(f, "\n{}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+/// Build the error for a non-2xx response, keeping the start of its body
+///
+/// A body that cannot be read is recorded as empty rather than masking the status.
+fn status_error(resp: Response, url: &str) -> anyhow::Error {
+    let status = resp.status();
+    let mut buf = Vec::new();
+    if let Err(e) = resp.take(ERROR_BODY_LIMIT as u64 + 1).read_to_end(&mut buf) {
+        debug!("could not read error body from {}: {}", url, e);
+    }
+    StatusError::new(status, url, &String::from_utf8_lossy(&buf)).into()
+}
+
+/// Context for a request that got no response, naming the trace ID to quote in support requests
+fn failed_request(method: &str, url: &str) -> String {
+    format!("{} {} (trace id {})", method, url, http::trace_id())
+}
+
+/// Cut `body` to at most `limit` bytes (on a character boundary), marking the cut with `...`
+fn truncate_body(body: &str, limit: usize) -> String {
+    if body.len() <= limit {
+        return body.to_string();
+    }
+    let mut end = limit;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...", &body[..end])
+}
+
+/// With JSON output, print a failed management call as an `{"error": ...}` envelope on stdout
+///
+/// The error is still returned, so the exit status stays non-zero.
+fn json_errors(json: bool, result: Result<()>) -> Result<()> {
+    if json
+        && let Err(e) = &result
+        && let Some(status) = e.downcast_ref::<StatusError>()
+    {
+        output::print_json(&status.envelope())?;
+    }
+    result
+}
+
+/// `Accept` value for requests whose response is parsed as JSON
+const ACCEPT_JSON: &str = "application/json";
+
+/// `Accept` value for `--raw-body`, where the response is shown as-is
+const ACCEPT_ANY: &str = "*/*";
+
+/// Attach the `Accept` header and the instance's stored request headers to a
+/// management API request
+///
+/// A stored `Accept` header takes precedence over `accept`.
+fn with_instance_headers(
+    mut req: RequestBuilder,
+    inst: &crate::storage::HarmonyInstance,
+    accept: &str,
+) -> RequestBuilder {
+    if !inst
+        .headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("accept"))
+    {
+        req = req.header(reqwest::header::ACCEPT, accept);
+    }
+    for (name, value) in &inst.headers {
+        req = req.header(name, value);
+    }
+    req
+}
+
+/// Send a management API request, retrying transient failures of GET and HEAD
+/// requests with the shared policy (see [`retry::policy_for`])
+///
+/// `request` builds a fresh request for every attempt; `phase` names it in
+/// `--timings` and in retry warnings.
+fn send(phase: String, request: impl FnMut() -> RequestBuilder) -> reqwest::Result<Response> {
+    let mut request = request;
+    let policy = retry::policy_for(request().build()?.method());
+    send_with(phase, &policy, request)
+}
+
+/// [`send`] with an explicit retry policy, for requests that opt in to retries
+/// although their method is not idempotent
+fn send_with(
+    phase: String,
+    policy: &retry::RetryPolicy,
+    mut request: impl FnMut() -> RequestBuilder,
+) -> reqwest::Result<Response> {
+    timings::time(&phase, || {
+        retry::with_retries(policy, &phase, || request().send())
+    })
+}
+
+/// Management API base URL for an instance, without a trailing slash
+///
+/// An empty path prefix means the API is served at the root.
+pub(crate) fn base_url(inst: &crate::storage::HarmonyInstance) -> String {
+    let origin = origin(inst);
+    let prefix = inst.path_prefix.trim_matches('/');
+    if prefix.is_empty() {
+        origin
+    } else {
+        format!("{}/{}", origin, prefix)
+    }
+}
+
+/// `scheme://host:port` for an instance, with IPv6 addresses in brackets
+fn origin(inst: &crate::storage::HarmonyInstance) -> String {
+    if inst.ip.contains(':') && !inst.ip.starts_with('[') {
+        format!("{}://[{}]:{}", inst.scheme, inst.ip, inst.port)
     } else {
-        render_json_table(&json);
+        format!("{}://{}:{}", inst.scheme, inst.ip, inst.port)
+    }
+}
+
+/// Print a response verbatim, without parsing it as JSON
+///
+/// The status line and content type go to stderr so the body can be piped.
+/// Non-success statuses are still reported as an error after the body is shown.
+fn print_raw_body(resp: Response, url: &str) -> Result<()> {
+    let status = resp.status();
+    let content_type = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("(none)")
+        .to_string();
+    let body = read_body(resp, url)?;
+
+    eprintln!("HTTP {}", status);
+    eprintln!("Content-Type: {}", content_type);
+    println!("{}", body);
+
+    if !status.is_success() {
+        return Err(StatusError::new(status, url, &body).into());
     }
     Ok(())
 }
 
-pub fn pipelines(id: Option<&str>, label: Option<&str>) -> Result<()> {
-    let inst = resolve_instance(id, label)?;
-    let url = format!("{}/pipelines", base_url(&inst));
-    let client = Client::new();
-    let resp = client
-        .get(&url)
-        .send()
-        .with_context(|| format!("GET {}", url))?;
+/// Check that an instance answers `GET /{prefix}/info` within `timeout`
+pub(crate) fn probe(inst: &crate::storage::HarmonyInstance, timeout: Duration) -> Result<()> {
+    let url = format!("{}/info", base_url(inst));
+    let client = http::blocking_client_builder()?
+        .timeout(timeout)
+        .build()
+        .context("building HTTP client")?;
+    let resp = timings::time(format!("GET {}", url), || {
+        with_instance_headers(client.get(&url), inst, ACCEPT_JSON).send()
+    })
+    .with_context(|| failed_request("GET", &url))?;
 
     if !resp.status().is_success() {
-        return Err(anyhow!("{} {}", resp.status(), url));
+        return Err(status_error(resp, &url));
     }
+    Ok(())
+}
 
-    let json: Value = resp.json().context("parsing JSON response")?;
-    if let Some(arr) = json.get("pipelines").and_then(|v| v.as_array()) {
-        render_array_of_objects(arr);
-    } else {
-        render_json_table(&json);
+/// Most instances [`probe_all`] checks at the same time
+const PROBE_CONCURRENCY: usize = 16;
+
+/// [`probe`] every instance, up to [`PROBE_CONCURRENCY`] at a time, so unreachable
+/// instances cost one `timeout` per batch instead of one each
+///
+/// Results are in the order of `instances`. Timings are recorded on the calling thread.
+pub(crate) fn probe_all(
+    instances: &[crate::storage::HarmonyInstance],
+    timeout: Duration,
+) -> Vec<Result<()>> {
+    let mut results = Vec::with_capacity(instances.len());
+    for batch in instances.chunks(PROBE_CONCURRENCY) {
+        let probed: Vec<(Result<()>, Duration)> = thread::scope(|scope| {
+            let handles: Vec<_> = batch
+                .iter()
+                .map(|inst| {
+                    scope.spawn(move || {
+                        let start = Instant::now();
+                        (probe(inst, timeout), start.elapsed())
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("probe thread panicked"))
+                .collect()
+        });
+        for (inst, (result, elapsed)) in batch.iter().zip(probed) {
+            timings::record(format!("GET {}/info", base_url(inst)), elapsed);
+            results.push(result);
+        }
+    }
+    results
+}
+
+/// Check the `version` reported in an `/info` response against a minimum
+///
+/// `feature` names what needs the version, for the error message.
+fn check_min_version(info: &Value, min: &str, feature: &str) -> Result<()> {
+    check_version(reported_version(info), min, feature)
+}
+
+/// The `version` field of an `/info` response, if present
+fn reported_version(info: &Value) -> Option<&str> {
+    info.get("version").and_then(|v| v.as_str())
+}
+
+/// Compare a reported Harmony version against a minimum
+fn check_version(reported: Option<&str>, min: &str, feature: &str) -> Result<()> {
+    let min_version = semver::Version::parse(min)
+        .with_context(|| format!("invalid minimum version '{}'", min))?;
+    let reported = reported.ok_or_else(|| {
+        anyhow!(
+            "{} requires Harmony >= {}, but the instance did not report a version",
+            feature,
+            min_version
+        )
+    })?;
+    let version = semver::Version::parse(reported.trim().trim_start_matches('v'))
+        .with_context(|| format!("instance reported an unparseable version '{}'", reported))?;
+
+    if version < min_version {
+        return Err(anyhow!(
+            "{} requires Harmony >= {}, instance reports {}",
+            feature,
+            min_version,
+            version
+        ));
     }
     Ok(())
 }
 
-pub fn routes(id: Option<&str>, label: Option<&str>, json: bool) -> Result<()> {
-    let inst = resolve_instance(id, label)?;
-    let url = format!("{}/routes", base_url(&inst));
-    let client = Client::new();
-    let resp = client
-        .get(&url)
-        .send()
-        .with_context(|| format!("GET {}", url))?;
+/// Fail early if an instance is older than `min`, before calling a version-gated endpoint
+///
+/// A version cached by the last successful `/info` that passes the check is trusted, so
+/// the check costs no round trip. Otherwise (no cached version, or one that is too old
+/// and may predate an upgrade) `/info` is fetched, its version cached and checked.
+///
+/// Returns the fetched `/info` response, if any, so callers after it need not fetch it again.
+pub(crate) fn require_min_version(
+    inst: &crate::storage::HarmonyInstance,
+    min: &str,
+    feature: &str,
+) -> Result<Option<Value>> {
+    let cached_error = match inst.version.as_deref() {
+        Some(cached) => match check_version(Some(cached), min, feature) {
+            Ok(()) => {
+                debug!("using cached version {} of '{}'", cached, inst.label);
+                return Ok(None);
+            }
+            Err(e) => {
+                debug!(
+                    "cached version of '{}' fails: {:#}; refreshing",
+                    inst.label, e
+                );
+                Some(e)
+            }
+        },
+        None => None,
+    };
+
+    match fetch_info(inst) {
+        Ok(info) => {
+            remember_version(inst, &info);
+            check_min_version(&info, min, feature)?;
+            Ok(Some(info))
+        }
+        // Unreachable (or --offline): the cached version is the best answer there is
+        Err(e) => match cached_error {
+            Some(cached) => Err(anyhow!("{} (cached; could not refresh: {:#})", cached, e)),
+            None => Err(e),
+        },
+    }
+}
+
+/// `GET /info` as JSON
+fn fetch_info(inst: &crate::storage::HarmonyInstance) -> Result<Value> {
+    let url = format!("{}/info", base_url(inst));
+    let client = http::blocking_client()?;
+    let resp = send(format!("GET {}", url), || {
+        with_instance_headers(client.get(&url), inst, ACCEPT_JSON)
+    })
+    .with_context(|| failed_request("GET", &url))?;
 
     if !resp.status().is_success() {
-        return Err(anyhow!("{} {}", resp.status(), url));
+        return Err(status_error(resp, &url));
+    }
+    read_json(resp, &url)
+}
+
+/// Cache the version from a successful `/info` response on the instance
+///
+/// Failing to persist it only costs a round trip later, so errors are logged, not returned.
+fn remember_version(inst: &crate::storage::HarmonyInstance, info: &Value) {
+    let Some(version) = reported_version(info) else {
+        return;
+    };
+    if inst.version.as_deref() == Some(version) {
+        return;
+    }
+    if let Err(e) = crate::storage::set_harmony_instance_version(&inst.id, version) {
+        warn!("could not cache version of '{}': {:#}", inst.label, e);
+    }
+}
+
+/// Shortest interval and per-check timeout used by `harmony:wait`
+const MIN_WAIT_STEP: Duration = Duration::from_millis(100);
+
+/// Poll `/info` until the instance answers successfully or `timeout` elapses
+pub fn wait(
+    id: Option<&str>,
+    label: Option<&str>,
+    timeout: Duration,
+    interval: Duration,
+) -> Result<()> {
+    let inst = connect(id, label)?;
+    let interval = interval.max(MIN_WAIT_STEP);
+    let start = std::time::Instant::now();
+
+    print!("⏳ Waiting for '{}' to become healthy", inst.label);
+    std::io::Write::flush(&mut std::io::stdout()).ok();
+    loop {
+        let remaining = timeout.saturating_sub(start.elapsed());
+        match probe(&inst, interval.min(remaining).max(MIN_WAIT_STEP)) {
+            Ok(()) => {
+                println!();
+                println!(
+                    "✓ Instance '{}' is healthy ({}s)",
+                    inst.label,
+                    start.elapsed().as_secs()
+                );
+                return Ok(());
+            }
+            Err(e) => debug!("health check failed: {:#}", e),
+        }
+
+        let remaining = timeout.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            println!();
+            return Err(anyhow!(
+                "instance did not become healthy within {}",
+                crate::cli::format_duration(timeout)
+            ));
+        }
+        print!(".");
+        std::io::Write::flush(&mut std::io::stdout()).ok();
+        thread::sleep(interval.min(remaining));
+    }
+}
+
+pub fn info(
+    id: Option<&str>,
+    label: Option<&str>,
+    raw_body: bool,
+    since_version: Option<&str>,
+    columns: &[String],
+    output: OutputFormat,
+) -> Result<()> {
+    let inst = connect(id, label)?;
+    let json = output == OutputFormat::Json && !raw_body;
+    json_errors(
+        json,
+        info_instance(&inst, raw_body, since_version, columns, json),
+    )
+}
+
+/// `harmony:info --all` / `--group`: query every registered instance, or a group's members
+///
+/// With `--output json` the responses are collected into one array (see [`batch::collect_json`]).
+pub fn info_all(
+    group: Option<&str>,
+    tag: Option<&str>,
+    fail_fast: bool,
+    raw_body: bool,
+    since_version: Option<&str>,
+    columns: &[String],
+    output: OutputFormat,
+) -> Result<()> {
+    let list = group::batch_targets(group, tag)?;
+    if output == OutputFormat::Json && !raw_body {
+        return batch::collect_json(&list, fail_fast, |inst| info_document(inst, since_version));
     }
+    batch::run_over_instances(&list, fail_fast, |inst| {
+        info_instance(inst, raw_body, since_version, columns, false)
+    })
+}
 
-    let json_value: Value = resp.json().context("parsing JSON response")?;
+/// With `json` (and without `raw_body`), the `/info` response is printed as JSON
+fn info_instance(
+    inst: &crate::storage::HarmonyInstance,
+    raw_body: bool,
+    since_version: Option<&str>,
+    columns: &[String],
+    json: bool,
+) -> Result<()> {
+    if raw_body {
+        if let Some(min) = since_version {
+            require_min_version(inst, min, "harmony:info --since-version")?;
+        }
+        let url = format!("{}/info", base_url(inst));
+        let client = http::blocking_client()?;
+        let resp = send(format!("GET {}", url), || {
+            with_instance_headers(client.get(&url), inst, ACCEPT_ANY)
+        })
+        .with_context(|| failed_request("GET", &url))?;
+        return print_raw_body(resp, &url);
+    }
 
+    let info = info_document(inst, since_version)?;
     if json {
-        println!("{}", serde_json::to_string_pretty(&json_value)?);
+        output::print_json(&info)?;
     } else {
-        // Render as table
-        if let Some(routes_array) = json_value.get("routes").and_then(|v| v.as_array()) {
-            render_array_of_objects(routes_array);
+        print_info(&mut io::stdout(), &info, columns)?;
+    }
+    Ok(())
+}
+
+/// The `/info` response shown by `harmony:info`, after the `--since-version` check
+fn info_document(
+    inst: &crate::storage::HarmonyInstance,
+    since_version: Option<&str>,
+) -> Result<Value> {
+    let checked = match since_version {
+        Some(min) => require_min_version(inst, min, "harmony:info --since-version")?,
+        None => None,
+    };
+    // The version check may already have fetched `/info`
+    if let Some(info) = checked {
+        return Ok(info);
+    }
+    let info = fetch_info(inst)?;
+    remember_version(inst, &info);
+    Ok(info)
+}
+
+/// Render an `/info` response: the scalar fields as the info view, then each
+/// nested array of objects (e.g. `routes`, `pipelines`) as its own table
+///
+/// `columns` applies to the top-level table only; nested tables keep all their columns.
+fn print_info(out: &mut dyn Write, json: &Value, columns: &[String]) -> io::Result<()> {
+    let Some(obj) = json.as_object() else {
+        return render_json_table(out, json, columns);
+    };
+    let (sections, fields): (Vec<_>, Vec<_>) = obj.iter().partition(|(_, v)| is_object_array(v));
+    let mut first = true;
+    if !fields.is_empty() || sections.is_empty() {
+        let fields: Map<String, Value> = fields
+            .into_iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        print_info_fields(out, &fields, columns)?;
+        first = false;
+    }
+    for (name, rows) in sections {
+        let rows = rows.as_array().map(Vec::as_slice).unwrap_or_default();
+        if !first {
+            writeln!(out)?;
+        }
+        first = false;
+        if output::markdown() {
+            writeln!(out, "### {}\n", name)?;
         } else {
-            render_json_table(&json_value);
+            writeln!(out, "{} ({}):", name, rows.len())?;
         }
+        render_array_of_objects(out, rows, &[])?;
     }
     Ok(())
 }
 
-pub fn reload(id: Option<&str>, label: Option<&str>) -> Result<()> {
-    let inst = resolve_instance(id, label)?;
-    let url = format!("http://{}:{}/api/reload", inst.ip, inst.port);
-    let client = Client::new();
-    let resp = client
-        .post(&url)
-        .send()
-        .with_context(|| format!("POST {}", url))?;
+/// Whether an `/info` field is shown as a table of its own rather than a row;
+/// empty arrays stay rows, so e.g. `tags: []` does not get a section
+fn is_object_array(v: &Value) -> bool {
+    v.as_array()
+        .is_some_and(|arr| !arr.is_empty() && arr.iter().all(Value::is_object))
+}
 
-    if !resp.status().is_success() {
-        return Err(anyhow!("{} {}", resp.status(), url));
+/// The scalar part of an `/info` response: the typed view, or the generic
+/// key/value table when `--columns` is given
+fn print_info_fields(
+    out: &mut dyn Write,
+    fields: &Map<String, Value>,
+    columns: &[String],
+) -> io::Result<()> {
+    if !columns.is_empty() {
+        return render_kv_table(out, fields, columns);
+    }
+    // Prefer the typed view; fields of an unexpected type fall back to the generic table
+    match InfoResponse::deserialize(Value::Object(fields.clone())) {
+        Ok(info) => print_kv_rows(out, &info_rows(&info)),
+        Err(e) => {
+            debug!("info response has an unexpected shape: {}", e);
+            render_kv_table(out, fields, &[])
+        }
+    }
+}
+
+/// Management endpoints checked by `harmony:info --probe-all-endpoints`, besides the data-plane root
+const SELFCHECK_ENDPOINTS: &[&str] = &["info", "routes", "pipelines"];
+
+/// Outcome of one request made by `harmony:info --probe-all-endpoints`
+#[derive(Debug, Serialize)]
+struct EndpointProbe {
+    /// Path of the request, e.g. `/admin/routes`
+    endpoint: String,
+    pass: bool,
+    /// HTTP status, if the instance answered at all
+    status: Option<u16>,
+    latency_ms: u64,
+    /// Why the endpoint failed; empty when it passed
+    detail: String,
+}
+
+/// `harmony:info --probe-all-endpoints`: check every endpoint of one instance
+pub fn selfcheck(id: Option<&str>, label: Option<&str>, output: OutputFormat) -> Result<()> {
+    let inst = connect(id, label)?;
+    selfcheck_instance(&inst, output == OutputFormat::Json)
+}
+
+/// `harmony:info --probe-all-endpoints --all` / `--group`
+///
+/// With `--output json` each instance's result is the `{"healthy", "endpoints"}`
+/// document of [`selfcheck`], collected into one array.
+pub fn selfcheck_all(
+    group: Option<&str>,
+    tag: Option<&str>,
+    fail_fast: bool,
+    output: OutputFormat,
+) -> Result<()> {
+    let list = group::batch_targets(group, tag)?;
+    if output == OutputFormat::Json {
+        return batch::collect_json(&list, fail_fast, |inst| {
+            let probes = probe_instance(inst)?;
+            selfcheck_verdict(&probes)?;
+            Ok(selfcheck_document(&probes))
+        });
+    }
+    batch::run_over_instances(&list, fail_fast, |inst| selfcheck_instance(inst, false))
+}
+
+/// Probe `/info`, `/routes`, `/pipelines` and the data-plane root, print the
+/// matrix and a verdict, and fail if any endpoint failed
+fn selfcheck_instance(inst: &crate::storage::HarmonyInstance, json: bool) -> Result<()> {
+    let probes = probe_instance(inst)?;
+    let failed = probes.iter().filter(|p| !p.pass).count();
+    if json {
+        output::print_json(&selfcheck_document(&probes))?;
+    } else {
+        print_endpoint_probes(&mut io::stdout(), &probes)?;
+        if failed == 0 {
+            println!("✓ Healthy: all {} endpoints passed", probes.len());
+        } else {
+            println!(
+                "✗ Unhealthy: {} of {} endpoints failed",
+                failed,
+                probes.len()
+            );
+        }
     }
+    selfcheck_verdict(&probes)
+}
+
+/// Probe every endpoint checked by `harmony:info --probe-all-endpoints`
+///
+/// Each endpoint is tried once, without retries, so the latencies are real.
+fn probe_instance(inst: &crate::storage::HarmonyInstance) -> Result<Vec<EndpointProbe>> {
+    let client = http::blocking_client()?;
+    let origin = origin(inst);
+    let mut probes: Vec<EndpointProbe> = SELFCHECK_ENDPOINTS
+        .iter()
+        .map(|name| {
+            let url = format!("{}/{}", base_url(inst), name);
+            probe_endpoint(&client, inst, &origin, &url, true)
+        })
+        .collect();
+    probes.push(probe_endpoint(
+        &client,
+        inst,
+        &origin,
+        &format!("{}/", origin),
+        false,
+    ));
+    Ok(probes)
+}
+
+/// The `--output json` document for one instance's probes
+fn selfcheck_document(probes: &[EndpointProbe]) -> Value {
+    serde_json::json!({
+        "healthy": probes.iter().all(|p| p.pass),
+        "endpoints": probes,
+    })
+}
 
-    let json: Value = resp.json().context("parsing JSON response")?;
-    println!("✓ Reload triggered successfully");
-    render_json_table(&json);
+/// Fail, naming the failed endpoints, unless every probe passed
+fn selfcheck_verdict(probes: &[EndpointProbe]) -> Result<()> {
+    let failed: Vec<&str> = probes
+        .iter()
+        .filter(|p| !p.pass)
+        .map(|p| p.endpoint.as_str())
+        .collect();
+    if !failed.is_empty() {
+        anyhow::bail!(
+            "{} of {} endpoints failed: {}",
+            failed.len(),
+            probes.len(),
+            failed.join(", ")
+        );
+    }
     Ok(())
 }
 
-pub fn update(id: Option<&str>, label: Option<&str>) -> Result<()> {
-    let inst = resolve_instance(id, label)?;
-    let url = format!(
-        "{}/update",
-        base_url(&inst)
+fn probe_endpoint(
+    client: &reqwest::blocking::Client,
+    inst: &crate::storage::HarmonyInstance,
+    origin: &str,
+    url: &str,
+    management: bool,
+) -> EndpointProbe {
+    let accept = if management { ACCEPT_JSON } else { ACCEPT_ANY };
+    let started = Instant::now();
+    let outcome = timings::time(format!("GET {}", url), || {
+        with_instance_headers(client.get(url), inst, accept).send()
+    })
+    .map_err(anyhow::Error::from)
+    .and_then(|resp| {
+        let status = resp.status();
+        Ok((status, read_body(resp, url)?))
+    });
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let (status, verdict) = match outcome {
+        Ok((status, body)) => (
+            Some(status.as_u16()),
+            endpoint_verdict(status, &body, management),
+        ),
+        Err(e) => (None, Err(format!("{:#}", e))),
+    };
+    EndpointProbe {
+        endpoint: url.strip_prefix(origin).unwrap_or(url).to_string(),
+        pass: verdict.is_ok(),
+        status,
+        latency_ms,
+        detail: verdict.err().unwrap_or_default(),
+    }
+}
+
+/// Whether a probed endpoint's answer counts as healthy, and if not, why
+///
+/// Management endpoints must answer 2xx with JSON. The data-plane root only has to
+/// answer without a server error: a 404 there just means no route is mounted at `/`.
+fn endpoint_verdict(
+    status: StatusCode,
+    body: &str,
+    management: bool,
+) -> std::result::Result<(), String> {
+    if (management && !status.is_success()) || status.is_server_error() {
+        return Err(format!("HTTP {}", status.as_u16()));
+    }
+    if management && let Err(e) = serde_json::from_str::<Value>(body) {
+        return Err(format!("invalid JSON: {}", e));
+    }
+    Ok(())
+}
+
+fn print_endpoint_probes(out: &mut dyn Write, probes: &[EndpointProbe]) -> io::Result<()> {
+    let rows: Vec<Value> = probes
+        .iter()
+        .map(|p| {
+            serde_json::json!({
+                "endpoint": p.endpoint,
+                "result": if p.pass { "pass" } else { "FAIL" },
+                "status": p.status,
+                "latency": format!("{}ms", p.latency_ms),
+                "detail": p.detail,
+            })
+        })
+        .collect();
+    let columns: Vec<String> = ["endpoint", "result", "status", "latency", "detail"]
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    render_array_of_objects(out, &rows, &columns)
+}
+
+/// Set by the Ctrl-C handler to end `harmony:info --watch` after the current poll
+static WATCH_STOPPED: AtomicBool = AtomicBool::new(false);
+
+/// `harmony:info --watch`: re-fetch `/info` every `interval` until Ctrl-C
+///
+/// The first response is shown in full. Later polls are shown in full too, or with
+/// `diff` only as the fields that changed since the previous poll. A failed poll is
+/// reported and the next one compares against the last good response, so an
+/// instance restarting mid-deploy does not end the watch.
+pub fn watch_info(
+    id: Option<&str>,
+    label: Option<&str>,
+    interval: Duration,
+    diff: bool,
+    columns: &[String],
+) -> Result<()> {
+    let inst = connect(id, label)?;
+    let interval = interval.max(MIN_WAIT_STEP);
+    if let Err(e) = ctrlc::set_handler(|| WATCH_STOPPED.store(true, Ordering::SeqCst)) {
+        debug!("Could not install Ctrl-C handler: {}", e);
+    }
+
+    let url = format!("{}/info", base_url(&inst));
+    let client = http::blocking_client()?;
+    let color = style::color_enabled();
+    let mut previous: Option<Value> = None;
+    println!(
+        "Watching '{}' every {} (Ctrl-C to stop)",
+        inst.label,
+        crate::cli::format_duration(interval)
     );
-    let client = Client::new();
-    let resp = client
-        .post(&url)
-        .send()
-        .with_context(|| format!("POST {}", url))?;
+    while !WATCH_STOPPED.load(Ordering::SeqCst) {
+        let fetched = timings::time(format!("GET {}", url), || {
+            with_instance_headers(client.get(&url), &inst, ACCEPT_JSON).send()
+        })
+        .with_context(|| failed_request("GET", &url))
+        .and_then(|resp| {
+            if resp.status().is_success() {
+                read_json(resp, &url)
+            } else {
+                Err(status_error(resp, &url))
+            }
+        });
+        match fetched {
+            Ok(json) => {
+                match &previous {
+                    Some(before) if diff => {
+                        for change in info_changes(before, &json) {
+                            println!("{}", style::paint(&change, style::Color::Yellow, color));
+                        }
+                    }
+                    Some(_) => {
+                        println!();
+                        print_info(&mut io::stdout(), &json, columns)?;
+                    }
+                    None => print_info(&mut io::stdout(), &json, columns)?,
+                }
+                previous = Some(json);
+            }
+            Err(e) => println!(
+                "{}",
+                style::paint(&format!("⚠️  {:#}", e), style::Color::Red, color)
+            ),
+        }
+        sleep_unless_stopped(interval);
+    }
+    println!("Stopped watching '{}'.", inst.label);
+    Ok(())
+}
 
-    let status = resp.status();
-    let json: Value = resp.json().context("parsing JSON response")?;
+/// `field: old -> new` lines for the top-level fields that differ between two `/info` responses
+fn info_changes(old: &Value, new: &Value) -> Vec<String> {
+    if old == new {
+        return Vec::new();
+    }
+    diff::changed_fields(old, new)
+}
 
-    if status.is_success() {
-        let config_size = json.get("config_size")
-            .and_then(|v| v.as_u64())
-            .unwrap_or(0);
-        println!("✓ Configuration uploaded successfully ({} bytes)", config_size);
-        Ok(())
-    } else {
-        let message = json.get("message")
-            .and_then(|v| v.as_str())
-            .unwrap_or("Unknown error");
-        Err(anyhow!("Failed to update configuration: {}", message))
+/// Sleep for `duration`, waking early once the watch has been stopped
+fn sleep_unless_stopped(duration: Duration) {
+    let deadline = std::time::Instant::now() + duration;
+    while !WATCH_STOPPED.load(Ordering::SeqCst) {
+        let now = std::time::Instant::now();
+        if now >= deadline {
+            return;
+        }
+        thread::sleep((deadline - now).min(MIN_WAIT_STEP));
+    }
+}
+
+/// The documented shape of `GET /{prefix}/info`; unknown fields are kept in `extra`
+#[derive(Debug, Deserialize)]
+struct InfoResponse {
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default)]
+    version: Option<String>,
+    /// Seconds since start (shown humanized); other shapes are shown as-is
+    #[serde(default)]
+    uptime: Option<Value>,
+    #[serde(default)]
+    routes_count: Option<u64>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+/// Rows for the info view: known fields in a fixed order, then extras sorted by key
+fn info_rows(info: &InfoResponse) -> Vec<(String, String)> {
+    let mut rows = Vec::new();
+    if let Some(status) = &info.status {
+        rows.push(("status".to_string(), status.clone()));
+    }
+    if let Some(version) = &info.version {
+        rows.push(("version".to_string(), version.clone()));
+    }
+    if let Some(uptime) = &info.uptime {
+        let shown = uptime
+            .as_f64()
+            .filter(|secs| *secs >= 0.0)
+            .map(|secs| format_uptime(secs as u64))
+            .unwrap_or_else(|| stringify_value(uptime));
+        rows.push(("uptime".to_string(), shown));
+    }
+    if let Some(count) = info.routes_count {
+        rows.push(("routes_count".to_string(), count.to_string()));
+    }
+    let mut extra: Vec<(String, String)> = info
+        .extra
+        .iter()
+        .map(|(k, v)| (k.clone(), stringify_value(v)))
+        .collect();
+    extra.sort_by(|a, b| a.0.cmp(&b.0));
+    rows.extend(extra);
+    rows
+}
+
+/// Humanize a duration in seconds, e.g. `1d 2h 3m 4s` (zero components are omitted)
+fn format_uptime(secs: u64) -> String {
+    let parts = [
+        (secs / 86_400, "d"),
+        (secs % 86_400 / 3_600, "h"),
+        (secs % 3_600 / 60, "m"),
+        (secs % 60, "s"),
+    ];
+    let shown: Vec<String> = parts
+        .iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, unit)| format!("{}{}", n, unit))
+        .collect();
+    if shown.is_empty() {
+        "0s".to_string()
+    } else {
+        shown.join(" ")
+    }
+}
+
+/// With `--output json` (and without `raw_body`), the response is printed as JSON
+pub fn pipelines(
+    id: Option<&str>,
+    label: Option<&str>,
+    raw_body: bool,
+    columns: &[String],
+    pipeline: Option<&str>,
+    output: OutputFormat,
+) -> Result<()> {
+    let inst = connect(id, label)?;
+    let json = output == OutputFormat::Json && !raw_body;
+    json_errors(
+        json,
+        pipelines_instance(&inst, raw_body, columns, pipeline, json),
+    )
+}
+
+/// With `json`, the response is printed as-is, or with `pipeline` as
+/// `{"pipelines": [...]}` holding just the pipelines of that name.
+fn pipelines_instance(
+    inst: &crate::storage::HarmonyInstance,
+    raw_body: bool,
+    columns: &[String],
+    pipeline: Option<&str>,
+    json: bool,
+) -> Result<()> {
+    let url = format!("{}/pipelines", base_url(inst));
+    let client = http::blocking_client()?;
+    let accept = if raw_body { ACCEPT_ANY } else { ACCEPT_JSON };
+    let resp = send(format!("GET {}", url), || {
+        with_instance_headers(client.get(&url), inst, accept)
+    })
+    .with_context(|| failed_request("GET", &url))?;
+
+    if raw_body {
+        return print_raw_body(resp, &url);
+    }
+
+    if !resp.status().is_success() {
+        return Err(status_error(resp, &url));
+    }
+
+    let body = read_json(resp, &url)?;
+    if let Some(name) = pipeline {
+        let arr = body
+            .get("pipelines")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| anyhow!("{} did not return a pipelines array", url))?;
+        let pipelines = Vec::<Pipeline>::deserialize(Value::Array(arr.clone()))
+            .with_context(|| format!("parsing pipelines from {}", url))?;
+        let named = pipelines_named(pipelines, name)?;
+        if json {
+            let matches: Vec<&Value> = arr
+                .iter()
+                .filter(|p| p.get("name").and_then(Value::as_str) == Some(name))
+                .collect();
+            output::print_json(&serde_json::json!({ "pipelines": matches }))?;
+        } else {
+            print!("{}", format_pipelines(&named));
+        }
+        return Ok(());
+    }
+    if json {
+        output::print_json(&body)?;
+        return Ok(());
+    }
+    if let Some(arr) = body.get("pipelines").and_then(|v| v.as_array()) {
+        // Prefer the dedicated view unless specific columns were requested;
+        // unknown shapes fall back to the generic table
+        if !columns.is_empty() {
+            render_array_of_objects(&mut io::stdout(), arr, columns)?;
+            return Ok(());
+        }
+        match Vec::<Pipeline>::deserialize(Value::Array(arr.clone())) {
+            Ok(pipelines) => print!("{}", format_pipelines(&pipelines)),
+            Err(e) => {
+                debug!("pipelines response has an unexpected shape: {}", e);
+                render_array_of_objects(&mut io::stdout(), arr, columns)?;
+            }
+        }
+    } else {
+        render_json_table(&mut io::stdout(), &body, columns)?;
+    }
+    Ok(())
+}
+
+/// Stable sort of routes by `field`, comparing strings case-insensitively
+///
+/// Routes without the field sort last in either direction.
+fn sort_routes(routes: &mut [Value], field: &str, reverse: bool) {
+    let key = |route: &Value| {
+        route.get(field).filter(|v| !v.is_null()).map(|v| match v {
+            Value::String(s) => s.to_lowercase(),
+            other => other.to_string(),
+        })
+    };
+    routes.sort_by(|a, b| match (key(a), key(b)) {
+        (Some(a), Some(b)) if reverse => b.cmp(&a),
+        (Some(a), Some(b)) => a.cmp(&b),
+        (a, b) => a.is_none().cmp(&b.is_none()),
+    });
+}
+
+/// A pipeline as returned by `GET /{prefix}/pipelines`
+#[derive(Debug, Deserialize, PartialEq)]
+struct Pipeline {
+    name: String,
+    #[serde(default)]
+    stages: Vec<String>,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+/// Pipelines whose name is exactly `name`; errors with the available names if none match
+fn pipelines_named(pipelines: Vec<Pipeline>, name: &str) -> Result<Vec<Pipeline>> {
+    let mut names: Vec<String> = pipelines.iter().map(|p| p.name.clone()).collect();
+    let matches: Vec<Pipeline> = pipelines.into_iter().filter(|p| p.name == name).collect();
+    if matches.is_empty() {
+        if names.is_empty() {
+            return Err(anyhow!(
+                "no pipeline named '{}' (no pipelines configured)",
+                name
+            ));
+        }
+        names.sort_unstable();
+        names.dedup();
+        return Err(anyhow!(
+            "no pipeline named '{}'; available pipelines: {}",
+            name,
+            names.join(", ")
+        ));
+    }
+    Ok(matches)
+}
+
+/// Render pipelines with the name as a header and stages as an ordered list
+fn format_pipelines(pipelines: &[Pipeline]) -> String {
+    if pipelines.is_empty() {
+        return "No pipelines configured.\n".to_string();
+    }
+    let mut out = String::new();
+    for (i, pipeline) in pipelines.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        out.push_str(&format!("{}\n", pipeline.name));
+        if let Some(description) = pipeline.description.as_deref().filter(|d| !d.is_empty()) {
+            out.push_str(&format!("  {}\n", description));
+        }
+        if pipeline.stages.is_empty() {
+            out.push_str("  (no stages)\n");
+        }
+        for (n, stage) in pipeline.stages.iter().enumerate() {
+            out.push_str(&format!("  {}. {}\n", n + 1, stage));
+        }
+    }
+    out
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn routes(
+    id: Option<&str>,
+    label: Option<&str>,
+    json: bool,
+    raw_body: bool,
+    columns: &[String],
+    stream: bool,
+    diff_against: Option<&str>,
+    endpoint: Option<&str>,
+    sort: Option<(RouteSort, bool)>,
+    count_only: bool,
+) -> Result<()> {
+    if stream && !json {
+        return Err(anyhow!("--stream requires --json or --output json"));
+    }
+    let snapshot = diff_against.map(load_routes_snapshot).transpose()?;
+    let inst = connect(id, label)?;
+    let url = format!("{}/routes", base_url(&inst));
+    let client = http::blocking_client()?;
+    let accept = if raw_body { ACCEPT_ANY } else { ACCEPT_JSON };
+    let resp = send(format!("GET {}", url), || {
+        with_instance_headers(client.get(&url), &inst, accept)
+    })
+    .with_context(|| failed_request("GET", &url))?;
+
+    if raw_body {
+        return print_raw_body(resp, &url);
+    }
+
+    if !resp.status().is_success() {
+        return json_errors(json, Err(status_error(resp, &url)));
+    }
+
+    let mut json_value = read_json(resp, &url)?;
+    if count_only {
+        let all = diff::routes_of(&json_value)
+            .ok_or_else(|| anyhow!("{} did not return a routes array", url))?;
+        let count = count_routes(all, endpoint);
+        if json {
+            output::print_json(&serde_json::json!({ "count": count }))?;
+        } else {
+            println!("{}", count);
+        }
+        return Ok(());
+    }
+    if let Some((key, reverse)) = sort {
+        let routes = match &mut json_value {
+            Value::Object(obj) => obj.get_mut("routes").and_then(|r| r.as_array_mut()),
+            Value::Array(arr) => Some(arr),
+            _ => None,
+        };
+        match routes {
+            Some(routes) => sort_routes(routes, key.field(), reverse),
+            None => warn!("{} did not return a routes array; --sort ignored", url),
+        }
+    }
+
+    if let Some(snapshot) = snapshot {
+        let live = diff::routes_of(&json_value)
+            .ok_or_else(|| anyhow!("{} did not return a routes array", url))?;
+        let snapshot_routes = diff::routes_of(&snapshot).unwrap_or_default();
+        let route_diff = diff::diff_routes(snapshot_routes, live);
+        print!("{}", route_diff.render(style::color_enabled()));
+        if !route_diff.is_empty() {
+            return Err(anyhow!("routes differ from snapshot"));
+        }
+        return Ok(());
+    }
+
+    if let Some(name) = endpoint {
+        let all = diff::routes_of(&json_value)
+            .ok_or_else(|| anyhow!("{} did not return a routes array", url))?;
+        let matches = routes_for_endpoint(all, name)?;
+        if json {
+            output::print_json(&matches)?;
+            return Ok(());
+        }
+        let mut stdout = io::stdout();
+        for (i, route) in matches.iter().enumerate() {
+            if i > 0 {
+                writeln!(stdout)?;
+            }
+            match route.as_object() {
+                Some(obj) => render_kv_table(&mut stdout, obj, &[])?,
+                None => writeln!(stdout, "{}", route)?,
+            }
+        }
+        return Ok(());
+    }
+
+    if stream {
+        let routes = json_value.get("routes").unwrap_or(&json_value);
+        let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+        write_ndjson(&mut out, routes).context("writing routes")?;
+    } else if json {
+        output::print_json(&json_value)?;
+    } else {
+        // Render as table
+        if let Some(routes_array) = json_value.get("routes").and_then(|v| v.as_array()) {
+            render_array_of_objects(&mut io::stdout(), routes_array, columns)?;
+        } else {
+            render_json_table(&mut io::stdout(), &json_value, columns)?;
+        }
+    }
+    Ok(())
+}
+
+/// Number of routes, or of those whose `endpoint_name` is exactly `endpoint`
+///
+/// Unlike `--endpoint` on its own, no match is a count of 0 rather than an error,
+/// so monitoring scripts can compare the number.
+fn count_routes(routes: &[Value], endpoint: Option<&str>) -> usize {
+    match endpoint {
+        Some(name) => routes
+            .iter()
+            .filter(|r| r.get("endpoint_name").and_then(|v| v.as_str()) == Some(name))
+            .count(),
+        None => routes.len(),
+    }
+}
+
+/// Routes whose `endpoint_name` is exactly `name`; errors with the available names if none match
+fn routes_for_endpoint<'a>(routes: &'a [Value], name: &str) -> Result<Vec<&'a Value>> {
+    let endpoint_of = |r: &'a Value| r.get("endpoint_name").and_then(|v| v.as_str());
+    let matches: Vec<&Value> = routes
+        .iter()
+        .filter(|r| endpoint_of(r) == Some(name))
+        .collect();
+    if matches.is_empty() {
+        let mut names: Vec<&str> = routes.iter().filter_map(endpoint_of).collect();
+        names.sort_unstable();
+        names.dedup();
+        if names.is_empty() {
+            return Err(anyhow!(
+                "no route has endpoint '{}' (no routes have an endpoint_name)",
+                name
+            ));
+        }
+        return Err(anyhow!(
+            "no route has endpoint '{}'; available endpoints: {}",
+            name,
+            names.join(", ")
+        ));
+    }
+    Ok(matches)
+}
+
+/// Load a routes snapshot saved with `harmony:routes --json`
+fn load_routes_snapshot(path: &str) -> Result<Value> {
+    let path = crate::storage::expand_path(path);
+    let data = std::fs::read_to_string(&path)
+        .with_context(|| format!("reading snapshot {}", path.display()))?;
+    let snapshot: Value = serde_json::from_str(&data)
+        .with_context(|| format!("parsing snapshot {}", path.display()))?;
+    if diff::routes_of(&snapshot).is_none() {
+        return Err(anyhow!(
+            "snapshot {} does not contain a routes array",
+            path.display()
+        ));
+    }
+    Ok(snapshot)
+}
+
+/// Write an array as NDJSON (one compact element per line), or any other value as a single line
+///
+/// Elements are serialized one at a time, so no string holding the whole document is built.
+fn write_ndjson(out: &mut impl std::io::Write, value: &Value) -> std::io::Result<()> {
+    let items = match value {
+        Value::Array(arr) => arr.as_slice(),
+        other => std::slice::from_ref(other),
+    };
+    for item in items {
+        serde_json::to_writer(&mut *out, item)?;
+        out.write_all(b"\n")?;
+    }
+    out.flush()
+}
+
+/// `harmony:snapshot`: capture `/info`, `/routes` and `/pipelines` in one document
+///
+/// A section that cannot be fetched is recorded as `{"error": "..."}` so the
+/// rest of the snapshot is still produced.
+pub fn snapshot(id: Option<&str>, label: Option<&str>, out: Option<&str>) -> Result<()> {
+    let inst = connect(id, label)?;
+    let client = http::blocking_client()?;
+
+    let mut doc = Map::new();
+    doc.insert(
+        "instance".to_string(),
+        serde_json::json!({
+            "id": inst.id,
+            "label": inst.label,
+            "base_url": base_url(&inst),
+        }),
+    );
+    for section in ["info", "routes", "pipelines"] {
+        let url = format!("{}/{}", base_url(&inst), section);
+        let value = fetch_json(&client, &inst, &url).unwrap_or_else(|e| {
+            warn!("snapshot section '{}' failed: {:#}", section, e);
+            serde_json::json!({ "error": format!("{:#}", e) })
+        });
+        doc.insert(section.to_string(), value);
+    }
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    doc.insert("captured_at".to_string(), Value::String(format_utc(now)));
+
+    let json = output::to_json(&doc)?;
+    match out {
+        Some(path) => {
+            let path = crate::storage::expand_path(path);
+            crate::storage::write_atomic(&path, format!("{}\n", json).as_bytes())?;
+            println!(
+                "✓ Snapshot of '{}' written to {}",
+                inst.label,
+                path.display()
+            );
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+/// `GET` a management endpoint and parse its JSON body
+fn fetch_json(
+    client: &reqwest::blocking::Client,
+    inst: &crate::storage::HarmonyInstance,
+    url: &str,
+) -> Result<Value> {
+    let resp = send(format!("GET {}", url), || {
+        with_instance_headers(client.get(url), inst, ACCEPT_JSON)
+    })
+    .with_context(|| failed_request("GET", url))?;
+    if !resp.status().is_success() {
+        return Err(status_error(resp, url));
+    }
+    read_json(resp, url)
+}
+
+/// Format Unix seconds as an RFC 3339 UTC timestamp, e.g. `2024-05-01T12:00:00Z`
+fn format_utc(secs: u64) -> String {
+    i64::try_from(secs)
+        .ok()
+        .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+        .unwrap_or_default()
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+/// Trigger a reload; with `wait`, block until the new configuration is live or `wait` elapses
+///
+/// With `--output json` the outcome is printed as `{"reloaded": true, "response": ...}`
+/// or `{"reloaded": false, "error": ...}`, and a failure still exits non-zero.
+pub fn reload(
+    id: Option<&str>,
+    label: Option<&str>,
+    wait: Option<Duration>,
+    output: OutputFormat,
+) -> Result<()> {
+    let inst = connect(id, label)?;
+    if output != OutputFormat::Json {
+        return reload_instance(&inst, wait, true).map(|_| ());
+    }
+    match reload_instance(&inst, wait, false) {
+        Ok(response) => Ok(output::print_json(&reload_result(Ok(&response)))?),
+        Err(e) => {
+            output::print_json(&reload_result(Err(&e)))?;
+            Err(e)
+        }
+    }
+}
+
+/// Normalized `harmony:reload --output json` document
+fn reload_result(result: std::result::Result<&Value, &anyhow::Error>) -> Value {
+    match result {
+        Ok(response) => serde_json::json!({ "reloaded": true, "response": response }),
+        Err(e) => {
+            let error = match e.downcast_ref::<StatusError>() {
+                Some(status) => status.envelope()["error"].clone(),
+                None => Value::String(format!("{:#}", e)),
+            };
+            serde_json::json!({ "reloaded": false, "error": error })
+        }
+    }
+}
+
+/// `harmony:reload --all` / `--group`: reload every registered instance, or a group's members
+///
+/// With `--output json` each instance's result is its reload response, collected into one array.
+pub fn reload_all(
+    group: Option<&str>,
+    tag: Option<&str>,
+    fail_fast: bool,
+    wait: Option<Duration>,
+    output: OutputFormat,
+) -> Result<()> {
+    let list = group::batch_targets(group, tag)?;
+    if output == OutputFormat::Json {
+        return batch::collect_json(&list, fail_fast, |inst| reload_instance(inst, wait, false));
+    }
+    batch::run_over_instances(&list, fail_fast, |inst| {
+        reload_instance(inst, wait, true).map(|_| ())
+    })
+}
+
+/// POST `/api/reload` and return the response; `progress` prints it and the `--wait` status
+fn reload_instance(
+    inst: &crate::storage::HarmonyInstance,
+    wait: Option<Duration>,
+    progress: bool,
+) -> Result<Value> {
+    let url = format!("{}/api/reload", origin(inst));
+    let client = http::blocking_client()?;
+    let info_url = format!("{}/info", base_url(inst));
+
+    // Remember the loaded configuration so --wait can tell when it changes
+    let before = match wait {
+        Some(_) => fetch_json(&client, inst, &info_url)
+            .inspect_err(|e| debug!("could not read /info before reload: {:#}", e))
+            .ok(),
+        None => None,
+    };
+
+    let sent = Instant::now();
+    let resp = send(format!("POST {}", url), || {
+        with_instance_headers(client.post(&url), inst, ACCEPT_JSON)
+    })
+    .with_context(|| failed_request("POST", &url))?;
+
+    if !resp.status().is_success() {
+        return Err(status_error(resp, &url));
+    }
+
+    let json = read_json(resp, &url)?;
+    if progress {
+        println!("✓ Reload triggered successfully");
+        render_json_table(&mut io::stdout(), &json, &[])?;
+    }
+
+    if let Some(timeout) = wait {
+        wait_for_reload(&client, inst, &info_url, before, sent, timeout, progress)?;
+    }
+    Ok(json)
+}
+
+/// `/info` fields that change whenever Harmony loads a configuration, in order of preference
+const CONFIG_MARKER_FIELDS: &[&str] = &[
+    "config_version",
+    "config_hash",
+    "config_loaded_at",
+    "last_reload",
+];
+
+/// `/info` fields holding when the Harmony process started
+const START_TIME_FIELDS: &[&str] = &["started_at", "start_time"];
+
+/// Pause between `/info` polls for `harmony:reload --wait`
+const RELOAD_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The first configuration marker reported in an `/info` response
+fn config_marker(info: &Value) -> Option<(&'static str, Value)> {
+    CONFIG_MARKER_FIELDS.iter().find_map(|field| {
+        info.get(*field)
+            .filter(|v| !v.is_null())
+            .map(|v| (*field, v.clone()))
+    })
+}
+
+/// Whether an `/info` response shows that Harmony restarted since a reload
+#[derive(Debug, PartialEq)]
+enum Restart {
+    /// It did; the evidence, e.g. `uptime 2s`
+    Seen(String),
+    NotYet,
+    /// The response has no uptime, version or start time to tell
+    Unknown,
+}
+
+/// Look for a restart in `info`, the answer to `/info` `since` the reload was sent:
+/// an uptime shorter than that, or a version or start time different from `before`
+fn check_restart(before: Option<&Value>, info: &Value, since: Duration) -> Restart {
+    let mut comparable = false;
+    if let Some(uptime) = info.get("uptime").and_then(Value::as_f64) {
+        comparable = true;
+        // A second of slack for instances that round their uptime up
+        if uptime >= 0.0 && uptime <= since.as_secs_f64() + 1.0 {
+            return Restart::Seen(format!("uptime {}", format_uptime(uptime as u64)));
+        }
+    }
+    for field in std::iter::once(&"version").chain(START_TIME_FIELDS) {
+        let Some(new) = info.get(*field).filter(|v| !v.is_null()) else {
+            continue;
+        };
+        comparable = true;
+        if let Some(old) = before.and_then(|b| b.get(*field)).filter(|v| !v.is_null())
+            && old != new
+        {
+            return Restart::Seen(format!(
+                "{}: {} -> {}",
+                field,
+                stringify_value(old),
+                stringify_value(new)
+            ));
+        }
+    }
+    if comparable {
+        Restart::NotYet
+    } else {
+        Restart::Unknown
+    }
+}
+
+/// Poll `/info` until the configuration marker differs from the one in `before`
+///
+/// Without a marker to compare, the reload counts as complete once Harmony has
+/// restarted (see [`check_restart`]); an instance that reports nothing to tell
+/// that by fails straight away rather than at the timeout.
+fn wait_for_reload(
+    client: &reqwest::blocking::Client,
+    inst: &crate::storage::HarmonyInstance,
+    info_url: &str,
+    before: Option<Value>,
+    sent: Instant,
+    timeout: Duration,
+    progress: bool,
+) -> Result<()> {
+    let start = std::time::Instant::now();
+    let marker = before.as_ref().and_then(config_marker);
+    if progress {
+        print!("⏳ Waiting for the reload to complete");
+        std::io::Write::flush(&mut std::io::stdout()).ok();
+    }
+    let done = |how: String| {
+        if progress {
+            println!();
+            println!("✓ Reload completed ({})", how);
+        }
+    };
+    loop {
+        let remaining = timeout.saturating_sub(start.elapsed());
+        thread::sleep(RELOAD_POLL_INTERVAL.min(remaining));
+        let last_problem = match fetch_json(client, inst, info_url) {
+            Ok(info) => match &marker {
+                Some((field, old)) => match config_marker(&info) {
+                    Some((_, new)) if new != *old => {
+                        done(format!(
+                            "{}: {} -> {}",
+                            field,
+                            stringify_value(old),
+                            stringify_value(&new)
+                        ));
+                        return Ok(());
+                    }
+                    _ => format!("{} is still {}", field, stringify_value(old)),
+                },
+                None => match check_restart(before.as_ref(), &info, sent.elapsed()) {
+                    Restart::Seen(how) => {
+                        done(format!("restarted: {}", how));
+                        return Ok(());
+                    }
+                    Restart::NotYet => {
+                        "no configuration version to compare, and Harmony has not restarted"
+                            .to_string()
+                    }
+                    Restart::Unknown => {
+                        if progress {
+                            println!();
+                        }
+                        return Err(anyhow!(
+                            "cannot tell when the reload completes: /info reports no configuration version ({}), uptime, version or start time",
+                            CONFIG_MARKER_FIELDS.join(", ")
+                        ));
+                    }
+                },
+            },
+            Err(e) => format!("{:#}", e),
+        };
+
+        if start.elapsed() >= timeout {
+            if progress {
+                println!();
+            }
+            return Err(anyhow!(
+                "reload did not complete within {} ({})",
+                crate::cli::format_duration(timeout),
+                last_problem
+            ));
+        }
+        if progress {
+            print!(".");
+            std::io::Write::flush(&mut std::io::stdout()).ok();
+        }
+    }
+}
+
+pub fn update(id: Option<&str>, label: Option<&str>) -> Result<()> {
+    let inst = connect(id, label)?;
+    update_instance(&inst).map(|_| ())
+}
+
+/// `harmony:update --all` / `--group`: upload the configuration of every
+/// authorized target, skipping instances that have no gateway yet
+pub fn update_all(group: Option<&str>, tag: Option<&str>, fail_fast: bool) -> Result<()> {
+    let (authorized, unauthorized): (Vec<_>, Vec<_>) = group::batch_targets(group, tag)?
+        .into_iter()
+        .partition(|inst| inst.gateway_id.is_some());
+
+    for inst in &unauthorized {
+        println!(
+            "Skipping {} ({}): not authorized; run `runbeam harmony:authorize --id {}` first",
+            inst.label, inst.id, inst.id
+        );
+    }
+    if authorized.is_empty() && !unauthorized.is_empty() {
+        println!("No authorized Harmony instances to update.");
+        return Ok(());
+    }
+    if !unauthorized.is_empty() {
+        println!();
+    }
+
+    let mut uploaded = 0u64;
+    let result = batch::run_over_instances(&authorized, fail_fast, |inst| {
+        uploaded += update_instance(inst)?;
+        Ok(())
+    });
+    if !authorized.is_empty() {
+        println!(
+            "Uploaded {} bytes in total; {} unauthorized instance(s) skipped",
+            uploaded,
+            unauthorized.len()
+        );
+    }
+    result
+}
+
+/// Ask one instance to upload its configuration; returns the reported size in bytes
+fn update_instance(inst: &crate::storage::HarmonyInstance) -> Result<u64> {
+    let url = format!("{}/update", base_url(inst));
+    let client = http::blocking_client()?;
+
+    // Transient failures (connection errors, timeouts, 5xx) are retried although this
+    // is a POST: Harmony only re-uploads its current configuration, so a repeat is harmless
+    let policy = retry::policy();
+    let attempts = policy.max_retries + 1;
+    let mut attempt = 0;
+    let resp = send_with(format!("POST {}", url), &policy, || {
+        attempt += 1;
+        if !output::quiet() {
+            eprintln!(
+                "⏳ Uploading configuration (attempt {}/{})...",
+                attempt, attempts
+            );
+        }
+        with_instance_headers(client.post(&url), inst, ACCEPT_JSON)
+    })
+    .with_context(|| failed_request("POST", &url))?;
+
+    let status = resp.status();
+    let body = read_body_with_progress(resp, &url)?;
+    debug!("POST {} -> {} ({} bytes received)", url, status, body.len());
+
+    if status.is_success() {
+        let json: Value = serde_json::from_str(&body).context("parsing JSON response")?;
+        let config_size = json
+            .get("config_size")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0);
+        println!(
+            "✓ Configuration uploaded successfully ({} bytes)",
+            config_size
+        );
+        return Ok(config_size);
+    }
+
+    if status == StatusCode::FORBIDDEN && auth::is_runbeam_disabled(&body) {
+        println!("⚠️  Harmony proxy rejected the update (HTTP 403):");
+        auth::print_runbeam_disabled_help(&format!("runbeam harmony:update --id {}", inst.id));
+        return Err(anyhow!(
+            "Failed to update configuration: Runbeam Cloud integration is disabled"
+        ));
+    }
+
+    let message = serde_json::from_str::<Value>(&body)
+        .ok()
+        .and_then(|json| {
+            json.get("message")
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| "Unknown error".to_string());
+    Err(anyhow!("Failed to update configuration: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::blocking::Client;
+
+    fn instance_with_prefix(prefix: &str) -> crate::storage::HarmonyInstance {
+        crate::storage::HarmonyInstance {
+            id: "abcd1234".to_string(),
+            ip: "127.0.0.1".to_string(),
+            port: 8081,
+            label: "test".to_string(),
+            path_prefix: prefix.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sort_routes_stable_case_insensitive() {
+        let mut routes = vec![
+            serde_json::json!({"path": "/b", "pipeline": "Zeta"}),
+            serde_json::json!({"path": "/a", "pipeline": "alpha"}),
+            serde_json::json!({"path": "/c"}),
+            serde_json::json!({"path": "/d", "pipeline": "ALPHA"}),
+        ];
+        let paths = |routes: &[Value]| -> Vec<String> {
+            routes
+                .iter()
+                .map(|r| r["path"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        sort_routes(&mut routes, "pipeline", false);
+        assert_eq!(paths(&routes), ["/a", "/d", "/b", "/c"]);
+        // Equal keys keep their order, and missing fields stay last
+        sort_routes(&mut routes, "pipeline", true);
+        assert_eq!(paths(&routes), ["/b", "/a", "/d", "/c"]);
+    }
+
+    #[test]
+    fn test_status_error_envelope_truncates_body() {
+        // Two-byte characters after one ASCII byte: the limit falls mid-character
+        let body = format!("x{}", "é".repeat(ERROR_BODY_LIMIT));
+        let err = StatusError::new(StatusCode::BAD_GATEWAY, "http://h/admin/info", &body);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "502 Bad Gateway http://h/admin/info (trace id {})",
+                http::trace_id()
+            )
+        );
+
+        let envelope = err.envelope();
+        assert_eq!(envelope["error"]["status"], 502);
+        assert_eq!(envelope["error"]["trace_id"], http::trace_id());
+        assert_eq!(envelope["error"]["url"], "http://h/admin/info");
+        let kept = envelope["error"]["body"].as_str().unwrap();
+        assert!(kept.ends_with("..."));
+        assert_eq!(kept.len(), ERROR_BODY_LIMIT - 1 + "...".len());
+
+        assert_eq!(truncate_body("short", 10), "short");
+        assert!(envelope["error"].get("hint").is_none());
+    }
+
+    #[test]
+    fn test_status_error_hints_at_auth_for_401_and_403() {
+        for (status, code) in [
+            (StatusCode::UNAUTHORIZED, "401"),
+            (StatusCode::FORBIDDEN, "403"),
+        ] {
+            let err = StatusError::new(status, "http://h/admin/info", "");
+            let hint = format!(
+                "Harmony returned {} — this instance may require an auth header",
+                code
+            );
+            assert!(err.to_string().contains(&hint), "{}", err);
+            assert!(
+                err.envelope()["error"]["hint"]
+                    .as_str()
+                    .unwrap()
+                    .starts_with(&hint)
+            );
+        }
+        let err = StatusError::new(StatusCode::NOT_FOUND, "http://h/admin/info", "");
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "404 Not Found http://h/admin/info (trace id {})",
+                http::trace_id()
+            )
+        );
+    }
+
+    #[test]
+    fn test_base_url_with_prefix() {
+        assert_eq!(
+            base_url(&instance_with_prefix("admin")),
+            "http://127.0.0.1:8081/admin"
+        );
+        assert_eq!(
+            base_url(&instance_with_prefix("/admin/")),
+            "http://127.0.0.1:8081/admin"
+        );
+    }
+
+    #[test]
+    fn test_base_url_with_empty_prefix() {
+        assert_eq!(base_url(&instance_with_prefix("")), "http://127.0.0.1:8081");
+        assert_eq!(
+            format!("{}/info", base_url(&instance_with_prefix(""))),
+            "http://127.0.0.1:8081/info"
+        );
+    }
+
+    #[test]
+    fn test_base_url_uses_instance_scheme() {
+        let inst = crate::storage::HarmonyInstance {
+            scheme: "https".to_string(),
+            ..instance_with_prefix("admin")
+        };
+        assert_eq!(base_url(&inst), "https://127.0.0.1:8081/admin");
+    }
+
+    #[test]
+    fn test_base_url_brackets_ipv6() {
+        let inst = crate::storage::HarmonyInstance {
+            ip: "fd00::5".to_string(),
+            ..instance_with_prefix("admin")
+        };
+        assert_eq!(base_url(&inst), "http://[fd00::5]:8081/admin");
+        let inst = crate::storage::HarmonyInstance {
+            ip: "[::1]".to_string(),
+            ..instance_with_prefix("")
+        };
+        assert_eq!(base_url(&inst), "http://[::1]:8081");
+    }
+
+    #[test]
+    fn test_pipeline_deserialization() {
+        let json = serde_json::json!([
+            {"name": "ingest", "stages": ["parse", "validate"], "description": "Inbound"},
+            {"name": "empty"}
+        ]);
+        let pipelines = Vec::<Pipeline>::deserialize(json).unwrap();
+        assert_eq!(pipelines.len(), 2);
+        assert_eq!(pipelines[0].stages, vec!["parse", "validate"]);
+        assert_eq!(pipelines[1].description, None);
+
+        // Unknown shapes are rejected so the caller can fall back
+        let odd = serde_json::json!([{"id": 1, "stages": [{"kind": "x"}]}]);
+        assert!(Vec::<Pipeline>::deserialize(odd).is_err());
+    }
+
+    #[test]
+    fn test_format_pipelines() {
+        let pipelines = vec![
+            Pipeline {
+                name: "ingest".to_string(),
+                stages: vec!["parse".to_string(), "validate".to_string()],
+                description: Some("Inbound".to_string()),
+            },
+            Pipeline {
+                name: "empty".to_string(),
+                stages: Vec::new(),
+                description: None,
+            },
+        ];
+        assert_eq!(
+            format_pipelines(&pipelines),
+            "ingest\n  Inbound\n  1. parse\n  2. validate\n\nempty\n  (no stages)\n"
+        );
+        assert_eq!(format_pipelines(&[]), "No pipelines configured.\n");
+    }
+
+    #[test]
+    fn test_check_min_version() {
+        let info = serde_json::json!({"version": "1.2.3"});
+        assert!(check_min_version(&info, "1.2.0", "feature").is_ok());
+        assert!(check_min_version(&info, "1.2.3", "feature").is_ok());
+
+        let err = check_min_version(&info, "1.3.0", "harmony:metrics").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "harmony:metrics requires Harmony >= 1.3.0, instance reports 1.2.3"
+        );
+
+        let prefixed = serde_json::json!({"version": "v2.0.0"});
+        assert!(check_min_version(&prefixed, "1.2.0", "feature").is_ok());
+
+        let prerelease = serde_json::json!({"version": "1.2.0-beta.1"});
+        assert!(check_min_version(&prerelease, "1.2.0", "feature").is_err());
+    }
+
+    #[test]
+    fn test_check_min_version_missing_or_invalid() {
+        let missing = serde_json::json!({"status": "healthy"});
+        let err = check_min_version(&missing, "1.0.0", "feature").unwrap_err();
+        assert!(err.to_string().contains("did not report a version"));
+
+        let garbage = serde_json::json!({"version": "latest"});
+        assert!(check_min_version(&garbage, "1.0.0", "feature").is_err());
+        assert!(
+            check_min_version(&serde_json::json!({"version": "1.0.0"}), "one", "feature").is_err()
+        );
+    }
+
+    #[test]
+    fn test_read_body_within_limit() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/admin/info")
+            .with_body(r#"{"status":"healthy"}"#)
+            .create();
+
+        let url = format!("{}/admin/info", server.url());
+        let resp = Client::new().get(&url).send().unwrap();
+        let body = read_body_limited(resp, &url, 1024).unwrap();
+
+        assert_eq!(body, r#"{"status":"healthy"}"#);
+        mock.assert();
+    }
+
+    #[test]
+    fn test_read_body_exceeding_limit() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/admin/routes")
+            .with_body("x".repeat(2048))
+            .create();
+
+        let url = format!("{}/admin/routes", server.url());
+        let resp = Client::new().get(&url).send().unwrap();
+        let err = read_body_limited(resp, &url, 1024).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            format!("response from {} exceeded 1024 bytes", url)
+        );
+    }
+
+    fn cols(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_select_columns_keeps_requested_order() {
+        let available = cols(&["id", "path", "methods", "pipeline"]);
+        assert_eq!(
+            select_columns(available, &cols(&["pipeline", "PATH"])),
+            cols(&["pipeline", "path"])
+        );
+    }
+
+    #[test]
+    fn test_select_columns_skips_unknown_and_duplicates() {
+        let available = cols(&["path", "methods"]);
+        assert_eq!(
+            select_columns(available, &cols(&["path", "nope", "path"])),
+            cols(&["path"])
+        );
+    }
+
+    #[test]
+    fn test_select_columns_falls_back_to_all() {
+        let available = cols(&["path", "methods"]);
+        assert_eq!(select_columns(available.clone(), &[]), available.clone());
+        assert_eq!(
+            select_columns(available.clone(), &cols(&["nope"])),
+            available
+        );
+    }
+
+    #[test]
+    fn test_requests_send_accept_header() {
+        let mut server = mockito::Server::new();
+        let json = server
+            .mock("GET", "/admin/info")
+            .match_header("accept", "application/json")
+            .create();
+        let url = format!("{}/admin/info", server.url());
+        let inst = instance_with_prefix("admin");
+        with_instance_headers(Client::new().get(&url), &inst, ACCEPT_JSON)
+            .send()
+            .unwrap();
+        json.assert();
+
+        // A stored Accept header wins over the default
+        let stored = server
+            .mock("GET", "/admin/routes")
+            .match_header("accept", "application/vnd.harmony+json")
+            .create();
+        let url = format!("{}/admin/routes", server.url());
+        let inst = crate::storage::HarmonyInstance {
+            headers: vec![(
+                "Accept".to_string(),
+                "application/vnd.harmony+json".to_string(),
+            )],
+            ..instance_with_prefix("admin")
+        };
+        with_instance_headers(Client::new().get(&url), &inst, ACCEPT_ANY)
+            .send()
+            .unwrap();
+        stored.assert();
+    }
+
+    #[test]
+    fn test_write_ndjson_one_route_per_line() {
+        let routes = serde_json::json!([
+            {"path": "/a", "methods": ["GET"]},
+            {"path": "/b", "methods": ["POST"]}
+        ]);
+        let mut out = Vec::new();
+        write_ndjson(&mut out, &routes).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"methods\":[\"GET\"],\"path\":\"/a\"}\n{\"methods\":[\"POST\"],\"path\":\"/b\"}\n"
+        );
+
+        let mut out = Vec::new();
+        write_ndjson(&mut out, &serde_json::json!({"status": "ok"})).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "{\"status\":\"ok\"}\n");
+    }
+
+    #[test]
+    fn test_format_uptime() {
+        assert_eq!(format_uptime(0), "0s");
+        assert_eq!(format_uptime(59), "59s");
+        assert_eq!(format_uptime(3_600), "1h");
+        assert_eq!(format_uptime(93_784), "1d 2h 3m 4s");
+    }
+
+    #[test]
+    fn test_info_changes_lists_changed_fields_only() {
+        let old = serde_json::json!({"status": "starting", "routes_count": 10, "zone": "eu"});
+        let new = serde_json::json!({"status": "healthy", "routes_count": 12, "zone": "eu", "ready": true});
+        assert_eq!(
+            info_changes(&old, &new),
+            [
+                "ready: (none) -> true",
+                "routes_count: 10 -> 12",
+                r#"status: "starting" -> "healthy""#,
+            ]
+        );
+        assert!(info_changes(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_info_rows_known_fields_first_then_extras() {
+        let json = serde_json::json!({
+            "zone": "eu",
+            "version": "1.4.0",
+            "uptime": 3_661,
+            "build": {"commit": "abc"},
+            "status": "healthy",
+            "routes_count": 12
+        });
+        let info = InfoResponse::deserialize(&json).unwrap();
+        let rows: Vec<String> = info_rows(&info)
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                "status=healthy",
+                "version=1.4.0",
+                "uptime=1h 1m 1s",
+                "routes_count=12",
+                r#"build={"commit":"abc"}"#,
+                "zone=eu",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_print_info_renders_each_nested_array_as_a_section() {
+        let json = serde_json::json!({
+            "status": "healthy",
+            "tags": ["eu", "prod"],
+            "routes": [{"path": "/fhir"}, {"path": "/dicom"}],
+            "pipelines": [{"name": "ingest"}]
+        });
+        let mut out = Vec::new();
+        print_info(&mut out, &json, &[]).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+        assert_eq!(
+            lines,
+            [
+                "KEY    | VALUE",
+                "-------+--------",
+                "status | healthy",
+                "tags   | eu,prod",
+                "",
+                "pipelines (1):",
+                "NAME",
+                "------",
+                "ingest",
+                "",
+                "routes (2):",
+                "PATH",
+                "------",
+                "/fhir",
+                "/dicom",
+            ]
+        );
+
+        // Empty arrays are rows, not "(no results)" sections
+        let mut out = Vec::new();
+        let json = serde_json::json!({"status": "healthy", "tags": [], "routes": []});
+        print_info(&mut out, &json, &[]).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+        assert_eq!(
+            lines,
+            [
+                "KEY    | VALUE",
+                "-------+--------",
+                "status | healthy",
+                "routes |",
+                "tags   |",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_print_info_columns_do_not_reach_nested_tables() {
+        let json = serde_json::json!({
+            "routes": [{"path": "/fhir", "pipeline": "ingest"}],
+            "pipelines": [{"name": "ingest"}]
+        });
+        let mut out = Vec::new();
+        print_info(&mut out, &json, &["path".to_string()]).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("NAME"), "{}", text);
+        assert!(text.contains("PIPELINE"), "{}", text);
+    }
+
+    #[test]
+    fn test_info_response_rejects_non_objects() {
+        assert!(InfoResponse::deserialize(&serde_json::json!("ok")).is_err());
+        let info = InfoResponse::deserialize(&serde_json::json!({"uptime": "3 days"})).unwrap();
+        assert_eq!(
+            info_rows(&info),
+            [("uptime".to_string(), "3 days".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_utc(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_utc(1_714_564_800), "2024-05-01T12:00:00Z");
+        assert_eq!(format_utc(1_735_689_599), "2024-12-31T23:59:59Z");
+        // Leap years, and century boundaries (2000 is a leap year, 2100 is not)
+        assert_eq!(format_utc(946_684_800), "2000-01-01T00:00:00Z");
+        assert_eq!(format_utc(1_709_164_800), "2024-02-29T00:00:00Z");
+        assert_eq!(format_utc(1_677_628_800), "2023-03-01T00:00:00Z");
+        assert_eq!(format_utc(4_107_542_399), "2100-02-28T23:59:59Z");
+        assert_eq!(format_utc(4_107_542_400), "2100-03-01T00:00:00Z");
+    }
+
+    #[test]
+    fn test_config_marker_prefers_version_fields_in_order() {
+        let info = serde_json::json!({
+            "status": "healthy",
+            "config_loaded_at": "2024-05-01T12:00:00Z",
+            "config_version": 7
+        });
+        assert_eq!(
+            config_marker(&info),
+            Some(("config_version", serde_json::json!(7)))
+        );
+        let info = serde_json::json!({"config_version": null, "last_reload": 1714564800});
+        assert_eq!(
+            config_marker(&info),
+            Some(("last_reload", serde_json::json!(1714564800)))
+        );
+        assert_eq!(config_marker(&serde_json::json!({"status": "ok"})), None);
+    }
+
+    #[test]
+    fn test_check_restart() {
+        let since = Duration::from_secs(3);
+        let before = serde_json::json!({"version": "1.0.0", "uptime": 500});
+
+        // The old process answering is not a restart
+        assert_eq!(
+            check_restart(Some(&before), &serde_json::json!({"uptime": 503}), since),
+            Restart::NotYet
+        );
+        assert_eq!(
+            check_restart(Some(&before), &before, since),
+            Restart::NotYet
+        );
+        assert_eq!(
+            check_restart(Some(&before), &serde_json::json!({"uptime": 2}), since),
+            Restart::Seen("uptime 2s".to_string())
+        );
+        assert_eq!(
+            check_restart(
+                Some(&before),
+                &serde_json::json!({"version": "1.1.0", "uptime": 900}),
+                since
+            ),
+            Restart::Seen("version: 1.0.0 -> 1.1.0".to_string())
+        );
+        let started = serde_json::json!({"started_at": "2024-05-01T12:00:00Z"});
+        assert_eq!(
+            check_restart(
+                Some(&started),
+                &serde_json::json!({"started_at": "2024-05-01T12:05:00Z"}),
+                since
+            ),
+            Restart::Seen("started_at: 2024-05-01T12:00:00Z -> 2024-05-01T12:05:00Z".to_string())
+        );
+        // Without a baseline only a fresh uptime counts
+        assert_eq!(
+            check_restart(None, &serde_json::json!({"version": "1.1.0"}), since),
+            Restart::NotYet
+        );
+        assert_eq!(
+            check_restart(None, &serde_json::json!({"status": "ok"}), since),
+            Restart::Unknown
+        );
+    }
+
+    fn rendered(f: impl FnOnce(&mut dyn Write) -> io::Result<()>) -> String {
+        let mut out = Vec::new();
+        f(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_render_array_of_objects_unions_keys() {
+        let routes = serde_json::json!([
+            {"path": "/a", "methods": ["GET", "POST"]},
+            {"path": "/longer", "pipeline": "p1"}
+        ]);
+        let arr = routes.as_array().unwrap();
+        assert_eq!(
+            rendered(|out| render_array_of_objects(out, arr, &[])),
+            "METHODS  | PATH    | PIPELINE\n\
+             ---------+---------+---------\n\
+             GET,POST | /a      |         \n\
+             \u{20}        | /longer | p1      \n"
+        );
+        assert_eq!(
+            rendered(|out| render_array_of_objects(out, arr, &["path".to_string()])),
+            "PATH   \n-------\n/a     \n/longer\n"
+        );
+        assert_eq!(
+            rendered(|out| render_array_of_objects(out, &[], &[])),
+            "(no results)\n"
+        );
+    }
+
+    #[test]
+    fn test_render_json_table_object_sorted() {
+        let info = serde_json::json!({"version": "1.4.0", "status": "ok"});
+        assert_eq!(
+            rendered(|out| render_json_table(out, &info, &[])),
+            "KEY     | VALUE\n--------+------\nstatus  | ok   \nversion | 1.4.0\n"
+        );
+    }
+
+    #[test]
+    fn test_print_info_columns_select_fields() {
+        let json = serde_json::json!({"status": "healthy", "version": "1.2.0", "uptime": 5});
+        let text =
+            rendered(|out| print_info(out, &json, &["VERSION".to_string(), "status".to_string()]));
+        let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+        assert_eq!(
+            lines,
+            [
+                "KEY     | VALUE",
+                "--------+--------",
+                "version | 1.2.0",
+                "status  | healthy",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pipelines_named() {
+        let pipelines = || {
+            Vec::<Pipeline>::deserialize(serde_json::json!([
+                {"name": "ingest", "stages": ["parse"]},
+                {"name": "egress", "stages": ["send"]},
+                {"name": "ingest", "stages": ["parse", "store"]}
+            ]))
+            .unwrap()
+        };
+        let matches = pipelines_named(pipelines(), "ingest").unwrap();
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[1].stages, ["parse", "store"]);
+
+        let err = pipelines_named(pipelines(), "Ingest").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no pipeline named 'Ingest'; available pipelines: egress, ingest"
+        );
+        let err = pipelines_named(Vec::new(), "ingest").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no pipeline named 'ingest' (no pipelines configured)"
+        );
+    }
+
+    #[test]
+    fn test_endpoint_verdict() {
+        assert_eq!(endpoint_verdict(StatusCode::OK, "{}", true), Ok(()));
+        assert_eq!(
+            endpoint_verdict(StatusCode::NOT_FOUND, "{}", true),
+            Err("HTTP 404".to_string())
+        );
+        assert!(
+            endpoint_verdict(StatusCode::OK, "<html>", true)
+                .unwrap_err()
+                .starts_with("invalid JSON")
+        );
+
+        // The data-plane root passes on anything but a server error, JSON or not
+        assert_eq!(endpoint_verdict(StatusCode::NOT_FOUND, "", false), Ok(()));
+        assert_eq!(
+            endpoint_verdict(StatusCode::BAD_GATEWAY, "", false),
+            Err("HTTP 502".to_string())
+        );
+    }
+
+    #[test]
+    fn test_routes_for_endpoint() {
+        let routes = serde_json::json!([
+            {"path": "/a", "endpoint_name": "fhir"},
+            {"path": "/b", "endpoint_name": "dicom"},
+            {"path": "/c", "endpoint_name": "fhir"},
+            {"path": "/d"}
+        ]);
+        let routes = routes.as_array().unwrap();
+        let paths: Vec<&Value> = routes_for_endpoint(routes, "fhir")
+            .unwrap()
+            .iter()
+            .map(|r| &r["path"])
+            .collect();
+        assert_eq!(paths, ["/a", "/c"]);
+
+        assert_eq!(count_routes(routes, None), 4);
+        assert_eq!(count_routes(routes, Some("fhir")), 2);
+        assert_eq!(count_routes(routes, Some("FHIR")), 0);
+
+        let err = routes_for_endpoint(routes, "FHIR").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no route has endpoint 'FHIR'; available endpoints: dicom, fhir"
+        );
     }
 }
@@ -1,5 +1,9 @@
 #![allow(clippy::module_inception)]
 
+pub mod batch;
+pub mod diff;
+pub mod group;
 pub mod harmony;
 pub mod info;
 pub mod management;
+pub mod validate;
@@ -0,0 +1,306 @@
+//! Offline lint of the instance registry (`harmony:validate`)
+//!
+//! The registry is read as raw JSON rather than through `load_harmony_instances`,
+//! so entries that would fail to deserialize are reported instead of aborting,
+//! and nothing (such as backfilled IDs) is written back.
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::storage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem with one registry entry
+#[derive(Debug, PartialEq, Eq)]
+struct Finding {
+    severity: Severity,
+    /// Index of the entry in `harmony.json`
+    entry: usize,
+    message: String,
+}
+
+impl Finding {
+    fn error(entry: usize, message: impl Into<String>) -> Self {
+        Finding {
+            severity: Severity::Error,
+            entry,
+            message: message.into(),
+        }
+    }
+
+    fn warning(entry: usize, message: impl Into<String>) -> Self {
+        Finding {
+            severity: Severity::Warning,
+            entry,
+            message: message.into(),
+        }
+    }
+
+    fn render(&self, entries: &[Value]) -> String {
+        let (icon, severity) = match self.severity {
+            Severity::Error => ("✗", "error"),
+            Severity::Warning => ("⚠", "warning"),
+        };
+        let location = match entries[self.entry].get("label").and_then(|l| l.as_str()) {
+            Some(label) => format!("entry {} ({})", self.entry, label),
+            None => format!("entry {}", self.entry),
+        };
+        format!("{} {:<7} {}: {}", icon, severity, location, self.message)
+    }
+}
+
+/// Check `harmony.json` for problems without contacting any instance
+///
+/// Fails if any error is found; warnings alone do not change the exit code.
+pub fn validate() -> Result<()> {
+    let path = storage::harmony_file_path()?;
+    if !path.exists() {
+        println!("No registry at {}; nothing to validate.", path.display());
+        return Ok(());
+    }
+
+    let data =
+        std::fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+    // Accept both the versioned envelope and the legacy bare array
+    let entries = match serde_json::from_str::<Value>(&data) {
+        Ok(Value::Array(entries)) => entries,
+        Ok(Value::Object(mut doc)) => match doc.remove("instances") {
+            Some(Value::Array(entries)) => entries,
+            _ => anyhow::bail!("{} has no \"instances\" array", path.display()),
+        },
+        Ok(_) => anyhow::bail!("{} is not a registry of instances", path.display()),
+        Err(e) => anyhow::bail!("{} is not valid JSON: {}", path.display(), e),
+    };
+
+    let findings = lint(&entries);
+    println!("Validating {} ({} entries)", path.display(), entries.len());
+    for finding in &findings {
+        println!("{}", finding.render(&entries));
+    }
+
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    let warnings = findings.len() - errors;
+    println!("{} error(s), {} warning(s)", errors, warnings);
+    if errors > 0 {
+        anyhow::bail!("registry has {} error(s)", errors);
+    }
+    Ok(())
+}
+
+/// All problems in the registry entries, ordered by entry then severity
+fn lint(entries: &[Value]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut ids: HashMap<&str, usize> = HashMap::new();
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    let mut addresses: HashMap<(String, u64), usize> = HashMap::new();
+
+    for (i, entry) in entries.iter().enumerate() {
+        let Some(obj) = entry.as_object() else {
+            findings.push(Finding::error(i, "entry is not a JSON object"));
+            continue;
+        };
+
+        for field in ["ip", "port", "label"] {
+            if !obj.contains_key(field) {
+                findings.push(Finding::error(
+                    i,
+                    format!("missing required field '{}'", field),
+                ));
+            }
+        }
+
+        match obj.get("id").and_then(|v| v.as_str()) {
+            Some(id) if !id.is_empty() => {
+                if let Some(first) = ids.insert(id, i) {
+                    findings.push(Finding::error(
+                        i,
+                        format!("duplicate ID '{}' (also entry {})", id, first),
+                    ));
+                }
+            }
+            _ => findings.push(Finding::warning(
+                i,
+                "no ID; one will be assigned the next time the registry is loaded",
+            )),
+        }
+
+        let label = obj.get("label").and_then(|v| v.as_str());
+        match label {
+            Some(l) if l.trim().is_empty() => findings.push(Finding::error(i, "empty label")),
+            Some(l) => {
+                if let Some(first) = labels.insert(l, i) {
+                    findings.push(Finding::error(
+                        i,
+                        format!("duplicate label '{}' (also entry {})", l, first),
+                    ));
+                }
+            }
+            None if obj.contains_key("label") => {
+                findings.push(Finding::error(i, "label is not a string"))
+            }
+            None => {}
+        }
+
+        let ip = obj.get("ip").and_then(|v| v.as_str());
+        match ip {
+            Some(ip) if ip.trim().is_empty() => findings.push(Finding::error(i, "empty IP")),
+            None if obj.contains_key("ip") => {
+                findings.push(Finding::error(i, "ip is not a string"))
+            }
+            _ => {}
+        }
+
+        let port = obj.get("port");
+        let valid_port = port
+            .and_then(|p| p.as_u64())
+            .filter(|p| (1..=u64::from(u16::MAX)).contains(p));
+        if let Some(port) = port
+            && valid_port.is_none()
+        {
+            findings.push(Finding::error(
+                i,
+                format!("invalid port {} (expected 1-65535)", port),
+            ));
+        }
+
+        if let (Some(ip), Some(port)) = (ip.filter(|ip| !ip.trim().is_empty()), valid_port)
+            && let Some(first) = addresses.insert((ip.to_string(), port), i)
+        {
+            findings.push(Finding::warning(
+                i,
+                format!("address {}:{} is also used by entry {}", ip, port, first),
+            ));
+        }
+
+        match obj.get("path_prefix") {
+            None => {}
+            Some(Value::String(prefix)) => match storage::normalize_path_prefix(prefix) {
+                Ok(normalized) if normalized != *prefix => findings.push(Finding::warning(
+                    i,
+                    format!(
+                        "path prefix '{}' is not normalized (expected '{}')",
+                        prefix, normalized
+                    ),
+                )),
+                Ok(_) => {}
+                Err(e) => findings.push(Finding::error(i, e.to_string())),
+            },
+            Some(other) => findings.push(Finding::error(
+                i,
+                format!("path_prefix is not a string: {}", other),
+            )),
+        }
+
+        if let Some(scheme) = obj.get("scheme") {
+            let valid = scheme
+                .as_str()
+                .is_some_and(|s| storage::normalize_scheme(s).is_ok());
+            if !valid {
+                findings.push(Finding::error(
+                    i,
+                    format!("invalid scheme {} (expected \"http\" or \"https\")", scheme),
+                ));
+            }
+        }
+    }
+
+    findings.sort_by_key(|f| (f.entry, f.severity));
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn messages(entries: &Value) -> Vec<(Severity, usize, String)> {
+        lint(entries.as_array().unwrap())
+            .into_iter()
+            .map(|f| (f.severity, f.entry, f.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_lint_clean_registry() {
+        let entries = json!([
+            {"id": "a1", "ip": "10.0.0.5", "port": 8081, "label": "prod", "path_prefix": "admin"},
+            {"id": "b2", "ip": "10.0.0.6", "port": 8081, "label": "staging", "scheme": "https"}
+        ]);
+        assert!(messages(&entries).is_empty());
+    }
+
+    #[test]
+    fn test_lint_duplicates() {
+        let entries = json!([
+            {"id": "a1", "ip": "10.0.0.5", "port": 8081, "label": "prod"},
+            {"id": "a1", "ip": "10.0.0.5", "port": 8081, "label": "prod"}
+        ]);
+        assert_eq!(
+            messages(&entries),
+            [
+                (
+                    Severity::Error,
+                    1,
+                    "duplicate ID 'a1' (also entry 0)".to_string()
+                ),
+                (
+                    Severity::Error,
+                    1,
+                    "duplicate label 'prod' (also entry 0)".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    1,
+                    "address 10.0.0.5:8081 is also used by entry 0".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_invalid_fields() {
+        let entries = json!([
+            {"id": "a1", "ip": " ", "port": 70000, "label": "x", "path_prefix": "http://h/admin"},
+            {"ip": "10.0.0.5", "path_prefix": "/admin/", "scheme": "ftp"},
+            "not an object"
+        ]);
+        let found = messages(&entries);
+        let has = |severity: Severity, entry: usize, text: &str| {
+            found
+                .iter()
+                .any(|(s, e, m)| *s == severity && *e == entry && m.contains(text))
+        };
+        assert!(has(Severity::Error, 0, "empty IP"));
+        assert!(has(Severity::Error, 0, "invalid port 70000"));
+        assert!(has(Severity::Error, 0, "not a URL"));
+        assert!(has(Severity::Error, 1, "missing required field 'port'"));
+        assert!(has(Severity::Error, 1, "missing required field 'label'"));
+        assert!(has(Severity::Error, 1, "invalid scheme \"ftp\""));
+        assert!(has(Severity::Warning, 1, "no ID"));
+        assert!(has(Severity::Warning, 1, "expected 'admin'"));
+        assert!(has(Severity::Error, 2, "not a JSON object"));
+    }
+
+    #[test]
+    fn test_finding_render() {
+        let entries = json!([{"label": "prod"}, {}]);
+        let entries = entries.as_array().unwrap();
+        assert_eq!(
+            Finding::error(0, "empty IP").render(entries),
+            "✗ error   entry 0 (prod): empty IP"
+        );
+        assert_eq!(
+            Finding::warning(1, "no ID").render(entries),
+            "⚠ warning entry 1: no ID"
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Shared HTTP client configuration for the global `--proxy` / `--no-proxy` flags
+//!
+//! Clients built here apply the selected proxy explicitly. The choice is also
+//! exported through the standard proxy environment variables so that clients
+//! created inside runbeam-sdk (Runbeam Cloud API, JWKS) follow it too.
+//!
+//! With the global `--offline` flag no client can be built at all. Commands that
+//! reach the network through runbeam-sdk call [`require_network`] themselves.
+//!
+//! `--resolve HOST:IP` overrides are applied to clients built here only; runbeam-sdk
+//! clients keep using DNS.
+//!
+//! With `--connect-via`, requests for instances reached through the SSH tunnel
+//! go through its SOCKS proxy; other requests use the proxy settings above.
+//!
+//! Every request from a client built here carries the run's trace ID in an
+//! `X-Request-Id` header (global `--trace-id`, or a random UUID), so it can be
+//! matched with Harmony and Runbeam Cloud logs. Requests made inside runbeam-sdk
+//! (the JWKS fetch when validating a token, and gateway authorization) go without
+//! it: the SDK builds its own clients and takes no extra headers.
+
+use anyhow::{Context, Result};
+use reqwest::header::{HeaderMap, HeaderValue};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::debug;
+
+static RESOLVE: OnceLock<Vec<(String, IpAddr)>> = OnceLock::new();
+
+/// Pin these hosts to fixed addresses for every client built here (global `--resolve`)
+pub fn configure_resolve(overrides: Vec<(String, IpAddr)>) {
+    for (host, ip) in &overrides {
+        debug!(host, %ip, "resolving host via --resolve instead of DNS");
+    }
+    let _ = RESOLVE.set(overrides);
+}
+
+/// `(host, address)` pairs for `ClientBuilder::resolve`; port 0 keeps the URL's port
+fn resolve_overrides() -> impl Iterator<Item = (&'static str, SocketAddr)> {
+    RESOLVE
+        .get()
+        .into_iter()
+        .flatten()
+        .map(|(host, ip)| (host.as_str(), SocketAddr::new(*ip, 0)))
+}
+
+/// Header carrying the trace ID
+pub const TRACE_ID_HEADER: &str = "X-Request-Id";
+
+static TRACE_ID: OnceLock<String> = OnceLock::new();
+
+/// Use `id` as this run's trace ID instead of a generated one (global `--trace-id`)
+pub fn configure_trace_id(id: String) {
+    let _ = TRACE_ID.set(id);
+}
+
+/// The trace ID sent with every request of this run, generated on first use
+pub fn trace_id() -> &'static str {
+    TRACE_ID.get_or_init(random_uuid)
+}
+
+/// A random (version 4) UUID in its usual hyphenated form
+fn random_uuid() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// Default headers for every client built here
+fn trace_headers() -> Result<HeaderMap> {
+    let value = HeaderValue::from_str(trace_id())
+        .with_context(|| format!("invalid trace ID '{}'", trace_id()))?;
+    let mut headers = HeaderMap::new();
+    headers.insert(TRACE_ID_HEADER, value);
+    Ok(headers)
+}
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Refuse all network access for the rest of the process (global `--offline`)
+pub fn go_offline() {
+    OFFLINE.store(true, Ordering::Relaxed);
+}
+
+/// Whether `--offline` was given
+pub fn offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+/// Fail fast, instead of timing out, when running with `--offline`
+pub fn require_network() -> Result<()> {
+    if offline() {
+        anyhow::bail!("operation requires network access (running in --offline mode)");
+    }
+    Ok(())
+}
+
+/// How outgoing requests should be proxied
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ProxySetting {
+    /// Use `HTTP_PROXY` / `HTTPS_PROXY` / `NO_PROXY` from the environment (reqwest default)
+    #[default]
+    Env,
+    /// Never use a proxy
+    Disabled,
+    /// Send every request through this proxy, except hosts listed in `NO_PROXY`
+    Url(String),
+}
+
+static PROXY: OnceLock<ProxySetting> = OnceLock::new();
+
+/// Select the proxy used for all outgoing requests
+///
+/// Must be called at startup, before any other threads are spawned, because it
+/// updates the process environment.
+pub fn configure_proxy(setting: ProxySetting) -> Result<()> {
+    match &setting {
+        ProxySetting::Env => {}
+        ProxySetting::Disabled => {
+            // SAFETY: called from main before any other threads exist
+            unsafe {
+                std::env::set_var("NO_PROXY", "*");
+                std::env::set_var("no_proxy", "*");
+            }
+        }
+        ProxySetting::Url(url) => {
+            reqwest::Proxy::all(url).with_context(|| format!("invalid proxy URL '{}'", url))?;
+            // SAFETY: called from main before any other threads exist
+            unsafe {
+                for var in ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"] {
+                    std::env::set_var(var, url);
+                }
+            }
+        }
+    }
+    let _ = PROXY.set(setting);
+    Ok(())
+}
+
+fn explicit_proxy(url: &str) -> Result<reqwest::Proxy> {
+    Ok(reqwest::Proxy::all(url)
+        .with_context(|| format!("invalid proxy URL '{}'", url))?
+        .no_proxy(reqwest::NoProxy::from_env()))
+}
+
+/// `HTTP_PROXY` / `HTTPS_PROXY` (either case) as explicit proxies, honouring `NO_PROXY`
+///
+/// reqwest stops reading the environment once any proxy is added, so these stand
+/// in for it when the `--connect-via` proxy has to come first.
+fn env_proxies() -> Result<Vec<reqwest::Proxy>> {
+    let mut proxies = Vec::new();
+    for (var, https) in [("HTTP_PROXY", false), ("HTTPS_PROXY", true)] {
+        let Some(url) = std::env::var(var)
+            .or_else(|_| std::env::var(var.to_ascii_lowercase()))
+            .ok()
+            .filter(|url| !url.is_empty())
+        else {
+            continue;
+        };
+        let proxy = if https {
+            reqwest::Proxy::https(&url)
+        } else {
+            reqwest::Proxy::http(&url)
+        };
+        let proxy = proxy.with_context(|| format!("invalid proxy URL '{}' in {}", url, var))?;
+        proxies.push(proxy.no_proxy(reqwest::NoProxy::from_env()));
+    }
+    Ok(proxies)
+}
+
+/// Proxies for a new client, in priority order, or `None` to keep reqwest's
+/// environment-based default
+fn proxy_list() -> Result<Option<Vec<reqwest::Proxy>>> {
+    let tunnel = crate::tunnel::configured()
+        .then(|| reqwest::Proxy::custom(crate::tunnel::proxy_for))
+        .into_iter();
+    Ok(match PROXY.get().cloned().unwrap_or_default() {
+        ProxySetting::Env if !crate::tunnel::configured() => None,
+        ProxySetting::Env => Some(tunnel.chain(env_proxies()?).collect()),
+        ProxySetting::Disabled => Some(tunnel.collect()),
+        ProxySetting::Url(url) => Some(tunnel.chain([explicit_proxy(&url)?]).collect()),
+    })
+}
+
+/// Blocking client builder with the configured proxy applied
+pub fn blocking_client_builder() -> Result<reqwest::blocking::ClientBuilder> {
+    require_network()?;
+    let mut builder = reqwest::blocking::Client::builder().default_headers(trace_headers()?);
+    for (host, addr) in resolve_overrides() {
+        builder = builder.resolve(host, addr);
+    }
+    if let Some(proxies) = proxy_list()? {
+        builder = builder.no_proxy();
+        for proxy in proxies {
+            builder = builder.proxy(proxy);
+        }
+    }
+    Ok(builder)
+}
+
+/// Blocking client with the configured proxy applied
+pub fn blocking_client() -> Result<reqwest::blocking::Client> {
+    blocking_client_builder()?
+        .build()
+        .context("building HTTP client")
+}
+
+/// Async client with the configured proxy applied
+pub fn async_client() -> Result<reqwest::Client> {
+    require_network()?;
+    let mut builder = reqwest::Client::builder().default_headers(trace_headers()?);
+    for (host, addr) in resolve_overrides() {
+        builder = builder.resolve(host, addr);
+    }
+    if let Some(proxies) = proxy_list()? {
+        builder = builder.no_proxy();
+        for proxy in proxies {
+            builder = builder.proxy(proxy);
+        }
+    }
+    builder.build().context("building HTTP client")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_uuid_is_version_4() {
+        let id = random_uuid();
+        let groups: Vec<&str> = id.split('-').collect();
+        assert_eq!(
+            groups.iter().map(|g| g.len()).collect::<Vec<_>>(),
+            [8, 4, 4, 4, 12]
+        );
+        assert!(groups[2].starts_with('4'));
+        assert!(matches!(
+            groups[3].chars().next(),
+            Some('8' | '9' | 'a' | 'b')
+        ));
+        assert_ne!(random_uuid(), id);
+    }
+}
@@ -1,10 +1,19 @@
 mod cli;
 mod commands;
+mod http;
+mod output;
+mod prompt;
+mod retry;
 mod storage;
+mod style;
+mod timings;
+mod tunnel;
 
 use anyhow::Result;
-use clap::Parser;
-use commands::{auth, basic, config, harmony};
+use clap::{CommandFactory, FromArgMatches};
+use commands::{auth, basic, config, doctor, harmony};
+use std::io::IsTerminal;
+use std::time::Duration;
 use tracing::{debug, warn};
 use tracing_subscriber::{EnvFilter, fmt};
 
@@ -22,73 +31,373 @@ fn init_tracing(verbosity: u8, quiet: bool) {
 
     let env_filter = std::env::var("RUST_LOG").unwrap_or_else(|_| level.to_string());
 
+    // Log to stderr so command output on stdout (JSON, exported tokens) stays clean
     let _ = fmt()
+        .with_writer(std::io::stderr)
         .with_env_filter(EnvFilter::new(env_filter))
         .with_target(false)
         .with_level(true)
         .try_init();
 }
 
+/// Exit status used when `--max-time` is exceeded (same as coreutils `timeout`)
+const MAX_TIME_EXIT_CODE: i32 = 124;
+
+/// Start a watchdog that aborts the process once `limit` has elapsed
+fn spawn_deadline(limit: Duration) {
+    std::thread::spawn(move || {
+        std::thread::sleep(limit);
+        eprintln!(
+            "Error: command exceeded --max-time of {}",
+            cli::format_duration(limit)
+        );
+        tunnel::close();
+        std::process::exit(MAX_TIME_EXIT_CODE);
+    });
+}
+
 fn main() -> Result<()> {
-    let args = cli::Cli::parse();
+    let matches = cli::Cli::command()
+        .get_matches_from(cli::join_spaced_subcommand(std::env::args_os().collect()));
+    let args = cli::Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
 
     init_tracing(args.verbose, args.quiet);
 
     debug!(?args.verbose, quiet = args.quiet, "logging initialized");
 
+    if args.no_migrate {
+        storage::disable_auth_migration();
+    }
+
+    let output = cli::resolve_output(
+        args.output,
+        std::env::var("RUNBEAM_OUTPUT").ok().as_deref(),
+        std::io::stdout().is_terminal(),
+    )
+    .map_err(anyhow::Error::msg)?;
+    debug!(?output, "output format");
+    if args.compact {
+        output::enable_compact();
+    }
+    if output == cli::OutputFormat::Markdown {
+        output::enable_markdown();
+    }
+    if args.quiet {
+        output::enable_quiet();
+    }
+
+    // Before any threads are spawned: these may update the process environment
+    let backend_preference = storage::apply_backend_preference()?;
+    if args.no_proxy {
+        http::configure_proxy(http::ProxySetting::Disabled)?;
+    } else if let Some(url) = &args.proxy {
+        http::configure_proxy(http::ProxySetting::Url(url.clone()))?;
+    }
+
+    if args.offline {
+        http::go_offline();
+    }
+    if !args.resolve.is_empty() {
+        http::configure_resolve(args.resolve.clone());
+    }
+    if let Some(id) = &args.trace_id {
+        http::configure_trace_id(id.clone());
+    }
+    debug!(trace_id = http::trace_id(), "request trace ID");
+
+    retry::configure(config::retry_policy(args.retries, args.retry_delay));
+
+    if let Some(jump_host) = &args.connect_via {
+        tunnel::configure(jump_host.clone())?;
+    }
+    let _tunnel = tunnel::Guard;
+
+    if let Some(limit) = args.max_time {
+        spawn_deadline(limit);
+    }
+
+    if args.timings {
+        timings::enable();
+    }
+
+    if args.yes {
+        prompt::assume_yes();
+    }
+    let _timings = timings::Summary::start();
+
     match args.command {
         Some(cli::Command::List) => {
-            basic::list_commands()?;
+            basic::list_commands(output, args.verbose > 0)?;
         }
-        Some(cli::Command::Login) => {
-            auth::login()?;
+        Some(cli::Command::Login {
+            team,
+            events,
+            poll_interval,
+        }) => {
+            auth::login(team.as_deref(), events, poll_interval)?;
         }
         Some(cli::Command::Logout) => {
             auth::logout()?;
         }
-        Some(cli::Command::Verify) => {
-            auth::verify_token()?;
+        Some(cli::Command::Verify { timeout }) => {
+            auth::verify_token(timeout, output)?;
+        }
+        Some(cli::Command::AuthRefresh) => {
+            auth::refresh_token()?;
+        }
+        Some(cli::Command::AuthBackend) => {
+            auth::storage_backend(backend_preference, output)?;
+        }
+        Some(cli::Command::AuthToken { decode, raw }) => {
+            auth::show_token(decode, raw, output)?;
+        }
+        Some(cli::Command::TokenExport { header, no_newline }) => {
+            auth::export_token(header, no_newline)?;
+        }
+        Some(cli::Command::Doctor { fix }) => {
+            doctor::doctor(fix, output)?;
         }
         Some(cli::Command::HarmonyAdd {
             ip,
             port,
+            port_range,
             label,
             path_prefix,
+            scheme,
             encryption_key,
+            headers,
+            test,
+            force,
+            clone_from,
         }) => {
-            harmony::harmony::harmony_add(
-                &ip,
-                port,
-                label.as_deref(),
-                &path_prefix,
-                encryption_key.as_deref(),
+            // Bare `harmony:add` on a terminal asks instead of silently using defaults
+            let wizard = (!cli::subcommand_has_args(&matches, "harmony:add")
+                && prompt::interactive())
+            .then(harmony::harmony::add_wizard)
+            .transpose()?;
+            let (ip, port, label, path_prefix, scheme) = match wizard {
+                Some(details) => (
+                    details.ip,
+                    details.port,
+                    Some(details.label),
+                    details.path_prefix,
+                    Some(details.scheme),
+                ),
+                None => (ip, port, label, path_prefix, scheme),
+            };
+            if let Some((start, end)) = port_range {
+                harmony::harmony::harmony_add_range(
+                    &ip,
+                    start..=end,
+                    label.as_deref(),
+                    &path_prefix,
+                    scheme.as_deref(),
+                    encryption_key.as_deref(),
+                    &headers,
+                    test,
+                    force,
+                    clone_from.as_deref(),
+                )?;
+            } else {
+                harmony::harmony::harmony_add(
+                    &ip,
+                    port,
+                    label.as_deref(),
+                    &path_prefix,
+                    scheme.as_deref(),
+                    encryption_key.as_deref(),
+                    &headers,
+                    test,
+                    force,
+                    clone_from.as_deref(),
+                )?;
+            }
+        }
+        Some(cli::Command::HarmonyList {
+            tag,
+            json,
+            check_auth,
+        }) => {
+            harmony::harmony::harmony_list(
+                &mut std::io::stdout(),
+                tag.as_deref(),
+                json || output == cli::OutputFormat::Json,
+                check_auth,
             )?;
         }
-        Some(cli::Command::HarmonyList) => {
-            harmony::harmony::harmony_list()?;
+        Some(cli::Command::HarmonyValidate) => {
+            harmony::validate::validate()?;
         }
+        Some(cli::Command::HarmonyGroup { action }) => match action {
+            cli::GroupAction::Create { name } => harmony::group::create(&name)?,
+            cli::GroupAction::Delete { name } => harmony::group::delete(&name)?,
+            cli::GroupAction::Add { name, id, label } => {
+                harmony::group::add(&name, id.as_deref(), label.as_deref())?
+            }
+            cli::GroupAction::Remove { name, id, label } => {
+                harmony::group::remove(&name, id.as_deref(), label.as_deref())?
+            }
+            cli::GroupAction::List { name } => {
+                harmony::group::list(&mut std::io::stdout(), name.as_deref())?
+            }
+        },
         Some(cli::Command::HarmonyRemove {
             id,
             label,
             ip,
             port,
+            stale,
+            stale_timeout,
         }) => {
-            harmony::harmony::harmony_remove(id.as_deref(), label.as_deref(), ip.as_deref(), port)?;
+            if stale {
+                harmony::harmony::harmony_remove_stale(stale_timeout)?;
+            } else {
+                harmony::harmony::harmony_remove(
+                    id.as_deref(),
+                    label.as_deref(),
+                    ip.as_deref(),
+                    port,
+                )?;
+            }
         }
-        Some(cli::Command::HarmonyInfo { id, label }) => {
-            harmony::management::info(id.as_deref(), label.as_deref())?;
+        Some(cli::Command::HarmonyInfo {
+            id,
+            label,
+            raw_body,
+            since_version,
+            columns,
+            all,
+            group,
+            tag,
+            fail_fast,
+            watch,
+            interval,
+            diff,
+            probe_all_endpoints,
+        }) => {
+            if probe_all_endpoints {
+                if all || group.is_some() {
+                    harmony::management::selfcheck_all(
+                        group.as_deref(),
+                        tag.as_deref(),
+                        fail_fast,
+                        output,
+                    )?;
+                } else {
+                    harmony::management::selfcheck(id.as_deref(), label.as_deref(), output)?;
+                }
+            } else if watch {
+                harmony::management::watch_info(
+                    id.as_deref(),
+                    label.as_deref(),
+                    interval,
+                    diff,
+                    &columns,
+                )?;
+            } else if all || group.is_some() {
+                harmony::management::info_all(
+                    group.as_deref(),
+                    tag.as_deref(),
+                    fail_fast,
+                    raw_body,
+                    since_version.as_deref(),
+                    &columns,
+                    output,
+                )?;
+            } else {
+                harmony::management::info(
+                    id.as_deref(),
+                    label.as_deref(),
+                    raw_body,
+                    since_version.as_deref(),
+                    &columns,
+                    output,
+                )?;
+            }
         }
-        Some(cli::Command::HarmonyPipelines { id, label }) => {
-            harmony::management::pipelines(id.as_deref(), label.as_deref())?;
+        Some(cli::Command::HarmonyPipelines {
+            id,
+            label,
+            raw_body,
+            columns,
+            pipeline,
+        }) => {
+            harmony::management::pipelines(
+                id.as_deref(),
+                label.as_deref(),
+                raw_body,
+                &columns,
+                pipeline.as_deref(),
+                output,
+            )?;
         }
-        Some(cli::Command::HarmonyRoutes { id, label, json }) => {
-            harmony::management::routes(id.as_deref(), label.as_deref(), json)?;
+        Some(cli::Command::HarmonyRoutes {
+            id,
+            label,
+            json,
+            raw_body,
+            columns,
+            stream,
+            diff,
+            endpoint,
+            sort,
+            reverse,
+            count_only,
+        }) => {
+            harmony::management::routes(
+                id.as_deref(),
+                label.as_deref(),
+                json || output == cli::OutputFormat::Json,
+                raw_body,
+                &columns,
+                stream,
+                diff.as_deref(),
+                endpoint.as_deref(),
+                sort.map(|key| (key, reverse)),
+                count_only,
+            )?;
         }
-        Some(cli::Command::HarmonyReload { id, label }) => {
-            harmony::management::reload(id.as_deref(), label.as_deref())?;
+        Some(cli::Command::HarmonySnapshot { id, label, out }) => {
+            harmony::management::snapshot(id.as_deref(), label.as_deref(), out.as_deref())?;
         }
-        Some(cli::Command::HarmonyAuthorize { id, label }) => {
-            auth::authorize_harmony(id.as_deref(), label.as_deref())?;
+        Some(cli::Command::HarmonyWait {
+            id,
+            label,
+            timeout,
+            interval,
+        }) => {
+            harmony::management::wait(id.as_deref(), label.as_deref(), timeout, interval)?;
+        }
+        Some(cli::Command::HarmonyReload {
+            id,
+            label,
+            all,
+            group,
+            tag,
+            fail_fast,
+            wait,
+            wait_timeout,
+        }) => {
+            let wait = wait.then_some(wait_timeout);
+            if all || group.is_some() {
+                harmony::management::reload_all(
+                    group.as_deref(),
+                    tag.as_deref(),
+                    fail_fast,
+                    wait,
+                    output,
+                )?;
+            } else {
+                harmony::management::reload(id.as_deref(), label.as_deref(), wait, output)?;
+            }
+        }
+        Some(cli::Command::HarmonyAuthorize {
+            id,
+            label,
+            print_token,
+            force,
+        }) => {
+            auth::authorize_harmony(id.as_deref(), label.as_deref(), print_token, force, output)?;
         }
         Some(cli::Command::HarmonySetKey { id, encryption_key }) => {
             harmony::harmony::harmony_set_key(&id, &encryption_key)?;
@@ -99,8 +408,48 @@ fn main() -> Result<()> {
         Some(cli::Command::HarmonyDeleteKey { id }) => {
             harmony::harmony::harmony_delete_key(&id)?;
         }
-        Some(cli::Command::HarmonyUpdate { id, label }) => {
-            harmony::management::update(id.as_deref(), label.as_deref())?;
+        Some(cli::Command::HarmonySetHeader { id, header }) => {
+            harmony::harmony::harmony_set_header(&id, &header)?;
+        }
+        Some(cli::Command::HarmonyUnsetHeader { id, name }) => {
+            harmony::harmony::harmony_unset_header(&id, &name)?;
+        }
+        Some(cli::Command::HarmonySetPrefix { id, prefix }) => {
+            harmony::harmony::harmony_set_prefix(&id, &prefix)?;
+        }
+        Some(cli::Command::HarmonySetScheme { id, scheme }) => {
+            harmony::harmony::harmony_set_scheme(&id, &scheme)?;
+        }
+        Some(cli::Command::HarmonyCopy {
+            from_id_or_label,
+            new_label,
+            ip,
+            port,
+        }) => {
+            harmony::harmony::harmony_copy(&from_id_or_label, &new_label, ip.as_deref(), port)?;
+        }
+        Some(cli::Command::HarmonyTag { id, tags }) => {
+            harmony::harmony::harmony_tag(&id, &tags)?;
+        }
+        Some(cli::Command::HarmonyUntag { id, tags }) => {
+            harmony::harmony::harmony_untag(&id, &tags)?;
+        }
+        Some(cli::Command::HarmonyRenameByLabel { label, to }) => {
+            harmony::harmony::harmony_rename_by_label(&label, &to)?;
+        }
+        Some(cli::Command::HarmonyUpdate {
+            id,
+            label,
+            all,
+            group,
+            tag,
+            fail_fast,
+        }) => {
+            if all || group.is_some() {
+                harmony::management::update_all(group.as_deref(), tag.as_deref(), fail_fast)?;
+            } else {
+                harmony::management::update(id.as_deref(), label.as_deref())?;
+            }
         }
         Some(cli::Command::TestBrowser) => {
             println!("Testing browser opening...");
@@ -109,15 +458,30 @@ fn main() -> Result<()> {
                 Err(e) => println!("✗ Failed to open browser: {}", e),
             }
         }
-        Some(cli::Command::ConfigSet { key, value }) => {
-            config::set_config(&key, &value)?;
+        Some(cli::Command::ConfigSet {
+            key,
+            value,
+            check,
+            force,
+        }) => {
+            config::set_config(&key, &value, check, force)?;
         }
-        Some(cli::Command::ConfigGet { key }) => {
-            config::get_config(key.as_deref())?;
+        Some(cli::Command::ConfigGet { key, env, reveal }) => {
+            if env {
+                config::show_env(reveal, output)?;
+            } else {
+                config::get_config(&mut std::io::stdout(), key.as_deref(), output)?;
+            }
         }
         Some(cli::Command::ConfigUnset { key }) => {
             config::unset_config(&key)?;
         }
+        Some(cli::Command::ConfigListKeys) => {
+            config::list_keys(output)?;
+        }
+        Some(cli::Command::ConfigRestore) => {
+            config::restore_config()?;
+        }
         None => {
             // No subcommand: show help-like hint
             warn!("no command provided");
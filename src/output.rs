@@ -0,0 +1,126 @@
+//! Process-wide output settings: JSON rendering, Markdown tables and `--quiet`
+//!
+//! JSON is pretty-printed by default; the global `--compact` flag switches
+//! every command to single-line JSON for piping and smaller logs. With
+//! `--output markdown`, table renderers emit GitHub-flavored Markdown instead.
+
+use serde::Serialize;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static COMPACT: AtomicBool = AtomicBool::new(false);
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+static MARKDOWN: AtomicBool = AtomicBool::new(false);
+
+/// Render tables as Markdown for the rest of the process (`--output markdown`)
+pub fn enable_markdown() {
+    MARKDOWN.store(true, Ordering::Relaxed);
+}
+
+/// Whether tables should be written with [`write_markdown_table`]
+pub fn markdown() -> bool {
+    MARKDOWN.load(Ordering::Relaxed)
+}
+
+/// Write a GitHub-flavored Markdown table: a header row, a `---` separator row, then `rows`
+pub fn write_markdown_table(
+    out: &mut dyn Write,
+    headers: &[String],
+    rows: &[Vec<String>],
+) -> io::Result<()> {
+    let line = |cells: &[String]| {
+        let cells: Vec<String> = cells.iter().map(|c| markdown_cell(c)).collect();
+        format!("| {} |", cells.join(" | "))
+    };
+    writeln!(out, "{}", line(headers))?;
+    writeln!(out, "|{}", " --- |".repeat(headers.len()))?;
+    for row in rows {
+        writeln!(out, "{}", line(row))?;
+    }
+    Ok(())
+}
+
+/// Escape a table cell: pipes would end the cell and line breaks would end the row
+fn markdown_cell(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+/// Suppress advisory stderr notices for the rest of the process (global `--quiet`)
+pub fn enable_quiet() {
+    QUIET.store(true, Ordering::Relaxed);
+}
+
+/// Whether `--quiet` was given
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Render JSON on a single line for the rest of the process (global `--compact`)
+pub fn enable_compact() {
+    COMPACT.store(true, Ordering::Relaxed);
+}
+
+/// Serialize `value` as pretty or compact JSON, depending on `--compact`
+pub fn to_json<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<String> {
+    render(value, COMPACT.load(Ordering::Relaxed))
+}
+
+/// Print `value` as JSON to stdout, followed by a newline
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<()> {
+    println!("{}", to_json(value)?);
+    Ok(())
+}
+
+fn render<T: Serialize + ?Sized>(value: &T, compact: bool) -> serde_json::Result<String> {
+    if compact {
+        serde_json::to_string(value)
+    } else {
+        serde_json::to_string_pretty(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_markdown_table_escapes_cells() {
+        let mut out = Vec::new();
+        write_markdown_table(
+            &mut out,
+            &["PATH".to_string(), "NOTE".to_string()],
+            &[
+                vec!["/a|b".to_string(), "two\nlines".to_string()],
+                vec!["/c".to_string(), String::new()],
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "| PATH | NOTE |\n\
+             | --- | --- |\n\
+             | /a\\|b | two<br>lines |\n\
+             | /c |  |\n"
+        );
+    }
+
+    #[test]
+    fn test_render_pretty_and_compact() {
+        let value = serde_json::json!({"routes": [{"path": "/a"}]});
+        assert_eq!(
+            render(&value, true).unwrap(),
+            r#"{"routes":[{"path":"/a"}]}"#
+        );
+        let pretty = render(&value, false).unwrap();
+        assert!(pretty.contains('\n'));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&pretty).unwrap(),
+            value
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Interactive prompts and the global `--yes` flag
+//!
+//! Every command that asks before doing something destructive goes through
+//! [`confirm`], so `--yes` makes the whole CLI non-interactive. Commands that
+//! ask for values with [`ask`] only do so when [`interactive`] is true.
+
+use anyhow::Result;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::info;
+
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+/// Answer "yes" to every prompt for the rest of the process (global `--yes`)
+pub fn assume_yes() {
+    ASSUME_YES.store(true, Ordering::Relaxed);
+}
+
+/// Ask a yes/no question on stdin, defaulting to "no"
+///
+/// With `--yes` the question is still printed, but answered without reading input.
+pub fn confirm(question: &str) -> Result<bool> {
+    if ASSUME_YES.load(Ordering::Relaxed) {
+        println!("{} [y/N] y (auto-confirmed by --yes)", question);
+        info!(question, "prompt auto-confirmed by --yes");
+        return Ok(true);
+    }
+
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(is_yes(&answer))
+}
+
+/// Whether values may be asked for: stdin is a terminal and `--yes` was not given
+pub fn interactive() -> bool {
+    !ASSUME_YES.load(Ordering::Relaxed) && io::stdin().is_terminal()
+}
+
+/// Ask for a value, showing `default` in brackets; an empty answer (or end of input) keeps it
+///
+/// Takes the input and output explicitly so prompt sequences can be tested.
+pub fn ask(
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+    question: &str,
+    default: &str,
+) -> Result<String> {
+    write!(out, "{} [{}]: ", question, default)?;
+    out.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok(if answer.is_empty() {
+        default.to_string()
+    } else {
+        answer.to_string()
+    })
+}
+
+fn is_yes(answer: &str) -> bool {
+    matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_yes() {
+        assert!(is_yes("y\n"));
+        assert!(is_yes(" YES "));
+        assert!(!is_yes(""));
+        assert!(!is_yes("no"));
+        assert!(!is_yes("yep"));
+    }
+
+    #[test]
+    fn test_ask_keeps_default_on_empty_answer() {
+        let mut out = Vec::new();
+        let mut input: &[u8] = b"\n  10.0.0.5 \n";
+        assert_eq!(
+            ask(&mut input, &mut out, "IP address", "127.0.0.1").unwrap(),
+            "127.0.0.1"
+        );
+        assert_eq!(
+            ask(&mut input, &mut out, "IP address", "127.0.0.1").unwrap(),
+            "10.0.0.5"
+        );
+        // End of input also keeps the default
+        assert_eq!(ask(&mut input, &mut out, "Port", "8081").unwrap(), "8081");
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "IP address [127.0.0.1]: IP address [127.0.0.1]: Port [8081]: "
+        );
+    }
+}
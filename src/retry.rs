@@ -0,0 +1,300 @@
+//! Shared retry policy for outgoing HTTP requests (global `--retries` / `--retry-delay`)
+//!
+//! Management API calls, Runbeam Cloud API calls and JWKS fetches go through
+//! [`with_retries`] with the policy configured at startup from the flags and the
+//! `retries` / `retry-delay` config keys. Delays double after each retry, up to
+//! [`MAX_RETRY_DELAY`].
+//!
+//! Only requests that can safely be repeated are retried by default: GET and HEAD
+//! ([`policy_for`]). A POST may already have taken effect when its answer is lost
+//! or is a 5xx (a reload, a login session, a token delivery), so it is sent once
+//! unless the caller opts in with [`policy`] (as `harmony:update` does).
+//!
+//! Probes with their own deadline (`harmony:add --test`, `config:set --check`),
+//! polling loops (`login`, `harmony:info --watch`) and the gateway authorization
+//! request, which issues a new machine token each time, are not retried.
+
+use reqwest::{Method, StatusCode};
+use std::fmt;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+use tracing::warn;
+
+/// Retries after a transient failure when neither `--retries` nor `retries` is set
+pub const DEFAULT_RETRIES: u32 = 2;
+
+/// Pause before the first retry when neither `--retry-delay` nor `retry-delay` is set
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Longest pause between two attempts, however many retries are allowed
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Kind of transient failure a request can be retried after
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryOn {
+    /// The connection could not be established
+    Connect,
+    /// The server answered with HTTP 5xx
+    ServerError,
+    /// The request timed out
+    Timeout,
+}
+
+impl fmt::Display for RetryOn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RetryOn::Connect => "connection error",
+            RetryOn::ServerError => "server error",
+            RetryOn::Timeout => "timeout",
+        })
+    }
+}
+
+/// How often, how long apart, and after which failures a request is retried
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 disables retrying
+    pub max_retries: u32,
+    /// Pause before the first retry
+    pub base_delay: Duration,
+    /// Upper bound for the pause before any retry
+    pub max_delay: Duration,
+    /// Failures worth retrying; anything else is returned straight away
+    pub retry_on: Vec<RetryOn>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: DEFAULT_RETRIES,
+            base_delay: DEFAULT_RETRY_DELAY,
+            max_delay: MAX_RETRY_DELAY,
+            retry_on: vec![RetryOn::Connect, RetryOn::ServerError, RetryOn::Timeout],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Pause before retry number `retry` (counting from 1): `base_delay` doubled
+    /// for each earlier retry, capped at `max_delay`
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(retry.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+
+    /// Whether a failure of this kind is retried at all
+    pub fn retries(&self, failure: RetryOn) -> bool {
+        self.retry_on.contains(&failure)
+    }
+}
+
+static POLICY: OnceLock<RetryPolicy> = OnceLock::new();
+
+/// Select the policy used by every [`with_retries`] call in this process
+pub fn configure(policy: RetryPolicy) {
+    let _ = POLICY.set(policy);
+}
+
+/// The configured policy, or the default one
+pub fn policy() -> RetryPolicy {
+    POLICY.get().cloned().unwrap_or_default()
+}
+
+/// The policy for a request with `method`: the configured one for GET and HEAD,
+/// no retries for anything else
+pub fn policy_for(method: &Method) -> RetryPolicy {
+    let policy = policy();
+    if method == Method::GET || method == Method::HEAD {
+        policy
+    } else {
+        RetryPolicy {
+            max_retries: 0,
+            ..policy
+        }
+    }
+}
+
+/// The outcome of one attempt, as far as retrying is concerned
+pub trait Retryable {
+    /// The transient failure this outcome represents, if any
+    fn failure(&self) -> Option<RetryOn>;
+}
+
+impl Retryable for Result<reqwest::blocking::Response, reqwest::Error> {
+    fn failure(&self) -> Option<RetryOn> {
+        match self {
+            Ok(resp) => status_failure(resp.status()),
+            Err(e) => error_failure(e),
+        }
+    }
+}
+
+impl Retryable for Result<reqwest::Response, reqwest::Error> {
+    fn failure(&self) -> Option<RetryOn> {
+        match self {
+            Ok(resp) => status_failure(resp.status()),
+            Err(e) => error_failure(e),
+        }
+    }
+}
+
+fn status_failure(status: StatusCode) -> Option<RetryOn> {
+    status.is_server_error().then_some(RetryOn::ServerError)
+}
+
+fn error_failure(err: &reqwest::Error) -> Option<RetryOn> {
+    if err.is_timeout() {
+        Some(RetryOn::Timeout)
+    } else if err.is_connect() {
+        Some(RetryOn::Connect)
+    } else {
+        None
+    }
+}
+
+/// Run `attempt` until it succeeds, fails in a way `policy` does not retry, or
+/// runs out of retries, sleeping between attempts
+///
+/// The last outcome is returned as-is, so an exhausted 5xx is still a response.
+/// `what` names the request in the retry warnings.
+pub fn with_retries<R: Retryable>(
+    policy: &RetryPolicy,
+    what: &str,
+    mut attempt: impl FnMut() -> R,
+) -> R {
+    let mut retry = 0;
+    loop {
+        let outcome = attempt();
+        match outcome.failure() {
+            Some(failure) if retry < policy.max_retries && policy.retries(failure) => {
+                retry += 1;
+                let delay = policy.delay(retry);
+                warn!(
+                    "{} failed ({}); retrying in {} ({}/{})",
+                    what,
+                    failure,
+                    crate::cli::format_duration(delay),
+                    retry,
+                    policy.max_retries
+                );
+                thread::sleep(delay);
+            }
+            _ => return outcome,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl Retryable for Result<(), RetryOn> {
+        fn failure(&self) -> Option<RetryOn> {
+            self.err()
+        }
+    }
+
+    fn instant(max_retries: u32, retry_on: Vec<RetryOn>) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+            retry_on,
+        }
+    }
+
+    #[test]
+    fn test_only_idempotent_methods_are_retried() {
+        assert_eq!(policy_for(&Method::GET), policy());
+        assert_eq!(policy_for(&Method::HEAD), policy());
+        for method in [Method::POST, Method::PUT, Method::PATCH, Method::DELETE] {
+            assert_eq!(policy_for(&method).max_retries, 0, "{}", method);
+        }
+    }
+
+    #[test]
+    fn test_delay_doubles_up_to_the_cap() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(3),
+            ..RetryPolicy::default()
+        };
+        let schedule: Vec<Duration> = (1..=5).map(|retry| policy.delay(retry)).collect();
+        assert_eq!(
+            schedule,
+            [
+                Duration::from_millis(500),
+                Duration::from_secs(1),
+                Duration::from_secs(2),
+                Duration::from_secs(3),
+                Duration::from_secs(3),
+            ]
+        );
+        assert_eq!(policy.delay(100), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_retry_predicates() {
+        let policy = RetryPolicy {
+            retry_on: vec![RetryOn::Connect, RetryOn::Timeout],
+            ..RetryPolicy::default()
+        };
+        assert!(policy.retries(RetryOn::Connect));
+        assert!(policy.retries(RetryOn::Timeout));
+        assert!(!policy.retries(RetryOn::ServerError));
+
+        assert_eq!(
+            status_failure(StatusCode::BAD_GATEWAY),
+            Some(RetryOn::ServerError)
+        );
+        assert_eq!(status_failure(StatusCode::NOT_FOUND), None);
+        assert_eq!(status_failure(StatusCode::OK), None);
+    }
+
+    #[test]
+    fn test_with_retries_stops_after_max_retries() {
+        let mut calls = 0;
+        let outcome = with_retries(&instant(2, vec![RetryOn::Connect]), "test", || {
+            calls += 1;
+            Err(RetryOn::Connect)
+        });
+        assert_eq!(outcome, Err(RetryOn::Connect));
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn test_with_retries_returns_first_success() {
+        let mut calls = 0;
+        let outcome = with_retries(&instant(5, vec![RetryOn::Timeout]), "test", || {
+            calls += 1;
+            if calls < 3 {
+                Err(RetryOn::Timeout)
+            } else {
+                Ok(())
+            }
+        });
+        assert_eq!(outcome, Ok(()));
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn test_with_retries_skips_unlisted_failures() {
+        let mut calls = 0;
+        let outcome = with_retries(&instant(5, vec![RetryOn::Connect]), "test", || {
+            calls += 1;
+            Err(RetryOn::ServerError)
+        });
+        assert_eq!(outcome, Err(RetryOn::ServerError));
+        assert_eq!(calls, 1);
+
+        let mut calls = 0;
+        let _ = with_retries(&instant(0, vec![RetryOn::Connect]), "test", || {
+            calls += 1;
+            Err(RetryOn::Connect)
+        });
+        assert_eq!(calls, 1);
+    }
+}
@@ -2,14 +2,27 @@ use anyhow::{Context, Result};
 use directories::BaseDirs;
 use runbeam_sdk::UserInfo;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::Once;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use tracing::{debug, info, warn};
 
+/// Set by the global `--no-migrate` flag; see [`disable_auth_migration`]
+static AUTH_MIGRATION_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Orphaned `*.tmp` files are swept from the data dir once per process
+static TMP_CLEANUP: Once = Once::new();
+
+/// Temp files younger than this may belong to a write in progress and are kept
+const STALE_TMP_AGE: Duration = Duration::from_secs(10);
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct HarmonyInstance {
-    /// Stable short identifier
+    /// Stable short identifier, assigned once and kept across label changes
     #[serde(default)]
     pub id: String,
     pub ip: String,
@@ -20,6 +33,21 @@ pub struct HarmonyInstance {
     /// Runbeam Cloud gateway ULID (set after authorization)
     #[serde(default)]
     pub gateway_id: Option<String>,
+    /// Extra request headers sent with every management API call
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<(String, String)>,
+    /// Free-form tags for grouping instances (e.g. "prod", "eu")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// URL scheme of the management API ("http" or "https")
+    #[serde(default = "default_scheme")]
+    pub scheme: String,
+    /// Harmony version from the last successful `/info`, used to gate features offline
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Expiry of the machine token last delivered by `harmony:authorize` (seconds since epoch)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub machine_token_expires_at: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +66,35 @@ fn default_path_prefix() -> String {
     "admin".to_string()
 }
 
+fn default_scheme() -> String {
+    "http".to_string()
+}
+
+/// The optional fields as a registry entry without them would load: no ID, the
+/// default path prefix and scheme, no gateway, headers, tags or cached state.
+/// The address and label are left empty for the caller to fill in.
+impl Default for HarmonyInstance {
+    fn default() -> Self {
+        HarmonyInstance {
+            id: String::new(),
+            ip: String::new(),
+            port: 0,
+            label: String::new(),
+            path_prefix: default_path_prefix(),
+            gateway_id: None,
+            headers: Vec::new(),
+            tags: Vec::new(),
+            scheme: default_scheme(),
+            version: None,
+            machine_token_expires_at: None,
+        }
+    }
+}
+
+/// Derive an ID from the address and label
+///
+/// Only used to backfill legacy entries that were saved without an ID; new
+/// instances get a random ID from [`generate_id`].
 fn derive_id(ip: &str, port: u16, label: &str) -> String {
     use sha2::{Digest, Sha256};
     let input = format!("{}:{}:{}", ip, port, label);
@@ -51,7 +108,18 @@ fn derive_id(ip: &str, port: u16, label: &str) -> String {
     )
 }
 
-fn base_dir() -> Result<PathBuf> {
+/// Generate a random 8-hex-character ID not already used in `list`
+fn generate_id(list: &[HarmonyInstance]) -> String {
+    loop {
+        let id = format!("{:08x}", rand::random::<u32>());
+        if !list.iter().any(|i| i.id == id) {
+            return id;
+        }
+    }
+}
+
+/// Location of the runbeam data directory, without creating it
+pub fn base_dir() -> Result<PathBuf> {
     let bd = BaseDirs::new().context("could not determine base directories")?;
     #[cfg(windows)]
     {
@@ -67,31 +135,241 @@ fn base_dir() -> Result<PathBuf> {
 pub fn data_dir() -> Result<PathBuf> {
     let dir = base_dir()?;
     if !dir.exists() {
-        fs::create_dir_all(&dir)
+        let mut builder = fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
+        }
+        builder
+            .create(&dir)
             .with_context(|| format!("creating data dir: {}", dir.display()))?;
     }
+    TMP_CLEANUP.call_once(|| {
+        remove_stale_tmp_files(&dir, STALE_TMP_AGE);
+        #[cfg(unix)]
+        restrict_data_dir_permissions(&dir);
+    });
     Ok(dir)
 }
 
-fn harmony_file_path() -> Result<PathBuf> {
+/// Files in the data directory that may hold a token or settings and must be owner-only
+#[cfg(unix)]
+const PRIVATE_FILES: &[&str] = &["auth.json", "config.json", "config.json.bak"];
+
+/// Restrict the data directory to 0700 and its private files to 0600, warning about each
+/// one that other users could read
+///
+/// Older versions created them with the default umask (typically 0755 / 0644).
+#[cfg(unix)]
+fn restrict_data_dir_permissions(dir: &Path) {
+    let targets = std::iter::once((dir.to_path_buf(), 0o700))
+        .chain(PRIVATE_FILES.iter().map(|name| (dir.join(name), 0o600)));
+    for (path, mode) in targets {
+        if path.exists() {
+            restrict_permissions(&path, mode);
+        }
+    }
+}
+
+/// Set `path` to `mode` if group or other users have any access to it, with a warning
+#[cfg(unix)]
+fn restrict_permissions(path: &Path, mode: u32) {
+    match tighten_mode(path, mode) {
+        Ok(Some(old)) => warn!(
+            "{} was accessible by other users (mode {:o}); restricted it to {:o}",
+            path.display(),
+            old,
+            mode
+        ),
+        Ok(None) => {}
+        Err(e) => warn!(
+            "{} may be readable by other users and its permissions could not be restricted: {}",
+            path.display(),
+            e
+        ),
+    }
+}
+
+/// Apply `mode` when the current mode grants group/other access; returns the old mode if changed
+#[cfg(unix)]
+fn tighten_mode(path: &Path, mode: u32) -> std::io::Result<Option<u32>> {
+    use std::os::unix::fs::PermissionsExt;
+    let old = fs::metadata(path)?.permissions().mode() & 0o777;
+    if old & 0o077 == 0 {
+        return Ok(None);
+    }
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    Ok(Some(old))
+}
+
+/// Remove `*.tmp` files in `dir` older than `max_age`
+///
+/// These are left behind when the process is killed between writing a temp file
+/// and renaming it into place. Returns how many files were removed.
+fn remove_stale_tmp_files(dir: &Path, max_age: Duration) -> usize {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "tmp") {
+            continue;
+        }
+        let stale = entry
+            .metadata()
+            .ok()
+            .filter(|m| m.is_file())
+            .and_then(|m| m.modified().ok())
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age >= max_age);
+        if stale && fs::remove_file(&path).is_ok() {
+            debug!("Removed stale temp file {}", path.display());
+            removed += 1;
+        }
+    }
+    removed
+}
+
+/// Expand a user-supplied filesystem path
+///
+/// A leading `~` (alone or followed by a separator) is replaced with the home
+/// directory, and `$VAR` / `${VAR}` references are replaced with the value of the
+/// environment variable. Unset variables are left untouched so the resulting path
+/// stays recognisable in error messages.
+pub fn expand_path(input: &str) -> PathBuf {
+    let expanded = expand_env_vars(input);
+
+    if (expanded == "~" || expanded.starts_with("~/") || expanded.starts_with("~\\"))
+        && let Some(bd) = BaseDirs::new()
+    {
+        let rest = expanded[1..].trim_start_matches(['/', '\\']);
+        return bd.home_dir().join(rest);
+    }
+
+    PathBuf::from(expanded)
+}
+
+fn expand_env_vars(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+
+        let (name, consumed) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            }
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], end)
+        };
+
+        match std::env::var(name) {
+            Ok(value) if !name.is_empty() => out.push_str(&value),
+            _ => out.push_str(&rest[pos..pos + 1 + consumed]),
+        }
+        rest = &after[consumed..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Path to the instance registry, `harmony.json`
+pub fn harmony_file_path() -> Result<PathBuf> {
     Ok(data_dir()?.join("harmony.json"))
 }
 
-fn auth_file_path() -> Result<PathBuf> {
+/// Path to the legacy plaintext auth file (pre secure storage)
+pub fn auth_file_path() -> Result<PathBuf> {
     Ok(data_dir()?.join("auth.json"))
 }
 
+/// Marker written next to `auth.json` when it holds a token because secure storage failed
+///
+/// While it exists, `load_auth` does not try to migrate the file into secure storage.
+fn auth_fallback_marker_path() -> Result<PathBuf> {
+    Ok(data_dir()?.join("auth.fallback"))
+}
+
+/// Current `harmony.json` format version
+///
+/// Version 1 was a bare JSON array of instances; version 2 wraps it in a
+/// `{ "version": N, "instances": [...] }` envelope. When the layout changes,
+/// bump this and append a step to [`REGISTRY_MIGRATIONS`].
+pub const REGISTRY_VERSION: u64 = 2;
+
+/// Upgrade steps: `REGISTRY_MIGRATIONS[n - 1]` turns a version `n` document into version `n + 1`
+const REGISTRY_MIGRATIONS: &[fn(serde_json::Value) -> Result<serde_json::Value>] =
+    &[migrate_registry_v1_to_v2];
+
+/// On-disk layout of `harmony.json`
+#[derive(Serialize)]
+struct RegistryFile<'a> {
+    version: u64,
+    instances: &'a [HarmonyInstance],
+}
+
+/// Version of a registry document: a bare array is version 1, anything else carries its own
+fn registry_version(doc: &serde_json::Value) -> Result<u64> {
+    if doc.is_array() {
+        return Ok(1);
+    }
+    doc.get("version")
+        .and_then(serde_json::Value::as_u64)
+        .filter(|v| *v > 0)
+        .ok_or_else(|| anyhow::anyhow!("expected an instance list or a versioned registry object"))
+}
+
+/// v1 -> v2: wrap the bare array in the versioned envelope
+fn migrate_registry_v1_to_v2(doc: serde_json::Value) -> Result<serde_json::Value> {
+    Ok(serde_json::json!({ "version": 2, "instances": doc }))
+}
+
+/// Upgrade a parsed registry document to [`REGISTRY_VERSION`] and extract its instances
+///
+/// Returns whether any migration ran, so the caller can persist the new format.
+/// Registries written by a newer CLI are rejected rather than guessed at.
+fn migrate_registry(mut doc: serde_json::Value) -> Result<(Vec<HarmonyInstance>, bool)> {
+    let from = registry_version(&doc)?;
+    if from > REGISTRY_VERSION {
+        anyhow::bail!(
+            "registry format version {} was written by a newer runbeam CLI (this one supports up to {})",
+            from,
+            REGISTRY_VERSION
+        );
+    }
+    for version in from..REGISTRY_VERSION {
+        debug!(from = version, to = version + 1, "migrating harmony.json");
+        doc = REGISTRY_MIGRATIONS[(version - 1) as usize](doc)?;
+    }
+    let instances = doc
+        .get_mut("instances")
+        .map(serde_json::Value::take)
+        .ok_or_else(|| anyhow::anyhow!("registry has no \"instances\" list"))?;
+    Ok((serde_json::from_value(instances)?, from < REGISTRY_VERSION))
+}
+
 pub fn load_harmony_instances() -> Result<Vec<HarmonyInstance>> {
     let path = harmony_file_path()?;
     if !path.exists() {
         return Ok(Vec::new());
     }
     let data = fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
-    let mut list: Vec<HarmonyInstance> =
+    let doc: serde_json::Value =
         serde_json::from_str(&data).with_context(|| format!("parsing {}", path.display()))?;
+    let (mut list, mut changed) =
+        migrate_registry(doc).with_context(|| format!("parsing {}", path.display()))?;
 
     // Backfill missing IDs for older entries and persist once
-    let mut changed = false;
     for inst in &mut list {
         if inst.id.is_empty() {
             inst.id = derive_id(&inst.ip, inst.port, &inst.label);
@@ -105,22 +383,153 @@ pub fn load_harmony_instances() -> Result<Vec<HarmonyInstance>> {
     Ok(list)
 }
 
+/// Environment variable naming a read-only inventory of instances
+pub const INVENTORY_ENV: &str = "RUNBEAM_INVENTORY";
+
+/// Read the instances listed in the `RUNBEAM_INVENTORY` file, if the variable is set
+///
+/// The file is YAML, or JSON when its name ends in `.json`, holding either a list
+/// of instances or an object with an `instances` list (the `harmony.json` layout).
+/// Entries use the registry's field names; an entry without an `id` gets the one
+/// `harmony:add` would derive. The file is never written.
+pub fn load_inventory() -> Result<Vec<HarmonyInstance>> {
+    let Some(raw) = std::env::var_os(INVENTORY_ENV).filter(|v| !v.is_empty()) else {
+        return Ok(Vec::new());
+    };
+    let path = expand_path(&raw.to_string_lossy());
+    let data = fs::read_to_string(&path)
+        .with_context(|| format!("reading {} ({})", path.display(), INVENTORY_ENV))?;
+    parse_inventory(&data, path.extension().is_some_and(|ext| ext == "json"))
+        .with_context(|| format!("parsing {} ({})", path.display(), INVENTORY_ENV))
+}
+
+/// Parse an inventory document; see [`load_inventory`] for the accepted layouts
+fn parse_inventory(data: &str, json: bool) -> Result<Vec<HarmonyInstance>> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Inventory {
+        List(Vec<HarmonyInstance>),
+        Registry { instances: Vec<HarmonyInstance> },
+    }
+
+    let inventory: Inventory = if json {
+        serde_json::from_str(data)?
+    } else {
+        serde_norway::from_str(data)?
+    };
+    let (Inventory::List(mut list)
+    | Inventory::Registry {
+        instances: mut list,
+    }) = inventory;
+    for inst in &mut list {
+        inst.path_prefix = normalize_path_prefix(&inst.path_prefix)
+            .with_context(|| format!("instance '{}'", inst.label))?;
+        inst.scheme =
+            normalize_scheme(&inst.scheme).with_context(|| format!("instance '{}'", inst.label))?;
+        if inst.id.is_empty() {
+            inst.id = derive_id(&inst.ip, inst.port, &inst.label);
+        }
+    }
+    Ok(list)
+}
+
+/// Local registry entries followed by the inventory entries they do not shadow
+///
+/// An inventory entry is dropped when a local entry has the same ID or label, so
+/// the local registry always wins.
+pub fn merge_inventory(
+    local: Vec<HarmonyInstance>,
+    inventory: Vec<HarmonyInstance>,
+) -> Vec<HarmonyInstance> {
+    let mut merged = local;
+    for inst in inventory {
+        if merged
+            .iter()
+            .any(|l| l.id == inst.id || l.label == inst.label)
+        {
+            debug!(
+                id = %inst.id,
+                label = %inst.label,
+                "inventory entry shadowed by the local registry"
+            );
+            continue;
+        }
+        merged.push(inst);
+    }
+    merged
+}
+
 pub fn save_harmony_instances(list: &[HarmonyInstance]) -> Result<()> {
     let path = harmony_file_path()?;
-    let tmp_path = tmp_path_for(&path);
-    let json = serde_json::to_string_pretty(list)?;
-    // Write atomically: write temp, then rename
+    let json = serde_json::to_string_pretty(&RegistryFile {
+        version: REGISTRY_VERSION,
+        instances: list,
+    })?;
+    write_atomic(&path, json.as_bytes())
+}
+
+/// Write `contents` to `path` atomically: write a sibling `.tmp` file, then rename
+///
+/// The temp file is removed if any step fails, so errors never leave it behind.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    write_atomic_with(path, contents, false)
+}
+
+/// Like [`write_atomic`], but the file is only readable by the current user (0600 on Unix)
+pub fn write_atomic_private(path: &Path, contents: &[u8]) -> Result<()> {
+    write_atomic_with(path, contents, true)
+}
+
+fn write_atomic_with(path: &Path, contents: &[u8], private: bool) -> Result<()> {
+    let tmp_path = tmp_path_for(path);
+    let guard = TmpFileGuard::new(tmp_path.clone());
     {
-        let mut f = fs::File::create(&tmp_path)
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        if private {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        #[cfg(not(unix))]
+        let _ = private;
+        let mut f = options
+            .open(&tmp_path)
             .with_context(|| format!("creating {}", tmp_path.display()))?;
-        f.write_all(json.as_bytes())?;
+        f.write_all(contents)
+            .with_context(|| format!("writing {}", tmp_path.display()))?;
         f.sync_all().ok();
     }
-    fs::rename(&tmp_path, &path)
+    fs::rename(&tmp_path, path)
         .with_context(|| format!("rename {} -> {}", tmp_path.display(), path.display()))?;
+    guard.disarm();
     Ok(())
 }
 
+/// Deletes a temp file on drop unless [`TmpFileGuard::disarm`] was called
+struct TmpFileGuard {
+    path: PathBuf,
+    armed: bool,
+}
+
+impl TmpFileGuard {
+    fn new(path: PathBuf) -> Self {
+        Self { path, armed: true }
+    }
+
+    fn disarm(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for TmpFileGuard {
+    fn drop(&mut self) {
+        if self.armed {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
 fn tmp_path_for(path: &Path) -> PathBuf {
     let mut p = path.to_path_buf();
     let fname = path
@@ -132,39 +541,175 @@ fn tmp_path_for(path: &Path) -> PathBuf {
     p
 }
 
-pub fn add_harmony_instance(new_inst: HarmonyInstance) -> Result<()> {
+/// Normalize a management API path prefix before storing it
+///
+/// Surrounding whitespace and slashes are stripped, so `/admin/` becomes `admin`.
+/// An empty result means the API is served at the root. Values that contain a
+/// scheme (`://`) or whitespace are rejected.
+pub fn normalize_path_prefix(raw: &str) -> Result<String> {
+    let trimmed = raw.trim();
+    if trimmed.contains("://") {
+        anyhow::bail!(
+            "invalid path prefix '{}': expected a path such as 'admin', not a URL",
+            raw
+        );
+    }
+    if trimmed.chars().any(char::is_whitespace) {
+        anyhow::bail!("invalid path prefix '{}': must not contain spaces", raw);
+    }
+    Ok(trimmed.trim_matches('/').to_string())
+}
+
+/// Normalize a URL scheme before storing it: `http` or `https`, case-insensitive
+pub fn normalize_scheme(raw: &str) -> Result<String> {
+    let scheme = raw.trim().to_ascii_lowercase();
+    match scheme.as_str() {
+        "http" | "https" => Ok(scheme),
+        _ => anyhow::bail!("invalid scheme '{}': expected 'http' or 'https'", raw),
+    }
+}
+
+/// The entry [`add_harmony_instance`] would merge an instance with this label and
+/// address into: the one with the same label, else the one at the same address
+pub fn merge_target<'a>(
+    list: &'a [HarmonyInstance],
+    label: &str,
+    ip: &str,
+    port: u16,
+) -> Option<&'a HarmonyInstance> {
+    list.iter()
+        .find(|i| i.label == label)
+        .or_else(|| list.iter().find(|i| i.ip == ip && i.port == port))
+}
+
+pub fn add_harmony_instance(mut new_inst: HarmonyInstance) -> Result<()> {
+    new_inst.path_prefix = normalize_path_prefix(&new_inst.path_prefix)?;
     let mut list = load_harmony_instances()?;
+    upsert_instance(&mut list, new_inst);
+    save_harmony_instances(&list)
+}
 
-    // De-duplicate by label first, else by ip:port
+/// Merge `new_inst` into `list`: update the entry with the same label, else the one
+/// with the same ip:port, else append it with a fresh ID
+fn upsert_instance(list: &mut Vec<HarmonyInstance>, new_inst: HarmonyInstance) {
     if let Some(existing) = list.iter_mut().find(|i| i.label == new_inst.label) {
+        // A new address may be a different Harmony: what was cached about the old one
+        // is no longer trusted
+        if existing.ip != new_inst.ip || existing.port != new_inst.port {
+            existing.version = None;
+            existing.machine_token_expires_at = None;
+        }
         // Update fields but preserve ID and gateway_id
         existing.ip = new_inst.ip;
         existing.port = new_inst.port;
         existing.label = new_inst.label;
         existing.path_prefix = new_inst.path_prefix;
+        existing.scheme = new_inst.scheme;
         // Only update gateway_id if new instance has one
         if new_inst.gateway_id.is_some() {
             existing.gateway_id = new_inst.gateway_id;
         }
+        if !new_inst.headers.is_empty() {
+            existing.headers = new_inst.headers;
+        }
+        if !new_inst.tags.is_empty() {
+            existing.tags = new_inst.tags;
+        }
     } else if let Some(existing) = list
         .iter_mut()
         .find(|i| i.ip == new_inst.ip && i.port == new_inst.port)
     {
         existing.label = new_inst.label;
         existing.path_prefix = new_inst.path_prefix;
+        existing.scheme = new_inst.scheme;
         // Only update gateway_id if new instance has one
         if new_inst.gateway_id.is_some() {
             existing.gateway_id = new_inst.gateway_id;
         }
+        if !new_inst.headers.is_empty() {
+            existing.headers = new_inst.headers;
+        }
+        if !new_inst.tags.is_empty() {
+            existing.tags = new_inst.tags;
+        }
     } else {
         let mut to_add = new_inst;
         if to_add.id.is_empty() {
-            to_add.id = derive_id(&to_add.ip, to_add.port, &to_add.label);
+            to_add.id = generate_id(list);
         }
         list.push(to_add);
     }
+}
 
-    save_harmony_instances(&list)
+/// Change the label of an instance, keeping its ID and all other fields
+///
+/// Returns `false` if no instance has `label`. Fails if `new_label` is already taken.
+pub fn rename_harmony_instance(label: &str, new_label: &str) -> Result<bool> {
+    let mut list = load_harmony_instances()?;
+    if label != new_label && list.iter().any(|i| i.label == new_label) {
+        anyhow::bail!("an instance with label '{}' already exists", new_label);
+    }
+    let Some(inst) = list.iter_mut().find(|i| i.label == label) else {
+        return Ok(false);
+    };
+    inst.label = new_label.to_string();
+    save_harmony_instances(&list)?;
+    Ok(true)
+}
+
+/// Change the management API path prefix of the instance with the given ID
+///
+/// The prefix is normalized first. Returns the previous prefix, or `None` if no
+/// instance has that ID.
+pub fn set_harmony_instance_prefix(id: &str, prefix: &str) -> Result<Option<String>> {
+    let prefix = normalize_path_prefix(prefix)?;
+    let mut list = load_harmony_instances()?;
+    let Some(inst) = list.iter_mut().find(|i| i.id == id) else {
+        return Ok(None);
+    };
+    let old = std::mem::replace(&mut inst.path_prefix, prefix);
+    save_harmony_instances(&list)?;
+    Ok(Some(old))
+}
+
+/// Change the URL scheme of the instance with the given ID
+///
+/// Returns the previous scheme, or `None` if no instance has that ID.
+pub fn set_harmony_instance_scheme(id: &str, scheme: &str) -> Result<Option<String>> {
+    let scheme = normalize_scheme(scheme)?;
+    let mut list = load_harmony_instances()?;
+    let Some(inst) = list.iter_mut().find(|i| i.id == id) else {
+        return Ok(None);
+    };
+    let old = std::mem::replace(&mut inst.scheme, scheme);
+    save_harmony_instances(&list)?;
+    Ok(Some(old))
+}
+
+/// Record the Harmony version last reported by the instance with the given ID
+///
+/// Returns `false` if no instance has that ID.
+pub fn set_harmony_instance_version(id: &str, version: &str) -> Result<bool> {
+    let mut list = load_harmony_instances()?;
+    let Some(inst) = list.iter_mut().find(|i| i.id == id) else {
+        return Ok(false);
+    };
+    inst.version = Some(version.to_string());
+    save_harmony_instances(&list)?;
+    Ok(true)
+}
+
+/// Record the expiry of the machine token delivered to the instance with the given ID
+///
+/// Returns `false` if no instance has that ID.
+pub fn set_machine_token_expiry(id: &str, expires_at: i64) -> Result<bool> {
+    let mut list = load_harmony_instances()?;
+    let Some(inst) = list.iter_mut().find(|i| i.id == id) else {
+        return Ok(false);
+    };
+    inst.machine_token_expires_at = Some(expires_at);
+    save_harmony_instances(&list)?;
+    Ok(true)
 }
 
 pub fn remove_harmony_instance_by_label(label: &str) -> Result<bool> {
@@ -189,43 +734,249 @@ pub fn remove_harmony_instance_by_addr(ip: &str, port: u16) -> Result<bool> {
     Ok(changed)
 }
 
-pub fn remove_harmony_instance_by_id(id: &str) -> Result<bool> {
+/// Find the instance with this ID, or else the only one whose ID starts with it
+///
+/// As with git's short hashes, an exact match wins over longer IDs sharing the
+/// prefix, and a prefix shared by several IDs is an error listing them.
+pub fn find_by_id<'a>(
+    list: &'a [HarmonyInstance],
+    id: &str,
+) -> Result<Option<&'a HarmonyInstance>> {
+    if let Some(inst) = list.iter().find(|i| i.id == id) {
+        return Ok(Some(inst));
+    }
+    if id.is_empty() {
+        return Ok(None);
+    }
+    let candidates: Vec<&HarmonyInstance> = list.iter().filter(|i| i.id.starts_with(id)).collect();
+    match candidates.as_slice() {
+        [] => Ok(None),
+        [inst] => Ok(Some(inst)),
+        _ => anyhow::bail!(
+            "id prefix '{}' is ambiguous; it matches {}",
+            id,
+            candidates
+                .iter()
+                .map(|i| format!("{} ({})", i.id, i.label))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Remove the instance with this ID or unique ID prefix (see [`find_by_id`])
+///
+/// Returns the removed instance, or `None` if nothing matched.
+pub fn remove_harmony_instance_by_id(id: &str) -> Result<Option<HarmonyInstance>> {
     let mut list = load_harmony_instances()?;
-    let before = list.len();
-    list.retain(|i| i.id != id);
-    let changed = list.len() != before;
+    let Some(removed) = find_by_id(&list, id)?.cloned() else {
+        return Ok(None);
+    };
+    list.retain(|i| i.id != removed.id);
+    save_harmony_instances(&list)?;
+    Ok(Some(removed))
+}
+
+/// Set (or replace) a stored request header on the instance with the given ID
+///
+/// Header names are matched case-insensitively. Returns `false` if no instance has that ID.
+pub fn set_harmony_instance_header(id: &str, name: &str, value: &str) -> Result<bool> {
+    let mut list = load_harmony_instances()?;
+    let Some(inst) = list.iter_mut().find(|i| i.id == id) else {
+        return Ok(false);
+    };
+    match inst
+        .headers
+        .iter_mut()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+    {
+        Some(existing) => *existing = (name.to_string(), value.to_string()),
+        None => inst.headers.push((name.to_string(), value.to_string())),
+    }
+    save_harmony_instances(&list)?;
+    Ok(true)
+}
+
+/// Remove a stored request header from the instance with the given ID
+///
+/// Returns `false` if the instance or the header was not found.
+pub fn unset_harmony_instance_header(id: &str, name: &str) -> Result<bool> {
+    let mut list = load_harmony_instances()?;
+    let Some(inst) = list.iter_mut().find(|i| i.id == id) else {
+        return Ok(false);
+    };
+    let before = inst.headers.len();
+    inst.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+    let changed = inst.headers.len() != before;
     if changed {
         save_harmony_instances(&list)?;
     }
     Ok(changed)
 }
 
+/// Add tags to the instance with the given ID (tags already present are skipped)
+///
+/// Returns `false` if no instance has that ID.
+pub fn tag_harmony_instance(id: &str, tags: &[String]) -> Result<bool> {
+    let mut list = load_harmony_instances()?;
+    let Some(inst) = list.iter_mut().find(|i| i.id == id) else {
+        return Ok(false);
+    };
+    for tag in tags {
+        if !inst.tags.contains(tag) {
+            inst.tags.push(tag.clone());
+        }
+    }
+    save_harmony_instances(&list)?;
+    Ok(true)
+}
+
+/// Remove tags from the instance with the given ID
+///
+/// Returns `false` if no instance has that ID.
+pub fn untag_harmony_instance(id: &str, tags: &[String]) -> Result<bool> {
+    let mut list = load_harmony_instances()?;
+    let Some(inst) = list.iter_mut().find(|i| i.id == id) else {
+        return Ok(false);
+    };
+    inst.tags.retain(|t| !tags.contains(t));
+    save_harmony_instances(&list)?;
+    Ok(true)
+}
+
+// ============================================================================
+// Instance Groups
+// ============================================================================
+
+/// Named groups of instances, mapping each group to the IDs of its members
+///
+/// Membership is stored by ID, so relabelling an instance does not break its groups.
+pub type Groups = BTreeMap<String, Vec<String>>;
+
+/// Longest accepted group name
+const MAX_GROUP_NAME_LEN: usize = 64;
+
+fn groups_file_path() -> Result<PathBuf> {
+    Ok(data_dir()?.join("groups.json"))
+}
+
+pub fn load_groups() -> Result<Groups> {
+    let path = groups_file_path()?;
+    if !path.exists() {
+        return Ok(Groups::new());
+    }
+    let data = fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+    serde_json::from_str(&data).with_context(|| format!("parsing {}", path.display()))
+}
+
+pub fn save_groups(groups: &Groups) -> Result<()> {
+    let path = groups_file_path()?;
+    let json = serde_json::to_string_pretty(groups)?;
+    write_atomic(&path, json.as_bytes())
+}
+
+/// Check a group name: 1-64 letters, digits, `-`, `_` or `.`, not starting with `-`
+pub fn validate_group_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > MAX_GROUP_NAME_LEN {
+        anyhow::bail!(
+            "invalid group name '{}': must be 1 to {} characters",
+            name,
+            MAX_GROUP_NAME_LEN
+        );
+    }
+    if name.starts_with('-') {
+        anyhow::bail!("invalid group name '{}': must not start with '-'", name);
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+    {
+        anyhow::bail!(
+            "invalid group name '{}': '{}' is not allowed (use letters, digits, '-', '_' or '.')",
+            name,
+            c
+        );
+    }
+    Ok(())
+}
+
 // ============================================================================
 // CLI Authentication Storage
 // ============================================================================
 
+/// Disable migration of the legacy plaintext `auth.json` for this process
+///
+/// The legacy file is still read, but it is neither copied into secure storage nor deleted.
+/// Equivalent to setting `RUNBEAM_NO_AUTH_MIGRATION=1`.
+pub fn disable_auth_migration() {
+    AUTH_MIGRATION_DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether `--no-migrate` or `RUNBEAM_NO_AUTH_MIGRATION` turned off legacy auth migration
+pub fn auth_migration_disabled() -> bool {
+    AUTH_MIGRATION_DISABLED.load(Ordering::Relaxed)
+        || std::env::var("RUNBEAM_NO_AUTH_MIGRATION")
+            .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false)
+}
+
 pub fn load_auth() -> Result<Option<CliAuth>> {
     // Try secure storage first (via SDK)
     let runtime = tokio::runtime::Runtime::new()?;
 
-    if let Ok(Some(user_token)) = runtime.block_on(
-        runbeam_sdk::load_token::<runbeam_sdk::UserToken>("runbeam-cli", "user_auth"),
-    ) {
-        return Ok(Some(CliAuth {
+    let secure = match runtime.block_on(runbeam_sdk::load_token::<runbeam_sdk::UserToken>(
+        "runbeam-cli",
+        "user_auth",
+    )) {
+        Ok(Some(user_token)) => Some(CliAuth {
             token: user_token.token,
             expires_at: user_token.expires_at,
             user: user_token.user,
-        }));
+        }),
+        _ => None,
+    };
+    let fallback = auth_fallback_marker_path()?.exists();
+    if secure.is_some() && !fallback {
+        return Ok(secure);
     }
 
     // Fall back to legacy plaintext file for migration
     let legacy_path = auth_file_path()?;
     if legacy_path.exists() {
+        #[cfg(unix)]
+        restrict_permissions(&legacy_path, 0o600);
         let data = fs::read_to_string(&legacy_path)
             .with_context(|| format!("reading {}", legacy_path.display()))?;
         let auth: CliAuth = serde_json::from_str(&data)
             .with_context(|| format!("parsing {}", legacy_path.display()))?;
 
+        if fallback {
+            // A token saved to secure storage before an earlier login fell back to the
+            // file may still be there; only use it if it is the newer one
+            if let Some(secure) = secure
+                && secure.expires_at > auth.expires_at
+            {
+                debug!(
+                    "Secure storage holds a newer token than fallback file {}",
+                    legacy_path.display()
+                );
+                return Ok(Some(secure));
+            }
+            debug!(
+                "Using fallback token file {}; secure storage was unavailable at login",
+                legacy_path.display()
+            );
+            return Ok(Some(auth));
+        }
+
+        if auth_migration_disabled() {
+            debug!(
+                "Auth migration disabled; leaving legacy {} untouched",
+                legacy_path.display()
+            );
+            return Ok(Some(auth));
+        }
+
         // Migrate to secure storage
         let user_token =
             runbeam_sdk::UserToken::new(auth.token.clone(), auth.expires_at, auth.user.clone());
@@ -248,26 +999,108 @@ pub fn load_auth() -> Result<Option<CliAuth>> {
     Ok(None)
 }
 
-pub fn save_auth(auth: &CliAuth) -> Result<()> {
+/// Where [`save_auth`] put the token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthStore {
+    /// The SDK's secure storage (OS keyring, or its encrypted file store when configured)
+    Secure,
+    /// The SDK's encrypted file store, because saving to the keyring failed
+    EncryptedFile(PathBuf),
+    /// A plaintext file readable only by the current user, because both stores failed
+    PlaintextFile(PathBuf),
+}
+
+impl AuthStore {
+    /// Warning to show the user when the token was saved with reduced security
+    pub fn reduced_security_warning(&self) -> Option<String> {
+        match self {
+            AuthStore::Secure => None,
+            AuthStore::EncryptedFile(path) => Some(format!(
+                "The OS keyring is unavailable; the token was saved to the encrypted file store at {} instead. Its key is kept on disk, so anyone with access to your account can read it.",
+                path.display()
+            )),
+            AuthStore::PlaintextFile(path) => Some(format!(
+                "Secure storage is unavailable; the token was saved UNENCRYPTED to {} (readable only by you). Run `runbeam auth:backend` to diagnose.",
+                path.display()
+            )),
+        }
+    }
+}
+
+/// Save the user token, falling back to less secure stores if secure storage fails
+///
+/// The fallbacks are tried in order: the SDK's encrypted file store, then a 0600
+/// plaintext `auth.json` (marked so it is not migrated on every load).
+pub fn save_auth(auth: &CliAuth) -> Result<AuthStore> {
     let user_token =
         runbeam_sdk::UserToken::new(auth.token.clone(), auth.expires_at, auth.user.clone());
 
     let runtime = tokio::runtime::Runtime::new()?;
-    runtime
-        .block_on(runbeam_sdk::save_token(
-            "runbeam-cli",
-            "user_auth",
-            &user_token,
-        ))
-        .map_err(|e| anyhow::anyhow!("Failed to save token to secure storage: {}", e))?;
-
-    // Remove legacy file if it exists (cleanup)
     let legacy_path = auth_file_path()?;
-    if legacy_path.exists() {
-        fs::remove_file(&legacy_path).ok();
+    let marker_path = auth_fallback_marker_path()?;
+    let secure = runtime.block_on(runbeam_sdk::save_token(
+        "runbeam-cli",
+        "user_auth",
+        &user_token,
+    ));
+    let Err(secure_err) = secure else {
+        // Remove legacy file if it exists (cleanup)
+        if legacy_path.exists() {
+            fs::remove_file(&legacy_path).ok();
+        }
+        fs::remove_file(&marker_path).ok();
+        return Ok(AuthStore::Secure);
+    };
+    warn!("Failed to save token to secure storage: {}", secure_err);
+
+    let json = serde_json::to_vec_pretty(&user_token)?;
+    match runtime.block_on(save_to_encrypted_file(&json)) {
+        Ok(path) => {
+            if legacy_path.exists() {
+                fs::remove_file(&legacy_path).ok();
+            }
+            fs::remove_file(&marker_path).ok();
+            return Ok(AuthStore::EncryptedFile(path));
+        }
+        Err(e) => warn!("Failed to save token to the encrypted file store: {}", e),
     }
 
-    Ok(())
+    let json = serde_json::to_vec_pretty(auth)?;
+    write_atomic_private(&legacy_path, &json).with_context(|| {
+        format!(
+            "Failed to save token to secure storage ({}) and to {}",
+            secure_err,
+            legacy_path.display()
+        )
+    })?;
+    write_atomic(&marker_path, b"").ok();
+    // Drop the token of an earlier login from secure storage, if it can still be reached,
+    // so it cannot be mistaken for the current one
+    let _ = runtime.block_on(runbeam_sdk::clear_token("runbeam-cli", "user_auth"));
+    Ok(AuthStore::PlaintextFile(legacy_path))
+}
+
+/// Write the token straight to the SDK's encrypted file store, bypassing the keyring
+async fn save_to_encrypted_file(json: &[u8]) -> std::result::Result<PathBuf, String> {
+    use runbeam_sdk::storage::{EncryptedFilesystemStorage, StorageBackend};
+
+    let store = EncryptedFilesystemStorage::new_with_instance("runbeam-cli")
+        .await
+        .map_err(|e| e.to_string())?;
+    store
+        .write_file_str(TOKEN_PATH, json)
+        .await
+        .map_err(|e| e.to_string())?;
+    encrypted_store_path()
+}
+
+/// Location of the SDK's encrypted file store (`~/.runbeam/runbeam-cli` on every platform)
+fn encrypted_store_path() -> std::result::Result<PathBuf, String> {
+    Ok(BaseDirs::new()
+        .ok_or("could not determine base directories")?
+        .home_dir()
+        .join(".runbeam")
+        .join("runbeam-cli"))
 }
 
 pub fn clear_auth() -> Result<bool> {
@@ -276,6 +1109,7 @@ pub fn clear_auth() -> Result<bool> {
 
     // Also remove legacy file if it exists (cleanup)
     let legacy_path = auth_file_path()?;
+    fs::remove_file(auth_fallback_marker_path()?).ok();
     if legacy_path.exists() {
         fs::remove_file(&legacy_path)
             .with_context(|| format!("removing {}", legacy_path.display()))?;
@@ -285,6 +1119,171 @@ pub fn clear_auth() -> Result<bool> {
     }
 }
 
+// ============================================================================
+// Token Storage Backend Selection
+// ============================================================================
+
+/// Token storage backend requested via `RUNBEAM_STORAGE_BACKEND`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendPreference {
+    /// Let the SDK choose (keyring unless `RUNBEAM_DISABLE_KEYRING` is set)
+    Auto,
+    /// OS keyring (keychain, Secret Service, Credential Manager)
+    Keyring,
+    /// Encrypted file under `~/.runbeam/runbeam-cli`
+    File,
+}
+
+impl BackendPreference {
+    /// The `RUNBEAM_STORAGE_BACKEND` value selecting this preference
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Keyring => "keyring",
+            Self::File => "file",
+        }
+    }
+
+    fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "auto" => Ok(Self::Auto),
+            "keyring" => Ok(Self::Keyring),
+            "file" => Ok(Self::File),
+            other => anyhow::bail!(
+                "invalid RUNBEAM_STORAGE_BACKEND '{}': expected auto, keyring or file",
+                other
+            ),
+        }
+    }
+}
+
+/// Read `RUNBEAM_STORAGE_BACKEND` and configure the SDK accordingly
+///
+/// The SDK only knows `RUNBEAM_DISABLE_KEYRING`, so `file` sets it and `keyring`
+/// clears it. Must be called before any other threads are spawned.
+pub fn apply_backend_preference() -> Result<BackendPreference> {
+    let preference = match std::env::var("RUNBEAM_STORAGE_BACKEND") {
+        Ok(value) => BackendPreference::parse(&value)?,
+        Err(_) => BackendPreference::Auto,
+    };
+    // SAFETY: called from main before any other threads exist
+    unsafe {
+        match preference {
+            BackendPreference::Auto => {}
+            BackendPreference::Keyring => std::env::remove_var("RUNBEAM_DISABLE_KEYRING"),
+            BackendPreference::File => std::env::set_var("RUNBEAM_DISABLE_KEYRING", "1"),
+        }
+    }
+    Ok(preference)
+}
+
+/// Health of the token storage backends, as reported by `auth:backend`
+#[derive(Debug)]
+pub struct BackendReport {
+    pub preference: BackendPreference,
+    /// Backend the SDK will write tokens to: "keyring" or "file"
+    pub active: &'static str,
+    /// Why the keyring is not used, if it isn't
+    pub fallback_reason: Option<String>,
+    /// Result of a write/read/remove probe against the keyring (`None` if disabled)
+    pub keyring: Option<std::result::Result<(), String>>,
+    /// Result of a write/read/remove probe against the encrypted file store
+    pub file: std::result::Result<(), String>,
+    /// Location of the encrypted file store
+    pub file_path: PathBuf,
+    /// Backend currently holding the user token, if any
+    pub token_location: Option<&'static str>,
+}
+
+const TOKEN_PATH: &str = "runbeam/user_auth.json";
+
+/// Keyring service of the probe entry, kept apart from every service holding tokens
+const KEYRING_PROBE_SERVICE: &str = "runbeam-cli-backend-check";
+
+/// Entry written, read back and removed again by [`probe_backend`]
+const PROBE_PATH: &str = "runbeam/backend_check.json";
+
+async fn probe_backend(
+    backend: &dyn runbeam_sdk::storage::StorageBackend,
+) -> std::result::Result<(), String> {
+    let payload = b"{\"probe\":true}";
+    backend
+        .write_file_str(PROBE_PATH, payload)
+        .await
+        .map_err(|e| format!("write failed: {}", e))?;
+    let read = backend.read_file_str(PROBE_PATH).await;
+    // Remove the entry whatever the read did, and say so if it stays behind
+    backend.remove_str(PROBE_PATH).await.map_err(|e| {
+        format!(
+            "could not remove the probe entry '{}' after the check: {}",
+            PROBE_PATH, e
+        )
+    })?;
+    let read = read.map_err(|e| format!("read failed: {}", e))?;
+    if read != payload {
+        return Err("read back a different value than was written".to_string());
+    }
+    Ok(())
+}
+
+/// Probe the token storage backends without touching the stored token
+pub fn check_token_backends(preference: BackendPreference) -> Result<BackendReport> {
+    use runbeam_sdk::storage::{EncryptedFilesystemStorage, KeyringStorage, StorageBackend};
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    let keyring_disabled = std::env::var("RUNBEAM_DISABLE_KEYRING").is_ok();
+    let file_path = encrypted_store_path().map_err(anyhow::Error::msg)?;
+
+    let keyring_store = KeyringStorage::new("runbeam");
+    let keyring = if keyring_disabled {
+        None
+    } else {
+        let probe_store = KeyringStorage::new(KEYRING_PROBE_SERVICE);
+        Some(runtime.block_on(probe_backend(&probe_store)))
+    };
+
+    let file_store = runtime.block_on(EncryptedFilesystemStorage::new_with_instance("runbeam-cli"));
+    let file = match &file_store {
+        Ok(store) => runtime.block_on(probe_backend(store)),
+        Err(e) => Err(format!("cannot open encrypted store: {}", e)),
+    };
+
+    let (active, fallback_reason) = if keyring_disabled {
+        let reason = if preference == BackendPreference::File {
+            "RUNBEAM_STORAGE_BACKEND=file"
+        } else {
+            "RUNBEAM_DISABLE_KEYRING is set"
+        };
+        ("file", Some(reason.to_string()))
+    } else {
+        ("keyring", None)
+    };
+
+    let token_location = if !keyring_disabled && keyring_store.exists_str(TOKEN_PATH) {
+        Some("keyring")
+    } else if file_store
+        .as_ref()
+        .map(|s| s.exists_str(TOKEN_PATH))
+        .unwrap_or(false)
+    {
+        Some("file")
+    } else if auth_fallback_marker_path()?.exists() && auth_file_path()?.exists() {
+        Some("plaintext file (fallback)")
+    } else {
+        None
+    };
+
+    Ok(BackendReport {
+        preference,
+        active,
+        fallback_reason,
+        keyring,
+        file,
+        file_path,
+        token_location,
+    })
+}
+
 /// Load authentication and verify the JWT token
 ///
 /// This function loads the stored auth and validates the JWT token using RS256.
@@ -354,6 +1353,190 @@ mod tests {
         assert_ne!(id1, id4, "Different ports should produce different IDs");
     }
 
+    #[test]
+    fn test_generate_id_format_and_uniqueness() {
+        let id = generate_id(&[]);
+        assert_eq!(id.len(), 8);
+        assert!(id.chars().all(|c| c.is_ascii_hexdigit()));
+
+        let existing: Vec<HarmonyInstance> = (0..50)
+            .map(|n| HarmonyInstance {
+                id: generate_id(&[]),
+                ip: "127.0.0.1".to_string(),
+                port: 8000 + n,
+                label: format!("inst-{}", n),
+                ..Default::default()
+            })
+            .collect();
+        let id = generate_id(&existing);
+        assert!(!existing.iter().any(|i| i.id == id));
+    }
+
+    #[test]
+    fn test_backend_preference_parse() {
+        assert_eq!(
+            BackendPreference::parse("").unwrap(),
+            BackendPreference::Auto
+        );
+        assert_eq!(
+            BackendPreference::parse("auto").unwrap(),
+            BackendPreference::Auto
+        );
+        assert_eq!(
+            BackendPreference::parse("Keyring").unwrap(),
+            BackendPreference::Keyring
+        );
+        assert_eq!(
+            BackendPreference::parse(" file ").unwrap(),
+            BackendPreference::File
+        );
+        assert!(BackendPreference::parse("vault").is_err());
+    }
+
+    #[test]
+    fn test_backend_preference_as_str_round_trips() {
+        for preference in [
+            BackendPreference::Auto,
+            BackendPreference::Keyring,
+            BackendPreference::File,
+        ] {
+            assert_eq!(
+                BackendPreference::parse(preference.as_str()).unwrap(),
+                preference
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_group_name() {
+        assert!(validate_group_name("us-east").is_ok());
+        assert!(validate_group_name("eu_west.2").is_ok());
+        assert!(validate_group_name("").is_err());
+        assert!(validate_group_name("-all").is_err());
+        assert!(validate_group_name(&"g".repeat(65)).is_err());
+        assert_eq!(
+            validate_group_name("us east").unwrap_err().to_string(),
+            "invalid group name 'us east': ' ' is not allowed (use letters, digits, '-', '_' or '.')"
+        );
+    }
+
+    #[test]
+    fn test_normalize_path_prefix() {
+        assert_eq!(normalize_path_prefix("admin").unwrap(), "admin");
+        assert_eq!(normalize_path_prefix("/admin/").unwrap(), "admin");
+        assert_eq!(normalize_path_prefix("  //api/v1/ ").unwrap(), "api/v1");
+        assert_eq!(normalize_path_prefix("").unwrap(), "");
+        assert_eq!(normalize_path_prefix("/").unwrap(), "");
+    }
+
+    #[test]
+    fn test_normalize_scheme() {
+        assert_eq!(normalize_scheme("https").unwrap(), "https");
+        assert_eq!(normalize_scheme(" HTTP ").unwrap(), "http");
+        let err = normalize_scheme("ftp").unwrap_err().to_string();
+        assert_eq!(err, "invalid scheme 'ftp': expected 'http' or 'https'");
+    }
+
+    #[test]
+    fn test_upsert_instance_forgets_cached_state_when_the_address_changes() {
+        let mut list = vec![HarmonyInstance {
+            id: "ab12cd34".to_string(),
+            ip: "10.0.0.5".to_string(),
+            port: 8081,
+            label: "edge".to_string(),
+            gateway_id: Some("gw-1".to_string()),
+            version: Some("1.2.0".to_string()),
+            machine_token_expires_at: Some(1_900_000_000),
+            ..Default::default()
+        }];
+        let readd = |ip: &str, port: u16| HarmonyInstance {
+            ip: ip.to_string(),
+            port,
+            label: "edge".to_string(),
+            ..Default::default()
+        };
+
+        // Same label and address: the cache stays
+        upsert_instance(&mut list, readd("10.0.0.5", 8081));
+        assert_eq!(list[0].version.as_deref(), Some("1.2.0"));
+        assert_eq!(list[0].machine_token_expires_at, Some(1_900_000_000));
+
+        // Same label, new address
+        upsert_instance(&mut list, readd("10.0.0.6", 8081));
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].id, "ab12cd34");
+        assert_eq!(list[0].ip, "10.0.0.6");
+        assert_eq!(list[0].gateway_id.as_deref(), Some("gw-1"));
+        assert_eq!(list[0].version, None);
+        assert_eq!(list[0].machine_token_expires_at, None);
+    }
+
+    #[test]
+    fn test_scheme_defaults_to_http_for_existing_entries() {
+        let inst: HarmonyInstance =
+            serde_json::from_str(r#"{"id":"abc","ip":"10.0.0.1","port":8081,"label":"x"}"#)
+                .unwrap();
+        assert_eq!(inst.scheme, "http");
+    }
+
+    #[test]
+    fn test_migrate_registry_v1_bare_array() {
+        let doc = serde_json::json!([{"id":"a1","ip":"10.0.0.1","port":8081,"label":"x"}]);
+        let (list, migrated) = migrate_registry(doc).unwrap();
+        assert!(migrated);
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].id, "a1");
+        assert_eq!(list[0].path_prefix, "admin");
+
+        let (list, migrated) = migrate_registry(serde_json::json!([])).unwrap();
+        assert!(migrated);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn test_migrate_registry_v1_to_v2_wraps_array() {
+        let doc = migrate_registry_v1_to_v2(serde_json::json!([{"label":"x"}])).unwrap();
+        assert_eq!(
+            doc,
+            serde_json::json!({"version": 2, "instances": [{"label":"x"}]})
+        );
+        assert_eq!(registry_version(&doc).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_migrate_registry_current_version_untouched() {
+        let doc = serde_json::json!({
+            "version": REGISTRY_VERSION,
+            "instances": [{"id":"a1","ip":"10.0.0.1","port":8081,"label":"x"}],
+        });
+        let (list, migrated) = migrate_registry(doc).unwrap();
+        assert!(!migrated);
+        assert_eq!(list[0].label, "x");
+    }
+
+    #[test]
+    fn test_migrate_registry_rejects_newer_and_unknown_formats() {
+        let err = migrate_registry(serde_json::json!({"version": 99, "instances": []}))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("written by a newer runbeam CLI"), "{err}");
+
+        assert!(migrate_registry(serde_json::json!({"instances": []})).is_err());
+        assert!(migrate_registry(serde_json::json!({"version": 0, "instances": []})).is_err());
+        assert!(migrate_registry(serde_json::json!({"version": 2})).is_err());
+    }
+
+    #[test]
+    fn test_registry_migrations_cover_every_version() {
+        assert_eq!(REGISTRY_MIGRATIONS.len() as u64, REGISTRY_VERSION - 1);
+    }
+
+    #[test]
+    fn test_normalize_path_prefix_rejects_invalid() {
+        assert!(normalize_path_prefix("http://host/admin").is_err());
+        assert!(normalize_path_prefix("my admin").is_err());
+    }
+
     #[test]
     fn test_default_path_prefix() {
         assert_eq!(default_path_prefix(), "admin");
@@ -366,8 +1549,7 @@ mod tests {
             ip: "127.0.0.1".to_string(),
             port: 8081,
             label: "test".to_string(),
-            path_prefix: "admin".to_string(),
-            gateway_id: None,
+            ..Default::default()
         };
 
         let json = serde_json::to_string(&instance).expect("Failed to serialize");
@@ -414,6 +1596,100 @@ mod tests {
         assert_eq!(tmp, PathBuf::from("/tmp/test.json.tmp"));
     }
 
+    #[test]
+    fn test_interrupted_write_tmp_is_cleaned_up() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let target = dir.path().join("harmony.json");
+        fs::write(&target, "[]").unwrap();
+        // Simulate a write killed before the rename
+        let orphan = tmp_path_for(&target);
+        fs::write(&orphan, "[{\"partial\"").unwrap();
+
+        // Fresh temp files may belong to a write in progress
+        assert_eq!(
+            remove_stale_tmp_files(dir.path(), Duration::from_secs(3600)),
+            0
+        );
+        assert!(orphan.exists());
+
+        assert_eq!(remove_stale_tmp_files(dir.path(), Duration::ZERO), 1);
+        assert!(!orphan.exists());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "[]");
+    }
+
+    #[test]
+    fn test_tmp_file_guard_removes_unless_disarmed() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let dropped = dir.path().join("a.json.tmp");
+        fs::write(&dropped, "x").unwrap();
+        drop(TmpFileGuard::new(dropped.clone()));
+        assert!(!dropped.exists());
+
+        let kept = dir.path().join("b.json.tmp");
+        fs::write(&kept, "x").unwrap();
+        TmpFileGuard::new(kept.clone()).disarm();
+        assert!(kept.exists());
+    }
+
+    #[test]
+    fn test_write_atomic_replaces_file_without_leftovers() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let target = dir.path().join("config.json");
+        fs::write(&target, "old").unwrap();
+
+        write_atomic(&target, b"new").unwrap();
+
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        assert!(!tmp_path_for(&target).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_atomic_private_is_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::TempDir::new().unwrap();
+        let target = dir.path().join("auth.json");
+        fs::write(&target, "old").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_atomic_private(&target, b"{}").unwrap();
+
+        let mode = fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(fs::read_to_string(&target).unwrap(), "{}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_tighten_mode_only_changes_shared_files() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::TempDir::new().unwrap();
+        let target = dir.path().join("config.json");
+        fs::write(&target, "{}").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o644)).unwrap();
+
+        assert_eq!(tighten_mode(&target, 0o600).unwrap(), Some(0o644));
+        let mode = fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(tighten_mode(&target, 0o600).unwrap(), None);
+
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o400)).unwrap();
+        assert_eq!(tighten_mode(&target, 0o600).unwrap(), None);
+    }
+
+    #[test]
+    fn test_auth_store_reduced_security_warning() {
+        assert_eq!(AuthStore::Secure.reduced_security_warning(), None);
+        let encrypted = AuthStore::EncryptedFile(PathBuf::from("/home/u/.runbeam/runbeam-cli"))
+            .reduced_security_warning()
+            .unwrap();
+        assert!(encrypted.contains("encrypted file store at /home/u/.runbeam/runbeam-cli"));
+        let plaintext = AuthStore::PlaintextFile(PathBuf::from("/home/u/.runbeam/auth.json"))
+            .reduced_security_warning()
+            .unwrap();
+        assert!(plaintext.contains("UNENCRYPTED to /home/u/.runbeam/auth.json"));
+    }
+
     #[test]
     fn test_harmony_instance_with_gateway_id() {
         let instance = HarmonyInstance {
@@ -421,8 +1697,8 @@ mod tests {
             ip: "127.0.0.1".to_string(),
             port: 8081,
             label: "test".to_string(),
-            path_prefix: "admin".to_string(),
             gateway_id: Some("01JBXXXXXXXXXXXXXXXXXXXXXXXXXX".to_string()),
+            ..Default::default()
         };
 
         let json = serde_json::to_string(&instance).expect("Failed to serialize");
@@ -431,7 +1707,10 @@ mod tests {
 
         let deserialized: HarmonyInstance =
             serde_json::from_str(&json).expect("Failed to deserialize");
-        assert_eq!(deserialized.gateway_id, Some("01JBXXXXXXXXXXXXXXXXXXXXXXXXXX".to_string()));
+        assert_eq!(
+            deserialized.gateway_id,
+            Some("01JBXXXXXXXXXXXXXXXXXXXXXXXXXX".to_string())
+        );
         assert_eq!(instance, deserialized);
     }
 
@@ -448,6 +1727,8 @@ mod tests {
 
         let instance: HarmonyInstance = serde_json::from_str(json).expect("Failed to deserialize");
         assert_eq!(instance.gateway_id, None);
+        assert!(instance.headers.is_empty());
+        assert!(instance.tags.is_empty());
         assert_eq!(instance.id, "abc123");
         assert_eq!(instance.label, "test");
     }
@@ -460,15 +1741,18 @@ mod tests {
             ip: "192.168.1.1".to_string(),
             port: 9090,
             label: "my-harmony".to_string(),
-            path_prefix: "admin".to_string(),
             gateway_id: Some("01JBXX1234567890ABCDEFGHIJK".to_string()),
+            ..Default::default()
         };
 
         let json = serde_json::to_string(&instance_with_gw).expect("Failed to serialize");
         let deserialized: HarmonyInstance =
             serde_json::from_str(&json).expect("Failed to deserialize");
 
-        assert_eq!(deserialized.gateway_id, Some("01JBXX1234567890ABCDEFGHIJK".to_string()));
+        assert_eq!(
+            deserialized.gateway_id,
+            Some("01JBXX1234567890ABCDEFGHIJK".to_string())
+        );
 
         // Test with gateway_id absent (None)
         let instance_without_gw = HarmonyInstance {
@@ -476,8 +1760,7 @@ mod tests {
             ip: "192.168.1.2".to_string(),
             port: 9091,
             label: "other-harmony".to_string(),
-            path_prefix: "admin".to_string(),
-            gateway_id: None,
+            ..Default::default()
         };
 
         let json = serde_json::to_string(&instance_without_gw).expect("Failed to serialize");
@@ -486,4 +1769,204 @@ mod tests {
 
         assert_eq!(deserialized.gateway_id, None);
     }
+
+    #[test]
+    fn test_expand_path_tilde() {
+        let home = BaseDirs::new().unwrap().home_dir().to_path_buf();
+        assert_eq!(expand_path("~"), home);
+        assert_eq!(expand_path("~/foo.json"), home.join("foo.json"));
+        // `~user` is not expanded
+        assert_eq!(expand_path("~other/foo"), PathBuf::from("~other/foo"));
+        assert_eq!(expand_path("/tmp/~/foo"), PathBuf::from("/tmp/~/foo"));
+    }
+
+    #[test]
+    fn test_expand_path_env_vars() {
+        unsafe {
+            std::env::set_var("RUNBEAM_TEST_EXPAND_DIR", "/srv/runbeam");
+        }
+        assert_eq!(
+            expand_path("$RUNBEAM_TEST_EXPAND_DIR/routes.json"),
+            PathBuf::from("/srv/runbeam/routes.json")
+        );
+        assert_eq!(
+            expand_path("${RUNBEAM_TEST_EXPAND_DIR}-backup/x"),
+            PathBuf::from("/srv/runbeam-backup/x")
+        );
+        unsafe {
+            std::env::remove_var("RUNBEAM_TEST_EXPAND_DIR");
+        }
+    }
+
+    #[test]
+    fn test_expand_path_unset_var_left_untouched() {
+        assert_eq!(
+            expand_path("$RUNBEAM_TEST_SURELY_UNSET/x"),
+            PathBuf::from("$RUNBEAM_TEST_SURELY_UNSET/x")
+        );
+        assert_eq!(
+            expand_path("${UNTERMINATED"),
+            PathBuf::from("${UNTERMINATED")
+        );
+        assert_eq!(expand_path("cost$"), PathBuf::from("cost$"));
+    }
+
+    #[test]
+    fn test_harmony_instance_headers_serialization() {
+        let instance = HarmonyInstance {
+            id: "abc123".to_string(),
+            ip: "127.0.0.1".to_string(),
+            port: 8081,
+            label: "test".to_string(),
+            headers: vec![("X-Api-Key".to_string(), "secret".to_string())],
+            ..Default::default()
+        };
+
+        let json = serde_json::to_string(&instance).expect("Failed to serialize");
+        assert!(json.contains("X-Api-Key"));
+        let deserialized: HarmonyInstance =
+            serde_json::from_str(&json).expect("Failed to deserialize");
+        assert_eq!(instance, deserialized);
+
+        // Empty headers are omitted to keep older files unchanged
+        let without = HarmonyInstance {
+            headers: Vec::new(),
+            ..instance
+        };
+        let json = serde_json::to_string(&without).expect("Failed to serialize");
+        assert!(!json.contains("headers"));
+    }
+
+    #[test]
+    fn test_harmony_instance_tags_serialization() {
+        let instance = HarmonyInstance {
+            id: "abc123".to_string(),
+            ip: "127.0.0.1".to_string(),
+            port: 8081,
+            label: "test".to_string(),
+            tags: vec!["prod".to_string(), "eu".to_string()],
+            ..Default::default()
+        };
+
+        let json = serde_json::to_string(&instance).expect("Failed to serialize");
+        assert!(json.contains(r#""tags":["prod","eu"]"#));
+        let deserialized: HarmonyInstance =
+            serde_json::from_str(&json).expect("Failed to deserialize");
+        assert_eq!(instance, deserialized);
+
+        let without = HarmonyInstance {
+            tags: Vec::new(),
+            ..instance
+        };
+        let json = serde_json::to_string(&without).expect("Failed to serialize");
+        assert!(!json.contains("tags"));
+    }
+
+    #[test]
+    fn test_parse_inventory_yaml_and_json_layouts() {
+        let yaml = "\
+- label: edge
+  ip: 10.0.0.5
+  port: 8081
+- id: fixedid1
+  label: core
+  ip: 10.0.0.6
+  port: 9090
+  scheme: https
+  tags: [prod]
+";
+        let list = parse_inventory(yaml, false).unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].id, derive_id("10.0.0.5", 8081, "edge"));
+        assert_eq!(list[0].path_prefix, "admin");
+        assert_eq!(list[0].scheme, "http");
+        assert_eq!(list[1].id, "fixedid1");
+        assert_eq!(list[1].scheme, "https");
+        assert_eq!(list[1].tags, ["prod"]);
+
+        let json =
+            r#"{"version": 2, "instances": [{"label": "edge", "ip": "10.0.0.5", "port": 8081}]}"#;
+        let list = parse_inventory(json, true).unwrap();
+        assert_eq!(list[0].label, "edge");
+
+        assert!(parse_inventory("- label: edge", false).is_err());
+    }
+
+    #[test]
+    fn test_parse_inventory_normalizes_like_harmony_add() {
+        let yaml = "\
+- label: edge
+  ip: 10.0.0.5
+  port: 8081
+  path_prefix: /api/admin/
+  scheme: HTTPS
+";
+        let list = parse_inventory(yaml, false).unwrap();
+        assert_eq!(list[0].path_prefix, "api/admin");
+        assert_eq!(list[0].scheme, "https");
+
+        let err = parse_inventory(
+            "- {label: edge, ip: 10.0.0.5, port: 8081, scheme: ftp}",
+            false,
+        )
+        .unwrap_err();
+        assert!(format!("{:#}", err).contains("instance 'edge'"));
+        assert!(format!("{:#}", err).contains("invalid scheme 'ftp'"));
+    }
+
+    #[test]
+    fn test_merge_inventory_prefers_local_entries() {
+        let inst = |id: &str, label: &str, port: u16| HarmonyInstance {
+            id: id.to_string(),
+            ip: "127.0.0.1".to_string(),
+            port,
+            label: label.to_string(),
+            ..Default::default()
+        };
+        let merged = merge_inventory(
+            vec![inst("aaaa0001", "edge", 1)],
+            vec![
+                inst("aaaa0001", "other", 2),
+                inst("bbbb0002", "edge", 3),
+                inst("cccc0003", "core", 4),
+            ],
+        );
+        let ports: Vec<u16> = merged.iter().map(|i| i.port).collect();
+        assert_eq!(ports, [1, 4]);
+    }
+
+    #[test]
+    fn test_find_by_id_accepts_unique_prefix() {
+        let inst = |id: &str, label: &str| HarmonyInstance {
+            id: id.to_string(),
+            ip: "127.0.0.1".to_string(),
+            port: 8081,
+            label: label.to_string(),
+            ..Default::default()
+        };
+        let list = vec![
+            inst("ab12cd34", "edge"),
+            inst("ab99ff00", "core"),
+            inst("7f3a9c21", "lab"),
+            inst("7f3a", "short"),
+        ];
+        let label_of = |id: &str| find_by_id(&list, id).unwrap().map(|i| i.label.clone());
+
+        // Unique prefix, full ID, and an exact ID that is also a prefix of another
+        assert_eq!(label_of("ab1").as_deref(), Some("edge"));
+        assert_eq!(label_of("ab99ff00").as_deref(), Some("core"));
+        assert_eq!(label_of("7f3a").as_deref(), Some("short"));
+        assert_eq!(label_of("7f3a9").as_deref(), Some("lab"));
+
+        // No match
+        assert_eq!(label_of("ffff"), None);
+        assert_eq!(label_of(""), None);
+
+        // Ambiguous prefix lists the candidates
+        let err = find_by_id(&list, "ab").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "id prefix 'ab' is ambiguous; it matches ab12cd34 (edge), ab99ff00 (core)"
+        );
+    }
 }
@@ -0,0 +1,51 @@
+//! Terminal colour helpers
+//!
+//! Colour is only used when stdout is a terminal and `NO_COLOR` is not set
+//! (see <https://no-color.org>).
+
+use std::io::IsTerminal;
+
+/// Foreground colours used by the CLI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Green,
+    Yellow,
+    Red,
+}
+
+impl Color {
+    fn ansi_code(self) -> &'static str {
+        match self {
+            Color::Green => "32",
+            Color::Yellow => "33",
+            Color::Red => "31",
+        }
+    }
+}
+
+/// Whether coloured output should be written to stdout
+pub fn color_enabled() -> bool {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    !no_color && std::io::stdout().is_terminal()
+}
+
+/// Wrap `text` in ANSI colour codes when `enabled`
+pub fn paint(text: &str, color: Color, enabled: bool) -> String {
+    if enabled {
+        format!("\x1b[{}m{}\x1b[0m", color.ansi_code(), text)
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paint() {
+        assert_eq!(paint("ok", Color::Green, false), "ok");
+        assert_eq!(paint("ok", Color::Green, true), "\x1b[32mok\x1b[0m");
+        assert_eq!(paint("late", Color::Red, true), "\x1b[31mlate\x1b[0m");
+    }
+}
@@ -0,0 +1,120 @@
+//! Per-command timing collector behind the global `--timings` flag
+//!
+//! Network calls record how long they took with [`time`]; a [`Summary`] guard
+//! created at startup prints a "phase | duration" table to stderr when the
+//! command finishes (successfully or not).
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static PHASES: RefCell<Vec<(String, Duration)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Turn on timing collection for this process
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Record a completed phase (no-op unless timings are enabled)
+pub fn record(phase: impl Into<String>, elapsed: Duration) {
+    if enabled() {
+        PHASES.with(|p| p.borrow_mut().push((phase.into(), elapsed)));
+    }
+}
+
+/// Run `f`, recording its wall-clock duration under `phase`
+pub fn time<T>(phase: impl Into<String>, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let out = f();
+    record(phase, start.elapsed());
+    out
+}
+
+fn format_duration(d: Duration) -> String {
+    if d < Duration::from_secs(1) {
+        format!("{} ms", d.as_millis())
+    } else {
+        format!("{:.2} s", d.as_secs_f64())
+    }
+}
+
+fn render(phases: &[(String, Duration)], total: Duration) -> String {
+    let rows: Vec<(&str, String)> = phases
+        .iter()
+        .map(|(name, d)| (name.as_str(), format_duration(*d)))
+        .chain(std::iter::once(("total", format_duration(total))))
+        .collect();
+    let w_phase = rows
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0)
+        .max("PHASE".len());
+
+    let mut out = format!("{:<w_phase$} | DURATION\n", "PHASE");
+    for (name, duration) in rows {
+        out.push_str(&format!("{:<w_phase$} | {}\n", name, duration));
+    }
+    out
+}
+
+/// Guard that prints the timing table when dropped
+pub struct Summary {
+    start: Instant,
+}
+
+impl Summary {
+    pub fn start() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for Summary {
+    fn drop(&mut self) {
+        if !enabled() {
+            return;
+        }
+        let phases = PHASES.with(|p| std::mem::take(&mut *p.borrow_mut()));
+        eprint!("\n{}", render(&phases, self.start.elapsed()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(42)), "42 ms");
+        assert_eq!(format_duration(Duration::from_millis(2500)), "2.50 s");
+    }
+
+    #[test]
+    fn test_render_table() {
+        let phases = vec![
+            (
+                "GET http://h:1/admin/info".to_string(),
+                Duration::from_millis(12),
+            ),
+            ("POST cloud".to_string(), Duration::from_millis(1500)),
+        ];
+        let table = render(&phases, Duration::from_millis(1600));
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("PHASE"));
+        assert_eq!(lines[1], "GET http://h:1/admin/info | 12 ms");
+        assert!(lines[2].ends_with("| 1.50 s"));
+        assert!(lines[3].starts_with("total "));
+        assert!(lines[3].ends_with("| 1.60 s"));
+    }
+}
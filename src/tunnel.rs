@@ -0,0 +1,235 @@
+//! SSH tunnel to Harmony instances behind a bastion (global `--connect-via`)
+//!
+//! Before a command talks to an instance, [`open`] makes sure an `ssh -N -D`
+//! SOCKS tunnel through the jump host is running and marks the instance as
+//! tunneled. Clients built in [`crate::http`] send requests for tunneled instances
+//! through that tunnel as a `socks5h://` proxy (see [`proxy_for`]), so URLs keep
+//! the instance's own host and port: the `Host` header and HTTPS certificate
+//! checks are unchanged, and the address is resolved on the jump host. One ssh
+//! process serves every instance of the run; the [`Guard`] created at startup
+//! stops it when the command finishes.
+//!
+//! Spawning the tunnel needs the `ssh-tunnel` feature and an OpenSSH client on
+//! `PATH` that can log in to the jump host without prompting (keys or an agent).
+
+use anyhow::Result;
+use std::collections::HashSet;
+use std::process::Child;
+use std::sync::{Mutex, OnceLock};
+
+static JUMP_HOST: OnceLock<String> = OnceLock::new();
+
+static TUNNEL: Mutex<Option<Tunnel>> = Mutex::new(None);
+
+/// `(host, port)` of the instances whose requests go through the tunnel
+static TARGETS: Mutex<Option<HashSet<(String, u16)>>> = Mutex::new(None);
+
+/// An `ssh -D` process serving SOCKS on a local port
+struct Tunnel {
+    local_port: u16,
+    child: Child,
+}
+
+impl Drop for Tunnel {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Reach instances through `jump_host` (`[user@]host`, or `ssh://user@host:port`)
+pub fn configure(jump_host: String) -> Result<()> {
+    if !cfg!(feature = "ssh-tunnel") {
+        anyhow::bail!(
+            "--connect-via requires runbeam to be built with the `ssh-tunnel` feature (cargo install runbeam-cli --features ssh-tunnel)"
+        );
+    }
+    let _ = JUMP_HOST.set(jump_host);
+    Ok(())
+}
+
+/// Whether `--connect-via` was given
+pub fn configured() -> bool {
+    JUMP_HOST.get().is_some()
+}
+
+/// Route requests for `inst` through the jump host, if one is configured,
+/// starting the tunnel on first use
+pub fn open(inst: &crate::storage::HarmonyInstance) -> Result<()> {
+    let Some(jump_host) = JUMP_HOST.get() else {
+        return Ok(());
+    };
+    {
+        let mut tunnel = TUNNEL.lock().unwrap_or_else(|e| e.into_inner());
+        if tunnel.is_none() {
+            *tunnel = Some(spawn(jump_host)?);
+        }
+    }
+    TARGETS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(HashSet::new)
+        .insert((unbracket(&inst.ip).to_string(), inst.port));
+    Ok(())
+}
+
+/// The `socks5h://` proxy URL for requests to `url`, if its instance is tunneled
+pub fn proxy_for(url: &reqwest::Url) -> Option<String> {
+    let host = unbracket(url.host_str()?).to_string();
+    let port = url.port_or_known_default()?;
+    let tunneled = TARGETS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .is_some_and(|targets| targets.contains(&(host, port)));
+    if !tunneled {
+        return None;
+    }
+    let tunnel = TUNNEL.lock().unwrap_or_else(|e| e.into_inner());
+    tunnel
+        .as_ref()
+        .map(|t| format!("socks5h://127.0.0.1:{}", t.local_port))
+}
+
+fn unbracket(host: &str) -> &str {
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+/// Stop the tunnel, if one is running
+pub fn close() {
+    TUNNEL.lock().unwrap_or_else(|e| e.into_inner()).take();
+    TARGETS.lock().unwrap_or_else(|e| e.into_inner()).take();
+}
+
+/// Stops the tunnel when dropped at the end of `main`
+pub struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        close();
+    }
+}
+
+#[cfg(not(feature = "ssh-tunnel"))]
+fn spawn(_jump_host: &str) -> Result<Tunnel> {
+    unreachable!("configure() refuses --connect-via without the ssh-tunnel feature")
+}
+
+#[cfg(feature = "ssh-tunnel")]
+use self::ssh::spawn;
+
+#[cfg(feature = "ssh-tunnel")]
+mod ssh {
+    use super::Tunnel;
+    use anyhow::{Context, Result};
+    use std::io::Read;
+    use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+    use std::process::{Command, Stdio};
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use tracing::debug;
+
+    /// How long to wait for ssh to log in and start forwarding
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+    /// Pause between checks of the local end of the tunnel
+    const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// Arguments for `ssh`, serving SOCKS on `127.0.0.1:local_port`
+    pub(super) fn ssh_args(jump_host: &str, local_port: u16) -> Vec<String> {
+        [
+            "-N",
+            "-o",
+            "ExitOnForwardFailure=yes",
+            "-o",
+            "BatchMode=yes",
+            "-o",
+            "LogLevel=ERROR",
+            "-D",
+            &format!("127.0.0.1:{}", local_port),
+            "--",
+            jump_host,
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+
+    /// A free local port; ssh binds it right after, so another process could
+    /// take it in between, in which case ssh exits with a forwarding error
+    fn free_port() -> Result<u16> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .context("finding a free local port for the SSH tunnel")?;
+        Ok(listener.local_addr()?.port())
+    }
+
+    pub(in crate::tunnel) fn spawn(jump_host: &str) -> Result<Tunnel> {
+        let local_port = free_port()?;
+        let args = ssh_args(jump_host, local_port);
+        debug!("starting SSH tunnel: ssh {}", args.join(" "));
+        let child = Command::new("ssh")
+            .args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("starting ssh for --connect-via; is an OpenSSH client installed?")?;
+        let mut tunnel = Tunnel { local_port, child };
+
+        let local = SocketAddr::from((Ipv4Addr::LOCALHOST, local_port));
+        let started = Instant::now();
+        loop {
+            if let Some(status) = tunnel.child.try_wait()? {
+                let mut stderr = String::new();
+                if let Some(mut pipe) = tunnel.child.stderr.take() {
+                    let _ = pipe.read_to_string(&mut stderr);
+                }
+                anyhow::bail!(
+                    "SSH tunnel via {} failed ({}): {}",
+                    jump_host,
+                    status,
+                    stderr.trim()
+                );
+            }
+            if TcpStream::connect_timeout(&local, POLL_INTERVAL).is_ok() {
+                debug!(
+                    "SSH tunnel via {} ready: SOCKS on 127.0.0.1:{}",
+                    jump_host, local_port
+                );
+                return Ok(tunnel);
+            }
+            if started.elapsed() >= CONNECT_TIMEOUT {
+                anyhow::bail!(
+                    "timed out after {}s waiting for the SSH tunnel via {}",
+                    CONNECT_TIMEOUT.as_secs(),
+                    jump_host
+                );
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_ssh_args_serve_socks_locally() {
+            assert_eq!(
+                ssh_args("ops@bastion", 40123),
+                [
+                    "-N",
+                    "-o",
+                    "ExitOnForwardFailure=yes",
+                    "-o",
+                    "BatchMode=yes",
+                    "-o",
+                    "LogLevel=ERROR",
+                    "-D",
+                    "127.0.0.1:40123",
+                    "--",
+                    "ops@bastion",
+                ]
+            );
+        }
+    }
+}
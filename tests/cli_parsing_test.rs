@@ -1,4 +1,5 @@
 use clap::Parser;
+use std::time::Duration;
 
 // Import the CLI structure - adjust path as needed
 // We need to make sure cli module is accessible for testing
@@ -14,7 +15,36 @@ fn test_parse_list_command() {
 #[test]
 fn test_parse_login_command() {
     let args = cli::Cli::parse_from(["runbeam", "login"]);
-    assert!(matches!(args.command, Some(cli::Command::Login)));
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::Login {
+            team: None,
+            events: false,
+            poll_interval: None
+        })
+    ));
+
+    let args = cli::Cli::parse_from(["runbeam", "login", "--team", "Platform Ops"]);
+    match args.command {
+        Some(cli::Command::Login { team, .. }) => {
+            assert_eq!(team, Some("Platform Ops".to_string()))
+        }
+        _ => panic!("Expected Login command"),
+    }
+
+    let args = cli::Cli::parse_from(["runbeam", "login", "--events"]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::Login { events: true, .. })
+    ));
+
+    let args = cli::Cli::parse_from(["runbeam", "login", "--poll-interval", "10"]);
+    match args.command {
+        Some(cli::Command::Login { poll_interval, .. }) => {
+            assert_eq!(poll_interval, Some(Duration::from_secs(10)))
+        }
+        _ => panic!("Expected Login command"),
+    }
 }
 
 #[test]
@@ -26,7 +56,18 @@ fn test_parse_logout_command() {
 #[test]
 fn test_parse_verify_command() {
     let args = cli::Cli::parse_from(["runbeam", "verify"]);
-    assert!(matches!(args.command, Some(cli::Command::Verify)));
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::Verify { timeout: None })
+    ));
+
+    let args = cli::Cli::parse_from(["runbeam", "verify", "--timeout", "3s"]);
+    match args.command {
+        Some(cli::Command::Verify { timeout }) => {
+            assert_eq!(timeout, Some(Duration::from_secs(3)))
+        }
+        _ => panic!("Expected Verify command"),
+    }
 }
 
 #[test]
@@ -83,7 +124,35 @@ fn test_parse_harmony_add_custom() {
 #[test]
 fn test_parse_harmony_list() {
     let args = cli::Cli::parse_from(["runbeam", "harmony:list"]);
-    assert!(matches!(args.command, Some(cli::Command::HarmonyList)));
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::HarmonyList {
+            tag: None,
+            json: false,
+            check_auth: false
+        })
+    ));
+}
+
+#[test]
+fn test_parse_harmony_list_json() {
+    let args = cli::Cli::parse_from(["runbeam", "harmony:list", "--json", "-t", "prod"]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::HarmonyList { json: true, tag: Some(ref t), .. }) if t == "prod"
+    ));
+}
+
+#[test]
+fn test_parse_harmony_list_check_auth() {
+    let args = cli::Cli::parse_from(["runbeam", "harmony:list", "--check-auth"]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::HarmonyList {
+            check_auth: true,
+            ..
+        })
+    ));
 }
 
 #[test]
@@ -95,6 +164,7 @@ fn test_parse_harmony_remove_by_id() {
             label,
             ip,
             port,
+            ..
         }) => {
             assert_eq!(id, Some("abc123".to_string()));
             assert_eq!(label, None);
@@ -114,6 +184,7 @@ fn test_parse_harmony_remove_by_label() {
             label,
             ip,
             port,
+            ..
         }) => {
             assert_eq!(id, None);
             assert_eq!(label, Some("my-instance".to_string()));
@@ -140,6 +211,7 @@ fn test_parse_harmony_remove_by_ip_port() {
             label,
             ip,
             port,
+            ..
         }) => {
             assert_eq!(id, None);
             assert_eq!(label, None);
@@ -150,11 +222,47 @@ fn test_parse_harmony_remove_by_ip_port() {
     }
 }
 
+#[test]
+fn test_parse_harmony_remove_stale() {
+    let args = cli::Cli::parse_from(["runbeam", "harmony:remove", "--stale"]);
+    match args.command {
+        Some(cli::Command::HarmonyRemove {
+            stale,
+            stale_timeout,
+            ..
+        }) => {
+            assert!(stale);
+            assert_eq!(stale_timeout, Duration::from_secs(3));
+        }
+        _ => panic!("Expected HarmonyRemove command"),
+    }
+
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "harmony:remove",
+        "--stale",
+        "--stale-timeout",
+        "750ms",
+    ]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::HarmonyRemove { stale_timeout, .. }) if stale_timeout == Duration::from_millis(750)
+    ));
+
+    // --stale is a bulk mode and cannot be combined with a selector
+    assert!(
+        cli::Cli::try_parse_from(["runbeam", "harmony:remove", "--stale", "--id", "abc"]).is_err()
+    );
+    assert!(
+        cli::Cli::try_parse_from(["runbeam", "harmony:remove", "--stale-timeout", "1s"]).is_err()
+    );
+}
+
 #[test]
 fn test_parse_harmony_info_by_id() {
     let args = cli::Cli::parse_from(["runbeam", "harmony:info", "--id", "abc123"]);
     match args.command {
-        Some(cli::Command::HarmonyInfo { id, label }) => {
+        Some(cli::Command::HarmonyInfo { id, label, .. }) => {
             assert_eq!(id, Some("abc123".to_string()));
             assert_eq!(label, None);
         }
@@ -172,7 +280,9 @@ fn test_parse_harmony_routes_with_json() {
         "--json",
     ]);
     match args.command {
-        Some(cli::Command::HarmonyRoutes { id, label, json }) => {
+        Some(cli::Command::HarmonyRoutes {
+            id, label, json, ..
+        }) => {
             assert_eq!(id, None);
             assert_eq!(label, Some("my-instance".to_string()));
             assert!(json);
@@ -190,7 +300,7 @@ fn test_parse_config_set() {
         "https://api.example.com",
     ]);
     match args.command {
-        Some(cli::Command::ConfigSet { key, value }) => {
+        Some(cli::Command::ConfigSet { key, value, .. }) => {
             assert_eq!(key, "api-url");
             assert_eq!(value, "https://api.example.com");
         }
@@ -198,11 +308,37 @@ fn test_parse_config_set() {
     }
 }
 
+#[test]
+fn test_parse_config_set_check_force() {
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "config:set",
+        "api-url",
+        "https://api.example.com",
+        "--check",
+        "--force",
+    ]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::ConfigSet {
+            check: true,
+            force: true,
+            ..
+        })
+    ));
+
+    // --force only makes sense together with --check
+    assert!(
+        cli::Cli::try_parse_from(["runbeam", "config:set", "api-url", "https://x", "--force"])
+            .is_err()
+    );
+}
+
 #[test]
 fn test_parse_config_get_specific() {
     let args = cli::Cli::parse_from(["runbeam", "config:get", "api-url"]);
     match args.command {
-        Some(cli::Command::ConfigGet { key }) => {
+        Some(cli::Command::ConfigGet { key, .. }) => {
             assert_eq!(key, Some("api-url".to_string()));
         }
         _ => panic!("Expected ConfigGet command"),
@@ -213,13 +349,30 @@ fn test_parse_config_get_specific() {
 fn test_parse_config_get_all() {
     let args = cli::Cli::parse_from(["runbeam", "config:get"]);
     match args.command {
-        Some(cli::Command::ConfigGet { key }) => {
+        Some(cli::Command::ConfigGet { key, env, reveal }) => {
             assert_eq!(key, None);
+            assert!(!env);
+            assert!(!reveal);
         }
         _ => panic!("Expected ConfigGet command"),
     }
 }
 
+#[test]
+fn test_parse_config_get_env() {
+    let args = cli::Cli::parse_from(["runbeam", "config:get", "--env", "--reveal"]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::ConfigGet {
+            key: None,
+            env: true,
+            reveal: true
+        })
+    ));
+    assert!(cli::Cli::try_parse_from(["runbeam", "config:get", "--reveal"]).is_err());
+    assert!(cli::Cli::try_parse_from(["runbeam", "config:get", "api-url", "--env"]).is_err());
+}
+
 #[test]
 fn test_parse_config_unset() {
     let args = cli::Cli::parse_from(["runbeam", "config:unset", "api-url"]);
@@ -231,6 +384,18 @@ fn test_parse_config_unset() {
     }
 }
 
+#[test]
+fn test_parse_config_restore() {
+    let args = cli::Cli::parse_from(["runbeam", "config:restore"]);
+    assert!(matches!(args.command, Some(cli::Command::ConfigRestore)));
+}
+
+#[test]
+fn test_parse_config_list_keys() {
+    let args = cli::Cli::parse_from(["runbeam", "config:list-keys"]);
+    assert!(matches!(args.command, Some(cli::Command::ConfigListKeys)));
+}
+
 #[test]
 fn test_parse_verbosity_flags() {
     let args = cli::Cli::parse_from(["runbeam", "-v", "list"]);
@@ -272,7 +437,7 @@ fn test_parse_test_browser_command() {
 fn test_parse_harmony_authorize_with_id() {
     let args = cli::Cli::parse_from(["runbeam", "harmony:authorize", "--id", "abc123"]);
     match args.command {
-        Some(cli::Command::HarmonyAuthorize { id, label }) => {
+        Some(cli::Command::HarmonyAuthorize { id, label, .. }) => {
             assert_eq!(id, Some("abc123".to_string()));
             assert_eq!(label, None);
         }
@@ -284,7 +449,7 @@ fn test_parse_harmony_authorize_with_id() {
 fn test_parse_harmony_authorize_with_label() {
     let args = cli::Cli::parse_from(["runbeam", "harmony:authorize", "--label", "production"]);
     match args.command {
-        Some(cli::Command::HarmonyAuthorize { id, label }) => {
+        Some(cli::Command::HarmonyAuthorize { id, label, .. }) => {
             assert_eq!(id, None);
             assert_eq!(label, Some("production".to_string()));
         }
@@ -296,7 +461,7 @@ fn test_parse_harmony_authorize_with_label() {
 fn test_parse_harmony_authorize_with_short_label() {
     let args = cli::Cli::parse_from(["runbeam", "harmony:authorize", "-l", "staging"]);
     match args.command {
-        Some(cli::Command::HarmonyAuthorize { id, label }) => {
+        Some(cli::Command::HarmonyAuthorize { id, label, .. }) => {
             assert_eq!(id, None);
             assert_eq!(label, Some("staging".to_string()));
         }
@@ -372,7 +537,7 @@ fn test_parse_harmony_delete_key() {
 fn test_parse_harmony_pipelines_with_id() {
     let args = cli::Cli::parse_from(["runbeam", "harmony:pipelines", "--id", "abc123"]);
     match args.command {
-        Some(cli::Command::HarmonyPipelines { id, label }) => {
+        Some(cli::Command::HarmonyPipelines { id, label, .. }) => {
             assert_eq!(id, Some("abc123".to_string()));
             assert_eq!(label, None);
         }
@@ -384,7 +549,7 @@ fn test_parse_harmony_pipelines_with_id() {
 fn test_parse_harmony_pipelines_with_label() {
     let args = cli::Cli::parse_from(["runbeam", "harmony:pipelines", "--label", "test-env"]);
     match args.command {
-        Some(cli::Command::HarmonyPipelines { id, label }) => {
+        Some(cli::Command::HarmonyPipelines { id, label, .. }) => {
             assert_eq!(id, None);
             assert_eq!(label, Some("test-env".to_string()));
         }
@@ -396,7 +561,7 @@ fn test_parse_harmony_pipelines_with_label() {
 fn test_parse_harmony_pipelines_with_short_label() {
     let args = cli::Cli::parse_from(["runbeam", "harmony:pipelines", "-l", "prod"]);
     match args.command {
-        Some(cli::Command::HarmonyPipelines { id, label }) => {
+        Some(cli::Command::HarmonyPipelines { id, label, .. }) => {
             assert_eq!(id, None);
             assert_eq!(label, Some("prod".to_string()));
         }
@@ -408,7 +573,7 @@ fn test_parse_harmony_pipelines_with_short_label() {
 fn test_parse_harmony_info_with_label() {
     let args = cli::Cli::parse_from(["runbeam", "harmony:info", "--label", "my-instance"]);
     match args.command {
-        Some(cli::Command::HarmonyInfo { id, label }) => {
+        Some(cli::Command::HarmonyInfo { id, label, .. }) => {
             assert_eq!(id, None);
             assert_eq!(label, Some("my-instance".to_string()));
         }
@@ -420,7 +585,9 @@ fn test_parse_harmony_info_with_label() {
 fn test_parse_harmony_routes_with_id() {
     let args = cli::Cli::parse_from(["runbeam", "harmony:routes", "--id", "route-id"]);
     match args.command {
-        Some(cli::Command::HarmonyRoutes { id, label, json }) => {
+        Some(cli::Command::HarmonyRoutes {
+            id, label, json, ..
+        }) => {
             assert_eq!(id, Some("route-id".to_string()));
             assert_eq!(label, None);
             assert!(!json, "JSON flag should be false by default");
@@ -433,7 +600,9 @@ fn test_parse_harmony_routes_with_id() {
 fn test_parse_harmony_routes_without_json() {
     let args = cli::Cli::parse_from(["runbeam", "harmony:routes", "--label", "instance"]);
     match args.command {
-        Some(cli::Command::HarmonyRoutes { id, label, json }) => {
+        Some(cli::Command::HarmonyRoutes {
+            id, label, json, ..
+        }) => {
             assert_eq!(id, None);
             assert_eq!(label, Some("instance".to_string()));
             assert!(!json, "JSON flag should be false when not specified");
@@ -463,6 +632,7 @@ fn test_parse_harmony_add_with_encryption_key() {
             label,
             path_prefix: _,
             encryption_key,
+            ..
         }) => {
             assert_eq!(ip, "10.0.0.1");
             assert_eq!(port, 9000);
@@ -477,7 +647,14 @@ fn test_parse_harmony_add_with_encryption_key() {
 fn test_parse_verbose_with_command() {
     let args = cli::Cli::parse_from(["runbeam", "-vv", "harmony:list"]);
     assert_eq!(args.verbose, 2);
-    assert!(matches!(args.command, Some(cli::Command::HarmonyList)));
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::HarmonyList {
+            tag: None,
+            json: false,
+            check_auth: false
+        })
+    ));
 }
 
 #[test]
@@ -485,7 +662,7 @@ fn test_parse_quiet_with_command() {
     let args = cli::Cli::parse_from(["runbeam", "-q", "config:get"]);
     assert!(args.quiet);
     match args.command {
-        Some(cli::Command::ConfigGet { key }) => {
+        Some(cli::Command::ConfigGet { key, .. }) => {
             assert_eq!(key, None);
         }
         _ => panic!("Expected ConfigGet command"),
@@ -524,6 +701,7 @@ fn test_parse_harmony_remove_with_all_options() {
             label,
             ip,
             port,
+            ..
         }) => {
             assert_eq!(id, Some("test-id".to_string()));
             assert_eq!(label, None);
@@ -543,10 +721,1132 @@ fn test_parse_config_set_with_complex_url() {
         "https://api-staging.example.com:8443/v2",
     ]);
     match args.command {
-        Some(cli::Command::ConfigSet { key, value }) => {
+        Some(cli::Command::ConfigSet { key, value, .. }) => {
             assert_eq!(key, "api-url");
             assert_eq!(value, "https://api-staging.example.com:8443/v2");
         }
         _ => panic!("Expected ConfigSet command"),
     }
 }
+
+#[test]
+fn test_parse_global_output_json() {
+    let args = cli::Cli::parse_from(["runbeam", "--output", "json", "list"]);
+    assert_eq!(args.output, Some(cli::OutputFormat::Json));
+
+    // Global flag is also accepted after the subcommand
+    let args = cli::Cli::parse_from(["runbeam", "harmony:list", "-o", "json"]);
+    assert_eq!(args.output, Some(cli::OutputFormat::Json));
+}
+
+#[test]
+fn test_parse_output_defaults_to_none() {
+    let args = cli::Cli::parse_from(["runbeam", "list"]);
+    assert_eq!(args.output, None);
+}
+
+#[test]
+fn test_parse_auth_backend() {
+    let args = cli::Cli::parse_from(["runbeam", "auth:backend"]);
+    assert!(matches!(args.command, Some(cli::Command::AuthBackend)));
+}
+
+#[test]
+fn test_parse_auth_refresh() {
+    let args = cli::Cli::parse_from(["runbeam", "auth:refresh"]);
+    assert!(matches!(args.command, Some(cli::Command::AuthRefresh)));
+}
+
+#[test]
+fn test_parse_token_export() {
+    let args = cli::Cli::parse_from(["runbeam", "token:export"]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::TokenExport {
+            header: false,
+            no_newline: false
+        })
+    ));
+
+    let args = cli::Cli::parse_from(["runbeam", "token:export", "--header"]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::TokenExport { header: true, .. })
+    ));
+}
+
+#[test]
+fn test_parse_token_cat_alias() {
+    let args = cli::Cli::parse_from(["runbeam", "token:cat", "--no-newline"]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::TokenExport {
+            header: false,
+            no_newline: true
+        })
+    ));
+
+    let args = cli::Cli::parse_from(cli::join_spaced_subcommand(
+        ["runbeam", "token", "cat", "-n"]
+            .map(std::ffi::OsString::from)
+            .to_vec(),
+    ));
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::TokenExport {
+            no_newline: true,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_parse_auth_token() {
+    let args = cli::Cli::parse_from(["runbeam", "auth:token", "--decode"]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::AuthToken {
+            decode: true,
+            raw: false
+        })
+    ));
+
+    // One of --decode / --raw is required
+    assert!(cli::Cli::try_parse_from(["runbeam", "auth:token"]).is_err());
+}
+
+#[test]
+fn test_parse_harmony_info_watch() {
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "harmony:info",
+        "-l",
+        "prod",
+        "--watch",
+        "--diff",
+        "--interval",
+        "5s",
+    ]);
+    match args.command {
+        Some(cli::Command::HarmonyInfo {
+            watch,
+            diff,
+            interval,
+            ..
+        }) => {
+            assert!(watch);
+            assert!(diff);
+            assert_eq!(interval, std::time::Duration::from_secs(5));
+        }
+        _ => panic!("Expected HarmonyInfo command"),
+    }
+
+    // --diff only makes sense while watching, and watching is single-instance
+    assert!(cli::Cli::try_parse_from(["runbeam", "harmony:info", "-l", "p", "--diff"]).is_err());
+    assert!(cli::Cli::try_parse_from(["runbeam", "harmony:info", "--all", "--watch"]).is_err());
+}
+
+#[test]
+fn test_parse_info_probe_all_endpoints() {
+    let args = cli::Cli::parse_from(["runbeam", "harmony:info", "--all", "--probe-all-endpoints"]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::HarmonyInfo {
+            all: true,
+            probe_all_endpoints: true,
+            ..
+        })
+    ));
+
+    assert!(
+        cli::Cli::try_parse_from([
+            "runbeam",
+            "harmony:info",
+            "-l",
+            "p",
+            "--probe-all-endpoints",
+            "--watch"
+        ])
+        .is_err()
+    );
+    assert!(
+        cli::Cli::try_parse_from([
+            "runbeam",
+            "harmony:info",
+            "-l",
+            "p",
+            "--probe-all-endpoints",
+            "--raw-body"
+        ])
+        .is_err()
+    );
+}
+
+#[test]
+fn test_parse_raw_body() {
+    let args = cli::Cli::parse_from(["runbeam", "harmony:info", "-l", "prod", "--raw-body"]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::HarmonyInfo { raw_body: true, .. })
+    ));
+
+    let args = cli::Cli::parse_from(["runbeam", "harmony:routes", "-l", "prod"]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::HarmonyRoutes {
+            raw_body: false,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_parse_info_since_version() {
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "harmony:info",
+        "-l",
+        "prod",
+        "--since-version",
+        "1.2.0",
+    ]);
+    match args.command {
+        Some(cli::Command::HarmonyInfo { since_version, .. }) => {
+            assert_eq!(since_version, Some("1.2.0".to_string()));
+        }
+        _ => panic!("Expected HarmonyInfo command"),
+    }
+}
+
+#[test]
+fn test_parse_harmony_wait() {
+    let args = cli::Cli::parse_from(["runbeam", "harmony:wait", "-l", "prod"]);
+    match args.command {
+        Some(cli::Command::HarmonyWait {
+            label,
+            timeout,
+            interval,
+            ..
+        }) => {
+            assert_eq!(label, Some("prod".to_string()));
+            assert_eq!(timeout, Duration::from_secs(60));
+            assert_eq!(interval, Duration::from_secs(2));
+        }
+        _ => panic!("Expected HarmonyWait command"),
+    }
+
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "harmony:wait",
+        "--id",
+        "abc123",
+        "--timeout",
+        "2m",
+        "--interval",
+        "500ms",
+    ]);
+    match args.command {
+        Some(cli::Command::HarmonyWait {
+            timeout, interval, ..
+        }) => {
+            assert_eq!(timeout, Duration::from_secs(120));
+            assert_eq!(interval, Duration::from_millis(500));
+        }
+        _ => panic!("Expected HarmonyWait command"),
+    }
+
+    assert!(cli::Cli::try_parse_from(["runbeam", "harmony:wait", "--timeout", "soon"]).is_err());
+}
+
+#[test]
+fn test_parse_compact_is_global() {
+    let args = cli::Cli::parse_from(["runbeam", "harmony:routes", "-l", "x", "--compact"]);
+    assert!(args.compact);
+    let args = cli::Cli::parse_from(["runbeam", "--compact", "-o", "json", "list"]);
+    assert!(args.compact);
+    assert!(!cli::Cli::parse_from(["runbeam", "list"]).compact);
+}
+
+#[test]
+fn test_parse_proxy_flags() {
+    let args = cli::Cli::parse_from(["runbeam", "--proxy", "http://proxy:3128", "harmony:list"]);
+    assert_eq!(args.proxy, Some("http://proxy:3128".to_string()));
+    assert!(!args.no_proxy);
+
+    let args = cli::Cli::parse_from(["runbeam", "harmony:list", "--no-proxy"]);
+    assert!(args.no_proxy);
+    assert_eq!(args.proxy, None);
+
+    // The two flags are mutually exclusive
+    assert!(
+        cli::Cli::try_parse_from(["runbeam", "--proxy", "http://p:1", "--no-proxy", "list"])
+            .is_err()
+    );
+}
+
+#[test]
+fn test_parse_max_time() {
+    let args = cli::Cli::parse_from(["runbeam", "--max-time", "30", "login"]);
+    assert_eq!(args.max_time, Some(Duration::from_secs(30)));
+
+    let args = cli::Cli::parse_from(["runbeam", "--max-time", "1.5s", "login"]);
+    assert_eq!(args.max_time, Some(Duration::from_millis(1500)));
+
+    let args = cli::Cli::parse_from(["runbeam", "login"]);
+    assert_eq!(args.max_time, None);
+}
+
+#[test]
+fn test_parse_retry_flags() {
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "harmony:list",
+        "--retries",
+        "4",
+        "--retry-delay",
+        "250ms",
+    ]);
+    assert_eq!(args.retries, Some(4));
+    assert_eq!(args.retry_delay, Some(Duration::from_millis(250)));
+
+    let args = cli::Cli::parse_from(["runbeam", "harmony:list"]);
+    assert_eq!(args.retries, None);
+    assert_eq!(args.retry_delay, None);
+
+    assert!(cli::Cli::try_parse_from(["runbeam", "--retries", "-1", "harmony:list"]).is_err());
+}
+
+#[test]
+fn test_parse_timings_flag() {
+    let args = cli::Cli::parse_from(["runbeam", "harmony:list", "--timings"]);
+    assert!(args.timings);
+
+    let args = cli::Cli::parse_from(["runbeam", "harmony:list"]);
+    assert!(!args.timings);
+}
+
+#[test]
+fn test_parse_harmony_authorize_print_token() {
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "harmony:authorize",
+        "-l",
+        "prod",
+        "--print-token",
+    ]);
+    match args.command {
+        Some(cli::Command::HarmonyAuthorize {
+            id,
+            label,
+            print_token,
+            force,
+        }) => {
+            assert_eq!(id, None);
+            assert_eq!(label, Some("prod".to_string()));
+            assert!(print_token);
+            assert!(!force);
+        }
+        _ => panic!("Expected HarmonyAuthorize command"),
+    }
+}
+
+#[test]
+fn test_parse_harmony_authorize_force() {
+    let args = cli::Cli::parse_from(["runbeam", "harmony:authorize", "-l", "prod", "--force"]);
+    match args.command {
+        Some(cli::Command::HarmonyAuthorize { force, .. }) => assert!(force),
+        _ => panic!("Expected HarmonyAuthorize command"),
+    }
+}
+
+#[test]
+fn test_parse_global_no_migrate() {
+    let args = cli::Cli::parse_from(["runbeam", "verify", "--no-migrate"]);
+    assert!(args.no_migrate);
+
+    let args = cli::Cli::parse_from(["runbeam", "verify"]);
+    assert!(!args.no_migrate);
+}
+
+#[test]
+fn test_parse_global_offline() {
+    let args = cli::Cli::parse_from(["runbeam", "harmony:list", "--offline"]);
+    assert!(args.offline);
+    let args = cli::Cli::parse_from(["runbeam", "--no-network", "verify"]);
+    assert!(args.offline);
+    let args = cli::Cli::parse_from(["runbeam", "verify"]);
+    assert!(!args.offline);
+}
+
+#[test]
+fn test_parse_harmony_add_headers() {
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "harmony:add",
+        "-H",
+        "X-Api-Key: secret",
+        "--header",
+        "X-Env=prod",
+    ]);
+    match args.command {
+        Some(cli::Command::HarmonyAdd { headers, .. }) => {
+            assert_eq!(headers, vec!["X-Api-Key: secret", "X-Env=prod"]);
+        }
+        _ => panic!("Expected HarmonyAdd command"),
+    }
+}
+
+#[test]
+fn test_parse_harmony_add_scheme() {
+    let args = cli::Cli::parse_from(["runbeam", "harmony:add", "--scheme", "https"]);
+    match args.command {
+        Some(cli::Command::HarmonyAdd { scheme, .. }) => {
+            assert_eq!(scheme, Some("https".to_string()))
+        }
+        _ => panic!("Expected HarmonyAdd command"),
+    }
+    let args = cli::Cli::parse_from(["runbeam", "harmony:add"]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::HarmonyAdd { scheme: None, .. })
+    ));
+}
+
+#[test]
+fn test_parse_harmony_add_test_and_force() {
+    let args = cli::Cli::parse_from(["runbeam", "harmony:add", "--test", "--force"]);
+    match args.command {
+        Some(cli::Command::HarmonyAdd { test, force, .. }) => {
+            assert!(test);
+            assert!(force);
+        }
+        _ => panic!("Expected HarmonyAdd command"),
+    }
+
+    // --force also silences the duplicate-address warning, so it stands alone
+    assert!(cli::Cli::try_parse_from(["runbeam", "harmony:add", "--force"]).is_ok());
+}
+
+#[test]
+fn test_parse_harmony_add_clone_from() {
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "harmony:add",
+        "-i",
+        "10.0.0.7",
+        "-l",
+        "edge-2",
+        "--clone-from",
+        "abc123",
+    ]);
+    match args.command {
+        Some(cli::Command::HarmonyAdd { clone_from, .. }) => {
+            assert_eq!(clone_from.as_deref(), Some("abc123"));
+        }
+        _ => panic!("Expected HarmonyAdd command"),
+    }
+
+    // The prefix comes from the source instance
+    assert!(
+        cli::Cli::try_parse_from([
+            "runbeam",
+            "harmony:add",
+            "--clone-from",
+            "abc123",
+            "-x",
+            "mgmt",
+        ])
+        .is_err()
+    );
+}
+
+#[test]
+fn test_parse_harmony_set_and_unset_header() {
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "harmony:set-header",
+        "--id",
+        "abc123",
+        "-H",
+        "X-Api-Key: secret",
+    ]);
+    match args.command {
+        Some(cli::Command::HarmonySetHeader { id, header }) => {
+            assert_eq!(id, "abc123");
+            assert_eq!(header, "X-Api-Key: secret");
+        }
+        _ => panic!("Expected HarmonySetHeader command"),
+    }
+
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "harmony:unset-header",
+        "--id",
+        "abc123",
+        "--name",
+        "x-api-key",
+    ]);
+    match args.command {
+        Some(cli::Command::HarmonyUnsetHeader { id, name }) => {
+            assert_eq!(id, "abc123");
+            assert_eq!(name, "x-api-key");
+        }
+        _ => panic!("Expected HarmonyUnsetHeader command"),
+    }
+}
+
+#[test]
+fn test_parse_harmony_list_tag() {
+    let args = cli::Cli::parse_from(["runbeam", "harmony:list", "--tag", "prod"]);
+    match args.command {
+        Some(cli::Command::HarmonyList { tag, .. }) => assert_eq!(tag, Some("prod".to_string())),
+        _ => panic!("Expected HarmonyList command"),
+    }
+}
+
+#[test]
+fn test_parse_harmony_tag_and_untag() {
+    let args = cli::Cli::parse_from(["runbeam", "harmony:tag", "--id", "abc123", "prod", "eu"]);
+    match args.command {
+        Some(cli::Command::HarmonyTag { id, tags }) => {
+            assert_eq!(id, "abc123");
+            assert_eq!(tags, vec!["prod", "eu"]);
+        }
+        _ => panic!("Expected HarmonyTag command"),
+    }
+
+    let args = cli::Cli::parse_from(["runbeam", "harmony:untag", "--id", "abc123", "eu"]);
+    match args.command {
+        Some(cli::Command::HarmonyUntag { id, tags }) => {
+            assert_eq!(id, "abc123");
+            assert_eq!(tags, vec!["eu"]);
+        }
+        _ => panic!("Expected HarmonyUntag command"),
+    }
+
+    // At least one tag is required
+    assert!(cli::Cli::try_parse_from(["runbeam", "harmony:tag", "--id", "abc123"]).is_err());
+}
+
+#[test]
+fn test_parse_harmony_copy() {
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "harmony:copy",
+        "--from",
+        "prod-1",
+        "-l",
+        "prod-2",
+        "-i",
+        "10.0.0.6",
+    ]);
+    match args.command {
+        Some(cli::Command::HarmonyCopy {
+            from_id_or_label,
+            new_label,
+            ip,
+            port,
+        }) => {
+            assert_eq!(from_id_or_label, "prod-1");
+            assert_eq!(new_label, "prod-2");
+            assert_eq!(ip, Some("10.0.0.6".to_string()));
+            assert_eq!(port, None);
+        }
+        _ => panic!("Expected HarmonyCopy command"),
+    }
+}
+
+#[test]
+fn test_parse_harmony_rename_by_label() {
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "harmony:rename-by-label",
+        "-l",
+        "old-name",
+        "--to",
+        "new-name",
+    ]);
+    match args.command {
+        Some(cli::Command::HarmonyRenameByLabel { label, to }) => {
+            assert_eq!(label, "old-name");
+            assert_eq!(to, "new-name");
+        }
+        _ => panic!("Expected HarmonyRenameByLabel command"),
+    }
+}
+
+#[test]
+fn test_blank_labels_are_rejected() {
+    for blank in ["", "   "] {
+        for args in [
+            vec![
+                "runbeam",
+                "harmony:rename-by-label",
+                "-l",
+                "old",
+                "--to",
+                blank,
+            ],
+            vec!["runbeam", "harmony:add", "-l", blank],
+            vec!["runbeam", "harmony:copy", "--from", "old", "-l", blank],
+        ] {
+            let err = cli::Cli::try_parse_from(&args).unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains("the label must not be empty or only whitespace"),
+                "{:?}",
+                args
+            );
+        }
+    }
+}
+
+#[test]
+fn test_parse_doctor_fix() {
+    let args = cli::Cli::parse_from(["runbeam", "doctor"]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::Doctor { fix: false })
+    ));
+    assert!(!args.yes);
+
+    // --yes is global, so it still works after the subcommand
+    let args = cli::Cli::parse_from(["runbeam", "doctor", "--fix", "-y"]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::Doctor { fix: true })
+    ));
+    assert!(args.yes);
+
+    let args = cli::Cli::parse_from(["runbeam", "--yes", "doctor", "--fix"]);
+    assert!(args.yes);
+}
+
+#[test]
+fn test_parse_harmony_routes_columns() {
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "harmony:routes",
+        "--id",
+        "abcd1234",
+        "--columns",
+        "path,methods,pipeline",
+    ]);
+    match args.command {
+        Some(cli::Command::HarmonyRoutes { columns, .. }) => {
+            assert_eq!(columns, vec!["path", "methods", "pipeline"]);
+        }
+        _ => panic!("Expected HarmonyRoutes command"),
+    }
+
+    let args = cli::Cli::parse_from(["runbeam", "harmony:info", "--id", "abcd1234"]);
+    match args.command {
+        Some(cli::Command::HarmonyInfo { columns, .. }) => assert!(columns.is_empty()),
+        _ => panic!("Expected HarmonyInfo command"),
+    }
+}
+
+#[test]
+fn test_parse_harmony_set_prefix_and_scheme() {
+    let args = cli::Cli::parse_from(["runbeam", "harmony:set-prefix", "--id", "abc123", "api"]);
+    match args.command {
+        Some(cli::Command::HarmonySetPrefix { id, prefix }) => {
+            assert_eq!(id, "abc123");
+            assert_eq!(prefix, "api");
+        }
+        _ => panic!("Expected HarmonySetPrefix command"),
+    }
+
+    let args = cli::Cli::parse_from(["runbeam", "harmony:set-scheme", "--id", "abc123", "https"]);
+    match args.command {
+        Some(cli::Command::HarmonySetScheme { id, scheme }) => {
+            assert_eq!(id, "abc123");
+            assert_eq!(scheme, "https");
+        }
+        _ => panic!("Expected HarmonySetScheme command"),
+    }
+}
+
+fn spaced(args: &[&str]) -> Vec<String> {
+    cli::join_spaced_subcommand(args.iter().map(|a| a.into()).collect())
+        .into_iter()
+        .map(|a| a.into_string().unwrap())
+        .collect()
+}
+
+#[test]
+fn test_join_spaced_subcommand() {
+    assert_eq!(
+        spaced(&["runbeam", "harmony", "list", "--tag", "prod"]),
+        ["runbeam", "harmony:list", "--tag", "prod"]
+    );
+    // Global options before the command, including ones that take a value
+    assert_eq!(
+        spaced(&[
+            "runbeam",
+            "-v",
+            "-o",
+            "json",
+            "--max-time",
+            "5",
+            "config",
+            "get"
+        ]),
+        [
+            "runbeam",
+            "-v",
+            "-o",
+            "json",
+            "--max-time",
+            "5",
+            "config:get"
+        ]
+    );
+    assert_eq!(
+        spaced(&["runbeam", "--output=json", "auth", "backend"]),
+        ["runbeam", "--output=json", "auth:backend"]
+    );
+
+    // Canonical and unknown forms are left alone
+    assert_eq!(
+        spaced(&["runbeam", "harmony:list"]),
+        ["runbeam", "harmony:list"]
+    );
+    assert_eq!(
+        spaced(&["runbeam", "harmony", "nope"]),
+        ["runbeam", "harmony", "nope"]
+    );
+    assert_eq!(
+        spaced(&["runbeam", "config:set", "api-url", "x"]),
+        ["runbeam", "config:set", "api-url", "x"]
+    );
+}
+
+#[test]
+fn test_parse_spaced_subcommand() {
+    let args = cli::Cli::parse_from(spaced(&["runbeam", "harmony", "info", "--id", "abc123"]));
+    match args.command {
+        Some(cli::Command::HarmonyInfo { id, .. }) => assert_eq!(id, Some("abc123".to_string())),
+        _ => panic!("Expected HarmonyInfo command"),
+    }
+}
+
+#[test]
+fn test_parse_all_and_fail_fast() {
+    let args = cli::Cli::parse_from(["runbeam", "harmony:reload", "--all", "--fail-fast"]);
+    match args.command {
+        Some(cli::Command::HarmonyReload { all, fail_fast, .. }) => {
+            assert!(all);
+            assert!(fail_fast);
+        }
+        _ => panic!("Expected HarmonyReload command"),
+    }
+
+    assert!(cli::Cli::try_parse_from(["runbeam", "harmony:info", "--all", "--id", "x"]).is_err());
+    assert!(cli::Cli::try_parse_from(["runbeam", "harmony:info", "--fail-fast"]).is_err());
+}
+
+#[test]
+fn test_parse_batch_tag() {
+    let args = cli::Cli::parse_from(["runbeam", "harmony:reload", "--all", "--tag", "prod"]);
+    match args.command {
+        Some(cli::Command::HarmonyReload { all, tag, .. }) => {
+            assert!(all);
+            assert_eq!(tag.as_deref(), Some("prod"));
+        }
+        _ => panic!("Expected HarmonyReload command"),
+    }
+
+    let args = cli::Cli::parse_from(["runbeam", "harmony:info", "--group", "eu", "-t", "prod"]);
+    match args.command {
+        Some(cli::Command::HarmonyInfo { group, tag, .. }) => {
+            assert_eq!(group.as_deref(), Some("eu"));
+            assert_eq!(tag.as_deref(), Some("prod"));
+        }
+        _ => panic!("Expected HarmonyInfo command"),
+    }
+
+    let args = cli::Cli::parse_from(["runbeam", "harmony:update", "--group", "eu", "-t", "prod"]);
+    match args.command {
+        Some(cli::Command::HarmonyUpdate { group, tag, .. }) => {
+            assert_eq!(group.as_deref(), Some("eu"));
+            assert_eq!(tag.as_deref(), Some("prod"));
+        }
+        _ => panic!("Expected HarmonyUpdate command"),
+    }
+
+    // --tag narrows a batch run, so it needs --all or --group
+    assert!(cli::Cli::try_parse_from(["runbeam", "harmony:reload", "--tag", "prod"]).is_err());
+    assert!(cli::Cli::try_parse_from(["runbeam", "harmony:info", "--tag", "prod"]).is_err());
+}
+
+#[test]
+fn test_parse_group_batch() {
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "harmony:reload",
+        "--group",
+        "us-east",
+        "--fail-fast",
+    ]);
+    match args.command {
+        Some(cli::Command::HarmonyReload {
+            all,
+            group,
+            fail_fast,
+            ..
+        }) => {
+            assert!(!all);
+            assert_eq!(group.as_deref(), Some("us-east"));
+            assert!(fail_fast);
+        }
+        _ => panic!("Expected HarmonyReload command"),
+    }
+
+    assert!(
+        cli::Cli::try_parse_from(["runbeam", "harmony:info", "--group", "g", "--all"]).is_err()
+    );
+    assert!(
+        cli::Cli::try_parse_from(["runbeam", "harmony:info", "--group", "g", "--id", "x"]).is_err()
+    );
+}
+
+#[test]
+fn test_parse_harmony_group_actions() {
+    let args = cli::Cli::parse_from(["runbeam", "harmony:group", "add", "us-east", "-l", "prod"]);
+    match args.command {
+        Some(cli::Command::HarmonyGroup {
+            action: cli::GroupAction::Add { name, id, label },
+        }) => {
+            assert_eq!(name, "us-east");
+            assert_eq!(id, None);
+            assert_eq!(label.as_deref(), Some("prod"));
+        }
+        _ => panic!("Expected HarmonyGroup add"),
+    }
+
+    let args = cli::Cli::parse_from(["runbeam", "harmony:group", "list"]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::HarmonyGroup {
+            action: cli::GroupAction::List { name: None }
+        })
+    ));
+
+    // add/remove need an instance selector
+    assert!(cli::Cli::try_parse_from(["runbeam", "harmony:group", "remove", "us-east"]).is_err());
+}
+
+#[test]
+fn test_parse_harmony_reload_wait() {
+    let args = cli::Cli::parse_from(["runbeam", "harmony:reload", "-l", "prod", "--wait"]);
+    match args.command {
+        Some(cli::Command::HarmonyReload {
+            wait, wait_timeout, ..
+        }) => {
+            assert!(wait);
+            assert_eq!(wait_timeout, Duration::from_secs(60));
+        }
+        _ => panic!("Expected HarmonyReload command"),
+    }
+
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "harmony:reload",
+        "--all",
+        "--wait",
+        "--wait-timeout",
+        "2m",
+    ]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::HarmonyReload { wait_timeout, .. }) if wait_timeout == Duration::from_secs(120)
+    ));
+    assert!(
+        cli::Cli::try_parse_from(["runbeam", "harmony:reload", "--wait-timeout", "5s"]).is_err()
+    );
+}
+
+#[test]
+fn test_parse_harmony_routes_stream() {
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "-o",
+        "json",
+        "harmony:routes",
+        "--id",
+        "abc123",
+        "--stream",
+    ]);
+    assert_eq!(args.output, Some(cli::OutputFormat::Json));
+    match args.command {
+        Some(cli::Command::HarmonyRoutes { stream, json, .. }) => {
+            assert!(stream);
+            assert!(!json);
+        }
+        _ => panic!("Expected HarmonyRoutes command"),
+    }
+}
+
+#[test]
+fn test_parse_harmony_routes_sort() {
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "harmony:routes",
+        "-l",
+        "prod",
+        "--sort",
+        "endpoint_name",
+        "--reverse",
+    ]);
+    match args.command {
+        Some(cli::Command::HarmonyRoutes { sort, reverse, .. }) => {
+            assert_eq!(sort, Some(cli::RouteSort::EndpointName));
+            assert_eq!(sort.unwrap().field(), "endpoint_name");
+            assert!(reverse);
+        }
+        _ => panic!("Expected HarmonyRoutes command"),
+    }
+
+    assert!(cli::Cli::try_parse_from(["runbeam", "harmony:routes", "--sort", "methods"]).is_err());
+    assert!(cli::Cli::try_parse_from(["runbeam", "harmony:routes", "--reverse"]).is_err());
+}
+
+#[test]
+fn test_parse_routes_count_only() {
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "harmony:routes",
+        "-l",
+        "x",
+        "--count-only",
+        "--endpoint",
+        "fhir",
+    ]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::HarmonyRoutes {
+            count_only: true,
+            ..
+        })
+    ));
+    assert!(
+        cli::Cli::try_parse_from(["runbeam", "harmony:routes", "--count-only", "--stream"])
+            .is_err()
+    );
+}
+
+#[test]
+fn test_parse_harmony_routes_endpoint() {
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "harmony:routes",
+        "-l",
+        "prod",
+        "--endpoint",
+        "fhir",
+    ]);
+    match args.command {
+        Some(cli::Command::HarmonyRoutes { endpoint, .. }) => {
+            assert_eq!(endpoint, Some("fhir".to_string()));
+        }
+        _ => panic!("Expected HarmonyRoutes command"),
+    }
+    // The detail view has no columns to pick and is not streamed
+    assert!(
+        cli::Cli::try_parse_from([
+            "runbeam",
+            "harmony:routes",
+            "--endpoint",
+            "fhir",
+            "--columns",
+            "path"
+        ])
+        .is_err()
+    );
+}
+
+#[test]
+fn test_subcommand_has_args_ignores_defaults_and_globals() {
+    use clap::CommandFactory;
+
+    let has_args = |argv: &[&str]| {
+        let matches = cli::Cli::command().get_matches_from(argv);
+        cli::subcommand_has_args(&matches, "harmony:add")
+    };
+    assert!(!has_args(&["runbeam", "harmony:add"]));
+    assert!(!has_args(&["runbeam", "-v", "harmony:add", "--offline"]));
+    assert!(has_args(&["runbeam", "harmony:add", "-p", "8081"]));
+    assert!(has_args(&["runbeam", "harmony:add", "--test"]));
+    assert!(!has_args(&["runbeam", "harmony:list", "-t", "prod"]));
+}
+
+#[test]
+fn test_parse_port_range() {
+    assert_eq!(cli::parse_port_range("8081-8084"), Ok((8081, 8084)));
+    assert_eq!(cli::parse_port_range("9000-9000"), Ok((9000, 9000)));
+    assert_eq!(cli::parse_port_range("1-100"), Ok((1, 100)));
+    for bad in ["8081", "8084-8081", "0-5", "8081-70000", "a-b", "-8081"] {
+        let err = cli::parse_port_range(bad).unwrap_err();
+        assert!(err.contains("expected START-END"), "{}: {}", bad, err);
+    }
+    let err = cli::parse_port_range("1-101").unwrap_err();
+    assert!(err.contains("covers 101 ports; at most 100"), "{}", err);
+
+    let args = cli::Cli::parse_from(["runbeam", "harmony:add", "--port-range", "8081-8082"]);
+    assert!(matches!(
+        args.command,
+        Some(cli::Command::HarmonyAdd {
+            port_range: Some((8081, 8082)),
+            ..
+        })
+    ));
+    assert!(
+        cli::Cli::try_parse_from(["runbeam", "harmony:add", "-p", "1", "--port-range", "2-3"])
+            .is_err()
+    );
+}
+
+#[test]
+fn test_parse_trace_id() {
+    assert_eq!(
+        cli::parse_trace_id("ticket-4711"),
+        Ok("ticket-4711".to_string())
+    );
+    for bad in ["", "has space", "caf\u{e9}", &"x".repeat(129)] {
+        assert!(cli::parse_trace_id(bad).is_err(), "{:?}", bad);
+    }
+    let args = cli::Cli::parse_from(["runbeam", "harmony:list", "--trace-id", "abc"]);
+    assert_eq!(args.trace_id.as_deref(), Some("abc"));
+}
+
+#[test]
+fn test_parse_connect_via() {
+    for ok in [
+        "bastion",
+        "ops@bastion.example.com",
+        "ssh://ops@bastion:2222",
+    ] {
+        assert_eq!(cli::parse_connect_via(ok), Ok(ok.to_string()));
+    }
+    for bad in ["", "-oProxyCommand=x", "ops@ bastion"] {
+        assert!(cli::parse_connect_via(bad).is_err(), "{:?}", bad);
+    }
+    let args = cli::Cli::parse_from(["runbeam", "harmony:info", "-l", "a", "--connect-via", "b"]);
+    assert_eq!(args.connect_via.as_deref(), Some("b"));
+}
+
+#[test]
+fn test_parse_resolve() {
+    use std::net::IpAddr;
+
+    let v4: IpAddr = "10.0.0.5".parse().unwrap();
+    let v6: IpAddr = "::1".parse().unwrap();
+    assert_eq!(
+        cli::parse_resolve("Harmony.Internal:10.0.0.5"),
+        Ok(("harmony.internal".to_string(), v4))
+    );
+    assert_eq!(cli::parse_resolve("h:[::1]"), Ok(("h".to_string(), v6)));
+    assert_eq!(cli::parse_resolve("h:::1"), Ok(("h".to_string(), v6)));
+    for bad in [
+        "harmony.internal",
+        ":10.0.0.5",
+        "h:not-an-ip",
+        "h:10.0.0.5:8081",
+    ] {
+        let err = cli::parse_resolve(bad).unwrap_err();
+        assert!(err.contains("expected HOST:IP"), "{}: {}", bad, err);
+    }
+
+    let args = cli::Cli::parse_from([
+        "runbeam",
+        "--resolve",
+        "a:10.0.0.5",
+        "--resolve",
+        "b:::1",
+        "list",
+    ]);
+    assert_eq!(args.resolve.len(), 2);
+}
+
+#[test]
+fn test_parse_duration_units() {
+    assert_eq!(cli::parse_duration("500ms"), Ok(Duration::from_millis(500)));
+    assert_eq!(cli::parse_duration("30s"), Ok(Duration::from_secs(30)));
+    assert_eq!(cli::parse_duration("2m"), Ok(Duration::from_secs(120)));
+    assert_eq!(cli::parse_duration("0.25s"), Ok(Duration::from_millis(250)));
+    assert_eq!(cli::parse_duration("45"), Ok(Duration::from_secs(45)));
+
+    for bad in ["", "s", "10h", "1.2.3s", "-5s", "ten"] {
+        let err = cli::parse_duration(bad).unwrap_err();
+        assert!(
+            err.contains("expected a number with an optional unit"),
+            "{}",
+            bad
+        );
+    }
+}
+
+#[test]
+fn test_format_duration_round_trips() {
+    assert_eq!(cli::format_duration(Duration::from_secs(5)), "5s");
+    assert_eq!(cli::format_duration(Duration::from_millis(10)), "10ms");
+    assert_eq!(cli::format_duration(Duration::from_millis(1500)), "1.5s");
+    assert_eq!(cli::format_duration(Duration::ZERO), "0s");
+    for d in [
+        Duration::from_secs(90),
+        Duration::from_millis(250),
+        Duration::from_millis(2001),
+    ] {
+        assert_eq!(cli::parse_duration(&cli::format_duration(d)), Ok(d));
+    }
+}
+
+#[test]
+fn test_resolve_output() {
+    use cli::OutputFormat::{Json, Table};
+
+    let args = cli::Cli::parse_from(["runbeam", "-o", "table", "list"]);
+    assert_eq!(args.output, Some(Table));
+
+    // An explicit --output always wins
+    assert_eq!(
+        cli::resolve_output(Some(Table), Some("json"), false),
+        Ok(Table)
+    );
+    assert_eq!(cli::resolve_output(Some(Json), None, true), Ok(Json));
+    // Otherwise RUNBEAM_OUTPUT, then terminal detection
+    assert_eq!(cli::resolve_output(None, Some("TABLE"), false), Ok(Table));
+    assert_eq!(cli::resolve_output(None, Some(""), true), Ok(Table));
+    assert_eq!(cli::resolve_output(None, None, true), Ok(Table));
+    assert_eq!(cli::resolve_output(None, None, false), Ok(Json));
+    assert!(cli::resolve_output(None, Some("yaml"), true).is_err());
+    assert_eq!(
+        cli::resolve_output(None, Some("markdown"), false),
+        Ok(cli::OutputFormat::Markdown)
+    );
+}
+
+#[test]
+fn test_examples_only_in_long_help() {
+    use clap::CommandFactory;
+
+    let mut cmd = cli::Cli::command();
+    for name in [
+        "harmony:add",
+        "harmony:remove",
+        "harmony:authorize",
+        "config:set",
+    ] {
+        let sub = cmd.find_subcommand_mut(name).unwrap();
+        let long = sub.render_long_help().to_string();
+        let short = sub.render_help().to_string();
+        assert!(long.contains("Examples:"), "{} --help", name);
+        assert!(
+            long.contains(&format!("runbeam {}", name)),
+            "{} --help",
+            name
+        );
+        assert!(!short.contains("Examples:"), "{} -h", name);
+    }
+}
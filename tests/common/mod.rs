@@ -2,10 +2,12 @@
 ///
 /// This module provides shared functionality for test setup, mocking,
 /// and assertions to reduce code duplication across test files.
+use assert_cmd::Command;
+use assert_cmd::assert::Assert;
 use serde_json::Value;
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tempfile::TempDir;
 
 /// Test environment handle that provides isolated filesystem and environment
@@ -55,6 +57,59 @@ impl TestEnv {
         }
     }
 
+    /// Create an isolated HOME for running the `runbeam` binary
+    ///
+    /// Unlike [`TestEnv::new`], the test process environment is left untouched
+    /// (HOME is only passed to the commands from [`TestEnv::command`]), so these
+    /// tests can run in parallel. `~/.runbeam` is not created up front.
+    #[allow(dead_code)]
+    pub fn cli() -> Self {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let data_dir = temp_dir.path().join(".runbeam");
+        TestEnv {
+            temp_dir,
+            data_dir,
+            original_env: Vec::new(),
+        }
+    }
+
+    /// The HOME directory of this environment
+    #[allow(dead_code)]
+    pub fn home(&self) -> &Path {
+        self.temp_dir.path()
+    }
+
+    /// A `runbeam` command with HOME pointing at this environment
+    ///
+    /// Tokens are kept in the encrypted file store under HOME
+    /// (`RUNBEAM_STORAGE_BACKEND=file`), so tests never read or write the OS keyring
+    /// of the developer running them; tests may override it.
+    #[allow(dead_code)]
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::cargo_bin("runbeam").expect("runbeam binary");
+        cmd.env("HOME", self.home())
+            .env("RUNBEAM_STORAGE_BACKEND", "file");
+        cmd
+    }
+
+    /// Run `runbeam --no-proxy <args>` in this environment
+    ///
+    /// `--no-proxy` keeps a proxy set in the developer's environment away from the
+    /// mock servers; use [`TestEnv::command`] for tests about proxies.
+    #[allow(dead_code)]
+    pub fn run(&self, args: &[&str]) -> Assert {
+        self.command().arg("--no-proxy").args(args).assert()
+    }
+
+    /// Register `server` as the instance `label` on 127.0.0.1, returning its port
+    #[allow(dead_code)]
+    pub fn register_mock_instance(&self, server: &mockito::Server, label: &str) -> String {
+        let port = mock_port(server);
+        self.run(&["harmony:add", "-i", "127.0.0.1", "-p", &port, "-l", label])
+            .success();
+        port
+    }
+
     /// Get the path to the data directory (~/.runbeam equivalent)
     pub fn data_path(&self) -> &PathBuf {
         &self.data_dir
@@ -246,7 +301,7 @@ pub fn assert_json_array_structure(json_array: &Value, expected_fields: &[&str])
     for (i, item) in array.iter().enumerate() {
         let obj = item
             .as_object()
-            .expect(&format!("Array item {} should be an object", i));
+            .unwrap_or_else(|| panic!("Array item {} should be an object", i));
 
         for field in expected_fields {
             assert!(
@@ -299,6 +354,17 @@ pub fn create_mock_info_response() -> Value {
     })
 }
 
+/// The port a mockito server listens on
+#[allow(dead_code)]
+pub fn mock_port(server: &mockito::Server) -> String {
+    server
+        .host_with_port()
+        .rsplit(':')
+        .next()
+        .expect("mock server address has a port")
+        .to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
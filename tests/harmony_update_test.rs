@@ -3,10 +3,11 @@ use predicates::prelude::*;
 
 // Import CLI and storage modules for testing
 #[path = "../src/cli.rs"]
+#[allow(dead_code)] // Argument helpers are used by the main binary, not these tests
 mod cli;
 
 #[path = "../src/storage.rs"]
-#[allow(dead_code)]  // Storage functions are used in main binary, not in these tests
+#[allow(dead_code)] // Storage functions are used in main binary, not in these tests
 mod storage;
 
 #[test]
@@ -14,7 +15,7 @@ fn test_harmony_update_command_exists() {
     // Test that the command is registered
     let mut cmd = Command::cargo_bin("runbeam").unwrap();
     cmd.arg("--help");
-    
+
     cmd.assert()
         .success()
         .stdout(predicate::str::contains("harmony:update"));
@@ -24,11 +25,13 @@ fn test_harmony_update_command_exists() {
 fn test_harmony_update_help() {
     // Test the help text for harmony:update
     let mut cmd = Command::cargo_bin("runbeam").unwrap();
-    cmd.args(&["harmony:update", "--help"]);
-    
+    cmd.args(["harmony:update", "--help"]);
+
     cmd.assert()
         .success()
-        .stdout(predicate::str::contains("Trigger Harmony to upload its configuration to Runbeam Cloud"))
+        .stdout(predicate::str::contains(
+            "Trigger Harmony to upload its configuration to Runbeam Cloud",
+        ))
         .stdout(predicate::str::contains("--id"))
         .stdout(predicate::str::contains("--label"));
 }
@@ -38,44 +41,44 @@ fn test_harmony_update_requires_id_or_label() {
     // Test that command fails without --id or --label
     let mut cmd = Command::cargo_bin("runbeam").unwrap();
     cmd.arg("harmony:update");
-    
-    cmd.assert()
-        .failure()
-        .stderr(predicate::str::contains("must supply --id or --label")
-            .or(predicate::str::contains("No Harmony instances registered")));
+
+    cmd.assert().failure().stderr(
+        predicate::str::contains("must supply --id or --label")
+            .or(predicate::str::contains("No Harmony instances registered")),
+    );
 }
 
 #[test]
 fn test_harmony_update_conflicts_id_and_label() {
     // Test that --id and --label are mutually exclusive
     let mut cmd = Command::cargo_bin("runbeam").unwrap();
-    cmd.args(&["harmony:update", "--id", "abc123", "--label", "test"]);
-    
-    cmd.assert()
-        .failure()
-        .stderr(predicate::str::contains("cannot be used with")
-            .or(predicate::str::contains("conflicts with")));
+    cmd.args(["harmony:update", "--id", "abc123", "--label", "test"]);
+
+    cmd.assert().failure().stderr(
+        predicate::str::contains("cannot be used with")
+            .or(predicate::str::contains("conflicts with")),
+    );
 }
 
 #[test]
 fn test_harmony_update_nonexistent_instance() {
     // Test error when instance doesn't exist
     let mut cmd = Command::cargo_bin("runbeam").unwrap();
-    cmd.args(&["harmony:update", "--id", "nonexistent"]);
-    
-    cmd.assert()
-        .failure()
-        .stderr(predicate::str::contains("no instance")
+    cmd.args(["harmony:update", "--id", "nonexistent"]);
+
+    cmd.assert().failure().stderr(
+        predicate::str::contains("no instance")
             .or(predicate::str::contains("not found"))
-            .or(predicate::str::contains("No Harmony instances registered")));
+            .or(predicate::str::contains("No Harmony instances registered")),
+    );
 }
 
 #[test]
 fn test_harmony_update_with_verbose() {
     // Test that verbose flag works
     let mut cmd = Command::cargo_bin("runbeam").unwrap();
-    cmd.args(&["-v", "harmony:update", "--help"]);
-    
+    cmd.args(["-v", "harmony:update", "--help"]);
+
     cmd.assert().success();
 }
 
@@ -83,8 +86,8 @@ fn test_harmony_update_with_verbose() {
 fn test_harmony_update_with_quiet() {
     // Test that quiet flag works
     let mut cmd = Command::cargo_bin("runbeam").unwrap();
-    cmd.args(&["-q", "harmony:update", "--help"]);
-    
+    cmd.args(["-q", "harmony:update", "--help"]);
+
     cmd.assert().success();
 }
 
@@ -96,9 +99,9 @@ fn test_harmony_update_cli_parsing() {
     // Test parsing with --id
     let args = vec!["runbeam", "harmony:update", "--id", "test123"];
     let cli = cli::Cli::parse_from(args);
-    
+
     match cli.command {
-        Some(cli::Command::HarmonyUpdate { id, label }) => {
+        Some(cli::Command::HarmonyUpdate { id, label, .. }) => {
             assert_eq!(id, Some("test123".to_string()));
             assert_eq!(label, None);
         }
@@ -108,9 +111,9 @@ fn test_harmony_update_cli_parsing() {
     // Test parsing with --label
     let args = vec!["runbeam", "harmony:update", "--label", "my-harmony"];
     let cli = cli::Cli::parse_from(args);
-    
+
     match cli.command {
-        Some(cli::Command::HarmonyUpdate { id, label }) => {
+        Some(cli::Command::HarmonyUpdate { id, label, .. }) => {
             assert_eq!(id, None);
             assert_eq!(label, Some("my-harmony".to_string()));
         }
@@ -120,27 +123,49 @@ fn test_harmony_update_cli_parsing() {
     // Test parsing with -l shorthand
     let args = vec!["runbeam", "harmony:update", "-l", "test"];
     let cli = cli::Cli::parse_from(args);
-    
+
     match cli.command {
-        Some(cli::Command::HarmonyUpdate { id, label }) => {
+        Some(cli::Command::HarmonyUpdate { id, label, .. }) => {
             assert_eq!(id, None);
             assert_eq!(label, Some("test".to_string()));
         }
         _ => panic!("Expected HarmonyUpdate command"),
     }
+
+    // Test parsing with --all --fail-fast
+    let args = vec!["runbeam", "harmony:update", "--all", "--fail-fast"];
+    let cli = cli::Cli::parse_from(args);
+
+    match cli.command {
+        Some(cli::Command::HarmonyUpdate {
+            id,
+            all,
+            group,
+            fail_fast,
+            ..
+        }) => {
+            assert_eq!(id, None);
+            assert!(all);
+            assert_eq!(group, None);
+            assert!(fail_fast);
+        }
+        _ => panic!("Expected HarmonyUpdate command"),
+    }
+
+    // --all cannot be combined with --id, and --fail-fast needs --all or --group
+    assert!(cli::Cli::try_parse_from(["runbeam", "harmony:update", "--all", "--id", "x"]).is_err());
+    assert!(cli::Cli::try_parse_from(["runbeam", "harmony:update", "--id", "x", "--fail-fast"]).is_err());
 }
 
 /// Test URL construction in management module
 #[test]
 fn test_update_url_construction() {
-
     let instance = storage::HarmonyInstance {
         id: "test123".to_string(),
         ip: "127.0.0.1".to_string(),
         port: 9090,
         label: "test-harmony".to_string(),
-        path_prefix: "admin".to_string(),
-        gateway_id: None,
+        ..Default::default()
     };
 
     // Verify the expected URL format
@@ -161,7 +186,7 @@ fn test_update_url_construction() {
         port: 8080,
         label: "test2".to_string(),
         path_prefix: "/admin/".to_string(),
-        gateway_id: None,
+        ..Default::default()
     };
 
     let constructed_url = format!(